@@ -47,6 +47,35 @@ impl DataChunk {
         &self.arrays
     }
 
+    /// Keep the rows whose visibility is `true`.
+    pub fn filter(&self, visibility: &[bool]) -> Self {
+        assert_eq!(visibility.len(), self.cardinality());
+        self.arrays
+            .iter()
+            .map(|array| {
+                let mut builder = ArrayBuilderImpl::from_type_of_array(array);
+                for (idx, _) in visibility.iter().enumerate().filter(|(_, &v)| v) {
+                    builder.push(&array.get(idx));
+                }
+                builder.finish()
+            })
+            .collect()
+    }
+
+    /// Gather the rows at `indices` in order.
+    pub fn take(&self, indices: &[usize]) -> Self {
+        self.arrays
+            .iter()
+            .map(|array| {
+                let mut builder = ArrayBuilderImpl::from_type_of_array(array);
+                for &idx in indices {
+                    builder.push(&array.get(idx));
+                }
+                builder.finish()
+            })
+            .collect()
+    }
+
     /// Concatenate multiple chunks into one.
     pub fn concat(chunks: &[DataChunk]) -> Self {
         assert!(!chunks.is_empty(), "must concat at least one chunk");
@@ -0,0 +1,72 @@
+//! Check the integrity of the on-disk data of a stopped database or a backup.
+//!
+//! Usage: `risinglight-check <dir>`
+//!
+//! A report of each table is printed. The exit code is 1 if any corruption is found, and 2 if
+//! the directory cannot be checked.
+
+use risinglight_03_02::storage::{DiskStorage, TableReport};
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: risinglight-check <dir>");
+            std::process::exit(2);
+        }
+    };
+    let report = match DiskStorage::check(&path).await {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    for table in &report.tables {
+        let status = if table.errors.is_empty() {
+            "OK"
+        } else {
+            "CORRUPTED"
+        };
+        println!(
+            "table {} (schema {}): {} rowsets, {} rows, {} deleted, {} versions: {}",
+            table.table_id.table_id,
+            table.table_id.schema_id,
+            table.rowsets,
+            table.rows,
+            table.deleted_rows,
+            table.versions,
+            status,
+        );
+        for error in &table.errors {
+            println!("  error: {}", error);
+        }
+        for warning in &table.warnings {
+            println!("  warning: {}", warning);
+        }
+    }
+    for error in &report.errors {
+        println!("error: {}", error);
+    }
+    for warning in &report.warnings {
+        println!("warning: {}", warning);
+    }
+
+    let count = |len: fn(&TableReport) -> usize| report.tables.iter().map(len).sum::<usize>();
+    let errors = report.errors.len() + count(|table| table.errors.len());
+    let warnings = report.warnings.len() + count(|table| table.warnings.len());
+    println!(
+        "checked {} tables in {}: {} errors, {} warnings",
+        report.tables.len(),
+        path,
+        errors,
+        warnings
+    );
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,112 @@
+use super::*;
+use crate::parser::BinaryOperator;
+use crate::types::DataTypeKind;
+
+/// A bound binary operation expression.
+#[derive(PartialEq, Clone)]
+pub struct BoundBinaryOp {
+    pub op: BinaryOperator,
+    pub left_expr: Box<BoundExpr>,
+    pub right_expr: Box<BoundExpr>,
+    pub return_type: Option<DataType>,
+}
+
+impl std::fmt::Debug for BoundBinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({:?} {} {:?})",
+            self.left_expr, self.op, self.right_expr
+        )
+    }
+}
+
+impl Binder {
+    pub fn bind_binary_op(
+        &mut self,
+        left: &Expr,
+        op: &BinaryOperator,
+        right: &Expr,
+    ) -> Result<BoundExpr, BindError> {
+        use BinaryOperator::*;
+
+        let left_expr = self.bind_expr(left)?;
+        let right_expr = self.bind_expr(right)?;
+        let left_kind = left_expr.return_type().map(|ty| ty.kind());
+        let right_kind = right_expr.return_type().map(|ty| ty.kind());
+        let type_mismatch = || {
+            let name = |kind: &Option<DataTypeKind>| match kind {
+                Some(kind) => kind.to_string(),
+                None => "NULL".into(),
+            };
+            BindError::BinaryOpTypeMismatch(name(&left_kind), op.to_string(), name(&right_kind))
+        };
+        match op {
+            And | Or => {
+                for kind in [&left_kind, &right_kind].into_iter().flatten() {
+                    if *kind != DataTypeKind::Boolean {
+                        return Err(type_mismatch());
+                    }
+                }
+            }
+            Eq | NotEq | Gt | GtEq | Lt | LtEq => {
+                if let (Some(left_kind), Some(right_kind)) = (&left_kind, &right_kind) {
+                    if !is_comparable(left_kind, right_kind) {
+                        return Err(type_mismatch());
+                    }
+                }
+            }
+            _ => return Err(BindError::Unsupported(format!("binary operator {}", op))),
+        }
+        // the result is null if any operand is null
+        let nullable = [&left_expr, &right_expr]
+            .iter()
+            .any(|expr| expr.return_type().map_or(true, |ty| ty.is_nullable()));
+        Ok(BoundExpr::BinaryOp(BoundBinaryOp {
+            op: op.clone(),
+            left_expr: left_expr.into(),
+            right_expr: right_expr.into(),
+            return_type: Some(DataType::new(DataTypeKind::Boolean, nullable)),
+        }))
+    }
+
+    /// Bind `expr [NOT] BETWEEN low AND high` as `expr >= low AND expr <= high`, or
+    /// `expr < low OR expr > high` if negated.
+    pub fn bind_between(
+        &mut self,
+        expr: &Expr,
+        negated: bool,
+        low: &Expr,
+        high: &Expr,
+    ) -> Result<BoundExpr, BindError> {
+        use BinaryOperator::*;
+
+        let (low_op, high_op, op) = match negated {
+            false => (GtEq, LtEq, And),
+            true => (Lt, Gt, Or),
+        };
+        let left_expr = self.bind_binary_op(expr, &low_op, low)?;
+        let right_expr = self.bind_binary_op(expr, &high_op, high)?;
+        let nullable = [&left_expr, &right_expr]
+            .iter()
+            .any(|expr| expr.return_type().map_or(true, |ty| ty.is_nullable()));
+        Ok(BoundExpr::BinaryOp(BoundBinaryOp {
+            op,
+            left_expr: left_expr.into(),
+            right_expr: right_expr.into(),
+            return_type: Some(DataType::new(DataTypeKind::Boolean, nullable)),
+        }))
+    }
+}
+
+/// Returns true if values of the two types can be compared with each other.
+fn is_comparable(left: &DataTypeKind, right: &DataTypeKind) -> bool {
+    use DataTypeKind::*;
+    let class = |kind: &DataTypeKind| match kind {
+        Int(_) | Float(_) | Double => 0,
+        Char(_) | Varchar(_) | String => 1,
+        Boolean => 2,
+        _ => 3,
+    };
+    class(left) == class(right)
+}
@@ -0,0 +1,18 @@
+use super::*;
+
+/// A bound input reference expression.
+///
+/// It refers to the column at `index` of the input chunk. The binder never generates it. Instead,
+/// the planner resolves [`BoundColumnRef`]s into input references once the columns produced by
+/// the child plan are known.
+#[derive(PartialEq, Clone)]
+pub struct BoundInputRef {
+    pub index: usize,
+    pub return_type: DataType,
+}
+
+impl std::fmt::Debug for BoundInputRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.index)
+    }
+}
@@ -1,16 +1,22 @@
 use super::*;
 use crate::parser::{Expr, Value};
-use crate::types::{DataType, DataValue};
+use crate::types::{DataType, DataTypeKind, DataValue};
 
+mod binary_op;
 mod column_ref;
+mod input_ref;
 
+pub use self::binary_op::*;
 pub use self::column_ref::*;
+pub use self::input_ref::*;
 
 /// A bound expression.
 #[derive(Debug, PartialEq, Clone)]
 pub enum BoundExpr {
     Constant(DataValue),
     ColumnRef(BoundColumnRef),
+    InputRef(BoundInputRef),
+    BinaryOp(BoundBinaryOp),
 }
 
 impl BoundExpr {
@@ -21,6 +27,44 @@ impl BoundExpr {
         match self {
             Self::Constant(v) => v.datatype(),
             Self::ColumnRef(c) => Some(c.return_type.clone()),
+            Self::InputRef(i) => Some(i.return_type.clone()),
+            Self::BinaryOp(b) => b.return_type.clone(),
+        }
+    }
+
+    /// Collect the ids of all columns referenced by the expression into `ids`.
+    pub fn collect_column_ids(&self, ids: &mut Vec<ColumnId>) {
+        match self {
+            Self::ColumnRef(c) => {
+                if !ids.contains(&c.column_ref_id.column_id) {
+                    ids.push(c.column_ref_id.column_id);
+                }
+            }
+            Self::BinaryOp(b) => {
+                b.left_expr.collect_column_ids(ids);
+                b.right_expr.collect_column_ids(ids);
+            }
+            Self::Constant(_) | Self::InputRef(_) => {}
+        }
+    }
+
+    /// Resolve all column references into input references, where the input columns are
+    /// `column_ids`.
+    pub fn resolve_input_ref(self, column_ids: &[ColumnId]) -> Self {
+        match self {
+            Self::ColumnRef(c) => Self::InputRef(BoundInputRef {
+                index: column_ids
+                    .iter()
+                    .position(|&id| id == c.column_ref_id.column_id)
+                    .expect("column is not in the input"),
+                return_type: c.return_type,
+            }),
+            Self::BinaryOp(b) => Self::BinaryOp(BoundBinaryOp {
+                left_expr: b.left_expr.resolve_input_ref(column_ids).into(),
+                right_expr: b.right_expr.resolve_input_ref(column_ids).into(),
+                ..b
+            }),
+            expr => expr,
         }
     }
 }
@@ -32,9 +76,26 @@ impl Binder {
             Expr::Value(v) => Ok(BoundExpr::Constant(v.into())),
             Expr::Identifier(ident) => self.bind_column_ref(std::slice::from_ref(ident)),
             Expr::CompoundIdentifier(idents) => self.bind_column_ref(idents),
+            Expr::BinaryOp { left, op, right } => self.bind_binary_op(left, op, right),
+            Expr::Nested(expr) => self.bind_expr(expr),
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => self.bind_between(expr, *negated, low, high),
             _ => todo!("bind expression: {:?}", expr),
         }
     }
+
+    /// Bind a condition, e.g. a `WHERE` clause, which must be a boolean expression.
+    pub fn bind_condition(&mut self, expr: &Expr) -> Result<BoundExpr, BindError> {
+        let expr = self.bind_expr(expr)?;
+        match expr.return_type() {
+            Some(ty) if ty.kind() != DataTypeKind::Boolean => Err(BindError::InvalidCondition),
+            _ => Ok(expr),
+        }
+    }
 }
 
 impl From<&Value> for DataValue {
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use std::vec::Vec;
 
 use crate::catalog::*;
-use crate::parser::{Ident, ObjectName, Statement};
+use crate::parser::{Ident, ObjectName, ParsedStatement, Statement, Value};
 
 mod expression;
 mod statement;
@@ -19,6 +19,10 @@ pub use self::table_ref::*;
 pub enum BoundStatement {
     CreateTable(BoundCreateTable),
     Insert(BoundInsert),
+    Delete(BoundDelete),
+    DropTable(BoundDropTable),
+    Truncate(BoundTruncate),
+    Backup(BoundBackup),
     Explain(Box<BoundStatement>),
     Select(BoundSelect),
 }
@@ -50,6 +54,14 @@ pub enum BindError {
     TupleLengthMismatch { expected: usize, actual: usize },
     #[error("value should not be null in column: {0}")]
     NullValueInColumn(String),
+    #[error("type mismatch in binary operation: {0} {1} {2}")]
+    BinaryOpTypeMismatch(String, String, String),
+    #[error("condition should be a boolean expression")]
+    InvalidCondition,
+    #[error("AS OF should be followed by an epoch or a timestamp: {0}")]
+    InvalidAsOf(String),
+    #[error("not supported: {0}")]
+    Unsupported(String),
 }
 
 /// The binder resolves all expressions referring to schema objects such as
@@ -57,6 +69,8 @@ pub enum BindError {
 pub struct Binder {
     catalog: CatalogRef,
     tables: HashMap<TableName, TableRefId>,
+    /// The `AS OF` clauses of the statement, which are removed once their tables are bound.
+    as_of: HashMap<TableName, Value>,
 }
 
 type TableName = String;
@@ -67,19 +81,40 @@ impl Binder {
         Binder {
             catalog,
             tables: HashMap::default(),
+            as_of: HashMap::default(),
         }
     }
 
     /// Bind a statement.
-    pub fn bind(&mut self, stmt: &Statement) -> Result<BoundStatement, BindError> {
+    pub fn bind(&mut self, stmt: &ParsedStatement) -> Result<BoundStatement, BindError> {
+        match stmt {
+            ParsedStatement::Sql { stmt, as_of } => {
+                self.as_of = as_of.clone();
+                let bound = self.bind_statement(stmt)?;
+                match self.as_of.keys().next() {
+                    Some(table) => Err(BindError::Unsupported(format!(
+                        "AS OF of {}, which is not a table in FROM",
+                        table
+                    ))),
+                    None => Ok(bound),
+                }
+            }
+            ParsedStatement::Backup { dir } => Ok(BoundStatement::Backup(self.bind_backup(dir)?)),
+        }
+    }
+
+    fn bind_statement(&mut self, stmt: &Statement) -> Result<BoundStatement, BindError> {
         match stmt {
             Statement::CreateTable { .. } => {
                 Ok(BoundStatement::CreateTable(self.bind_create_table(stmt)?))
             }
             Statement::Insert { .. } => Ok(BoundStatement::Insert(self.bind_insert(stmt)?)),
-            Statement::Explain { statement, .. } => {
-                Ok(BoundStatement::Explain(self.bind(&*statement)?.into()))
-            }
+            Statement::Delete { .. } => Ok(BoundStatement::Delete(self.bind_delete(stmt)?)),
+            Statement::Drop { .. } => Ok(BoundStatement::DropTable(self.bind_drop_table(stmt)?)),
+            Statement::Truncate { .. } => Ok(BoundStatement::Truncate(self.bind_truncate(stmt)?)),
+            Statement::Explain { statement, .. } => Ok(BoundStatement::Explain(
+                self.bind_statement(&*statement)?.into(),
+            )),
             Statement::Query(query) => Ok(BoundStatement::Select(self.bind_select(&*query)?)),
             _ => todo!("bind statement: {:#?}", stmt),
         }
@@ -0,0 +1,15 @@
+use super::*;
+
+/// A bound `BACKUP TO` statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundBackup {
+    /// The directory to write the backup.
+    pub dir: String,
+}
+
+impl Binder {
+    /// Bind `BACKUP TO '<dir>'`.
+    pub fn bind_backup(&mut self, dir: &str) -> Result<BoundBackup, BindError> {
+        Ok(BoundBackup { dir: dir.into() })
+    }
+}
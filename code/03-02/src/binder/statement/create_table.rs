@@ -77,7 +77,7 @@ mod tests {
 
     use super::*;
     use crate::catalog::DatabaseCatalog;
-    use crate::parser::parse;
+    use crate::parser::{parse, ParsedStatement};
     use crate::types::{DataTypeExt, DataTypeKind};
 
     #[test]
@@ -88,7 +88,12 @@ mod tests {
             create table t1 (v1 int not null, v2 int); 
             create table t2 (a int not null, a int not null);
             create table t3 (v1 int not null);";
-        let stmts = parse(sql).unwrap();
+        let stmts = (parse(sql).unwrap().into_iter())
+            .map(|stmt| match stmt {
+                ParsedStatement::Sql { stmt, .. } => stmt,
+                _ => panic!("not a SQL statement"),
+            })
+            .collect::<Vec<_>>();
 
         assert_eq!(
             binder.bind_create_table(&stmts[0]).unwrap(),
@@ -0,0 +1,34 @@
+use super::*;
+use crate::parser::Statement;
+
+/// A bound `DELETE` statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundDelete {
+    pub table_ref_id: TableRefId,
+    /// The condition of rows to delete. All rows are deleted if it is `None`.
+    pub where_clause: Option<BoundExpr>,
+}
+
+impl Binder {
+    pub fn bind_delete(&mut self, stmt: &Statement) -> Result<BoundDelete, BindError> {
+        let (table_name, selection) = match stmt {
+            Statement::Delete {
+                table_name,
+                selection,
+            } => (table_name, selection),
+            _ => panic!("mismatched statement type"),
+        };
+        let (table_ref_id, _, _) = self.bind_table_columns(table_name, &[])?;
+        let (_, name) = split_name(table_name)?;
+        self.tables.insert(name.into(), table_ref_id);
+
+        let where_clause = match selection {
+            Some(expr) => Some(self.bind_condition(expr)?),
+            None => None,
+        };
+        Ok(BoundDelete {
+            table_ref_id,
+            where_clause,
+        })
+    }
+}
@@ -0,0 +1,39 @@
+use super::*;
+use crate::parser::{ObjectType, Statement};
+
+/// A bound `DROP TABLE` statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundDropTable {
+    /// The tables to drop, excluding the tables not found with `IF EXISTS`.
+    pub table_ref_ids: Vec<TableRefId>,
+}
+
+impl Binder {
+    pub fn bind_drop_table(&mut self, stmt: &Statement) -> Result<BoundDropTable, BindError> {
+        let (if_exists, names) = match stmt {
+            Statement::Drop {
+                object_type: ObjectType::Table,
+                if_exists,
+                names,
+                ..
+            } => (*if_exists, names),
+            Statement::Drop { object_type, .. } => {
+                return Err(BindError::Unsupported(format!("DROP {}", object_type)))
+            }
+            _ => panic!("mismatched statement type"),
+        };
+        let mut table_ref_ids = vec![];
+        for name in names {
+            match self.bind_table_columns(name, &[]) {
+                Ok((table_ref_id, _, _)) => {
+                    if !table_ref_ids.contains(&table_ref_id) {
+                        table_ref_ids.push(table_ref_id);
+                    }
+                }
+                Err(BindError::SchemaNotFound(_) | BindError::TableNotFound(_)) if if_exists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(BoundDropTable { table_ref_ids })
+    }
+}
@@ -1,9 +1,17 @@
 use super::*;
 
+mod backup;
 mod create_table;
+mod delete;
+mod drop_table;
 mod insert;
 mod select;
+mod truncate;
 
+pub use self::backup::*;
 pub use self::create_table::*;
+pub use self::delete::*;
+pub use self::drop_table::*;
 pub use self::insert::*;
 pub use self::select::*;
+pub use self::truncate::*;
@@ -7,6 +7,7 @@ use crate::parser::{Query, SelectItem, SetExpr};
 pub struct BoundSelect {
     pub select_list: Vec<BoundExpr>,
     pub from_list: Vec<BoundTableRef>,
+    pub where_clause: Option<BoundExpr>,
 }
 
 impl Binder {
@@ -23,7 +24,10 @@ impl Binder {
             from_list.push(table_ref);
         }
 
-        assert!(select.selection.is_none(), "WHERE clause is not supported");
+        let where_clause = match &select.selection {
+            Some(expr) => Some(self.bind_condition(expr)?),
+            None => None,
+        };
         assert!(
             query.order_by.is_empty(),
             "ORDER BY clause is not supported"
@@ -56,6 +60,7 @@ impl Binder {
         Ok(BoundSelect {
             select_list,
             from_list,
+            where_clause,
         })
     }
 }
@@ -0,0 +1,19 @@
+use super::*;
+use crate::parser::Statement;
+
+/// A bound `TRUNCATE TABLE` statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoundTruncate {
+    pub table_ref_id: TableRefId,
+}
+
+impl Binder {
+    pub fn bind_truncate(&mut self, stmt: &Statement) -> Result<BoundTruncate, BindError> {
+        let table_name = match stmt {
+            Statement::Truncate { table_name, .. } => table_name,
+            _ => panic!("mismatched statement type"),
+        };
+        let (table_ref_id, _, _) = self.bind_table_columns(table_name, &[])?;
+        Ok(BoundTruncate { table_ref_id })
+    }
+}
@@ -1,11 +1,16 @@
 use super::*;
-use crate::parser::{TableFactor, TableWithJoins};
+use crate::parser::{TableFactor, TableWithJoins, Value};
+use crate::storage::AsOf;
 
 /// A bound table reference.
 #[derive(Debug, PartialEq, Clone)]
 pub struct BoundTableRef {
     pub table_ref_id: TableRefId,
     pub column_ids: Vec<ColumnId>,
+    /// The primary key column, by which the rows are sorted in storage.
+    pub primary_key: Option<ColumnId>,
+    /// The version of the table to read. The latest version is read if it is `None`.
+    pub as_of: Option<AsOf>,
 }
 
 impl Binder {
@@ -28,9 +33,95 @@ impl Binder {
             return Err(BindError::DuplicatedAlias(alias.into()));
         }
         self.tables.insert(alias.into(), table_ref_id);
+        let as_of = self.bind_as_of(alias)?;
         Ok(BoundTableRef {
             table_ref_id,
             column_ids: columns.iter().map(|col| col.id()).collect(),
+            primary_key: columns
+                .iter()
+                .find(|col| col.is_primary())
+                .map(|col| col.id()),
+            as_of,
         })
     }
+
+    /// Bind `AS OF <epoch>` or `AS OF '<timestamp>'` of the table with `alias`.
+    fn bind_as_of(&mut self, alias: &str) -> Result<Option<AsOf>, BindError> {
+        let value = match self.as_of.remove(alias) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let as_of = match &value {
+            Value::Number(n, _) => n.parse().ok().map(AsOf::Epoch),
+            Value::SingleQuotedString(s) => parse_timestamp(s).map(AsOf::Timestamp),
+            _ => None,
+        };
+        match as_of {
+            Some(as_of) => Ok(Some(as_of)),
+            None => Err(BindError::InvalidAsOf(value.to_string())),
+        }
+    }
+}
+
+/// Parse a timestamp `YYYY-MM-DD[ HH:MM:SS[.fff]]` in UTC into milliseconds since the Unix epoch.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (date, time) = s.trim().split_once(' ').unwrap_or((s.trim(), "00:00:00"));
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let numbers = |s: &str, sep| {
+        s.split(sep)
+            .map(|x| x.parse().ok())
+            .collect::<Option<Vec<i64>>>()
+    };
+    let (year, month, day, hour, minute, second) = match (
+        numbers(date, '-')?.as_slice(),
+        numbers(time, ':')?.as_slice(),
+    ) {
+        (&[y, m, d], &[h, mi, s]) => (y, m, d, h, mi, s),
+        _ => return None,
+    };
+    let valid = year >= 1970
+        && (1..=12).contains(&month)
+        && (1..=31).contains(&day)
+        && (0..24).contains(&hour)
+        && (0..60).contains(&minute)
+        && (0..60).contains(&second)
+        && !fraction.is_empty()
+        && fraction.bytes().all(|b| b.is_ascii_digit());
+    if !valid {
+        return None;
+    }
+    let millis: i64 = format!("{:0<3.3}", fraction).parse().ok()?;
+
+    // days since 1970-01-01 in the proleptic Gregorian calendar, counting years from March
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let days = year * 365 + year / 4 - year / 100 + year / 400 + day_of_year - 719468;
+    let seconds = ((days * 24 + hour) * 60 + minute) * 60 + second;
+    u64::try_from(seconds * 1000 + millis).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+        assert_eq!(parse_timestamp("1970-01-02 00:00:01.5"), Some(86_401_500));
+        assert_eq!(
+            parse_timestamp("2000-03-01 00:00:00"),
+            Some(951_868_800_000)
+        );
+        assert_eq!(
+            parse_timestamp("2022-02-03 04:05:06.789"),
+            Some(1_643_861_106_789)
+        );
+        assert_eq!(parse_timestamp("2022-13-01"), None);
+        assert_eq!(parse_timestamp("2022-01-01 00:00"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
 }
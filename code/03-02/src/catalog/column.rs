@@ -1,8 +1,10 @@
+use serde::{Deserialize, Serialize};
+
 use super::*;
 use crate::types::DataType;
 
 /// The descriptor of a column.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ColumnDesc {
     datatype: DataType,
     is_primary: bool,
@@ -40,7 +42,7 @@ impl DataType {
 }
 
 /// The catalog of a column.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ColumnCatalog {
     id: ColumnId,
     name: String,
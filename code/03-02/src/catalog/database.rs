@@ -1,11 +1,19 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
 use super::*;
 
 /// The catalog of a database.
 pub struct DatabaseCatalog {
     inner: Mutex<Inner>,
+    /// Serializes the writes of the catalog, so that snapshots are written in order.
+    write_lock: tokio::sync::Mutex<()>,
+    /// The file to persist the catalog. If it is none, the catalog lives only in memory.
+    path: Option<PathBuf>,
 }
 
 #[derive(Default)]
@@ -15,6 +23,13 @@ struct Inner {
     next_schema_id: SchemaId,
 }
 
+/// The persistent form of [`DatabaseCatalog`].
+#[derive(Serialize, Deserialize)]
+struct DatabaseSnapshot {
+    schemas: Vec<SchemaSnapshot>,
+    next_schema_id: SchemaId,
+}
+
 impl Default for DatabaseCatalog {
     fn default() -> Self {
         Self::new()
@@ -25,11 +40,72 @@ impl DatabaseCatalog {
     pub fn new() -> Self {
         let db_catalog = DatabaseCatalog {
             inner: Mutex::new(Inner::default()),
+            write_lock: tokio::sync::Mutex::new(()),
+            path: None,
         };
         db_catalog.add_schema(DEFAULT_SCHEMA_NAME).unwrap();
         db_catalog
     }
 
+    /// Open the catalog persisted at `path`, or create a new one if it does not exist.
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self, CatalogError> {
+        let path = path.into();
+        if !path.exists() {
+            let db_catalog = DatabaseCatalog {
+                path: Some(path),
+                ..Self::new()
+            };
+            db_catalog.persist().await?;
+            return Ok(db_catalog);
+        }
+        let snapshot: DatabaseSnapshot =
+            serde_json::from_slice(&tokio::fs::read(&path).await?).map_err(std::io::Error::from)?;
+        let schemas = (snapshot.schemas.into_iter())
+            .map(|s| Arc::new(SchemaCatalog::from_snapshot(s)))
+            .collect::<Vec<_>>();
+        Ok(DatabaseCatalog {
+            inner: Mutex::new(Inner {
+                schema_idxs: schemas.iter().map(|s| (s.name(), s.id())).collect(),
+                schemas: schemas.into_iter().map(|s| (s.id(), s)).collect(),
+                next_schema_id: snapshot.next_schema_id,
+            }),
+            write_lock: tokio::sync::Mutex::new(()),
+            path: Some(path),
+        })
+    }
+
+    /// Write the whole catalog to disk.
+    pub async fn persist(&self) -> Result<(), CatalogError> {
+        match &self.path {
+            Some(path) => self.write_to(path).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Write the whole catalog to `path`, which is also used to back up the catalog.
+    ///
+    /// The snapshot is written to a temporary file and then renamed, so that a crash
+    /// leaves either the old or the new catalog.
+    pub async fn write_to(&self, path: &Path) -> Result<(), CatalogError> {
+        // take the snapshot under the lock and hold it until the file is replaced, so that
+        // snapshots are written in order
+        let _guard = self.write_lock.lock().await;
+        let data = {
+            let inner = self.inner.lock().unwrap();
+            let snapshot = DatabaseSnapshot {
+                schemas: inner.schemas.values().map(|s| s.snapshot()).collect(),
+                next_schema_id: inner.next_schema_id,
+            };
+            serde_json::to_vec(&snapshot).map_err(std::io::Error::from)?
+        };
+        let tmp_path = path.with_extension("tmp");
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(&data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
     pub fn add_schema(&self, name: &str) -> Result<SchemaId, CatalogError> {
         let mut inner = self.inner.lock().unwrap();
         if inner.schema_idxs.contains_key(name) {
@@ -77,3 +153,51 @@ impl DatabaseCatalog {
         schema.get_table(table_ref_id.table_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.json");
+
+        let catalog = DatabaseCatalog::open(&path).await.unwrap();
+        let schema = catalog.get_schema_by_name(DEFAULT_SCHEMA_NAME).unwrap();
+        schema.add_table("t0").unwrap();
+        let table_id = schema.add_table("t1").unwrap();
+        schema.del_table_by_name("t0").unwrap();
+        let table = schema.get_table(table_id).unwrap();
+        table
+            .add_column(
+                "a",
+                DataTypeKind::Int(None).not_null().to_column_primary_key(),
+            )
+            .unwrap();
+        table
+            .add_column("b", DataTypeKind::Varchar(None).nullable().to_column())
+            .unwrap();
+        catalog.persist().await.unwrap();
+        drop(catalog);
+
+        let catalog = DatabaseCatalog::open(&path).await.unwrap();
+        let schema = catalog.get_schema_by_name(DEFAULT_SCHEMA_NAME).unwrap();
+        assert!(schema.get_table_by_name("t0").is_none());
+        let table = schema.get_table_by_name("t1").unwrap();
+        assert_eq!(table.id(), table_id);
+        let a = table.get_column_by_name("a").unwrap();
+        assert_eq!(a.id(), 0);
+        assert!(a.is_primary());
+        assert!(!a.is_nullable());
+        let b = table.get_column_by_name("b").unwrap();
+        assert_eq!(b.id(), 1);
+        assert!(b.is_nullable());
+
+        // ids are never reused
+        assert_eq!(schema.add_table("t2").unwrap(), 2);
+        assert_eq!(table.add_column("c", b.desc().clone()).unwrap(), 2);
+        assert_eq!(catalog.add_schema("s").unwrap(), 1);
+    }
+}
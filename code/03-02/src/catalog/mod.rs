@@ -11,6 +11,8 @@
 
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 mod column;
 mod database;
 mod schema;
@@ -34,7 +36,7 @@ pub type ColumnId = u32;
 pub const DEFAULT_SCHEMA_NAME: &str = "postgres";
 
 /// The reference ID of a table.
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 pub struct TableRefId {
     pub schema_id: SchemaId,
     pub table_id: TableId,
@@ -82,4 +84,6 @@ pub enum CatalogError {
     NotFound(&'static str, String),
     #[error("duplicated {0}: {1}")]
     Duplicated(&'static str, String),
+    #[error("failed to persist catalog: {0}")]
+    Io(#[from] std::io::Error),
 }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+use serde::{Deserialize, Serialize};
+
 use super::*;
 
 /// The catalog of a schema.
@@ -16,6 +18,15 @@ struct Inner {
     next_table_id: TableId,
 }
 
+/// The persistent form of [`SchemaCatalog`].
+#[derive(Serialize, Deserialize)]
+pub(super) struct SchemaSnapshot {
+    id: SchemaId,
+    name: String,
+    tables: Vec<TableSnapshot>,
+    next_table_id: TableId,
+}
+
 impl SchemaCatalog {
     pub(super) fn new(id: SchemaId, name: String) -> SchemaCatalog {
         SchemaCatalog {
@@ -29,6 +40,31 @@ impl SchemaCatalog {
         }
     }
 
+    pub(super) fn from_snapshot(snapshot: SchemaSnapshot) -> SchemaCatalog {
+        let tables = (snapshot.tables.into_iter())
+            .map(|t| Arc::new(TableCatalog::from_snapshot(t)))
+            .collect::<Vec<_>>();
+        SchemaCatalog {
+            id: snapshot.id,
+            inner: Mutex::new(Inner {
+                name: snapshot.name,
+                table_idxs: tables.iter().map(|t| (t.name(), t.id())).collect(),
+                tables: tables.into_iter().map(|t| (t.id(), t)).collect(),
+                next_table_id: snapshot.next_table_id,
+            }),
+        }
+    }
+
+    pub(super) fn snapshot(&self) -> SchemaSnapshot {
+        let inner = self.inner.lock().unwrap();
+        SchemaSnapshot {
+            id: self.id,
+            name: inner.name.clone(),
+            tables: inner.tables.values().map(|t| t.snapshot()).collect(),
+            next_table_id: inner.next_table_id,
+        }
+    }
+
     pub fn id(&self) -> SchemaId {
         self.id
     }
@@ -1,6 +1,8 @@
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 
+use serde::{Deserialize, Serialize};
+
 use super::*;
 
 /// The catalog of a table.
@@ -17,6 +19,15 @@ struct Inner {
     next_column_id: ColumnId,
 }
 
+/// The persistent form of [`TableCatalog`].
+#[derive(Serialize, Deserialize)]
+pub(super) struct TableSnapshot {
+    id: TableId,
+    name: String,
+    columns: Vec<ColumnCatalog>,
+    next_column_id: ColumnId,
+}
+
 impl TableCatalog {
     pub(super) fn new(id: TableId, name: String) -> TableCatalog {
         TableCatalog {
@@ -30,6 +41,32 @@ impl TableCatalog {
         }
     }
 
+    pub(super) fn from_snapshot(snapshot: TableSnapshot) -> TableCatalog {
+        TableCatalog {
+            id: snapshot.id,
+            inner: Mutex::new(Inner {
+                name: snapshot.name,
+                column_idxs: (snapshot.columns.iter())
+                    .map(|c| (c.name().into(), c.id()))
+                    .collect(),
+                columns: (snapshot.columns.into_iter())
+                    .map(|c| (c.id(), c))
+                    .collect(),
+                next_column_id: snapshot.next_column_id,
+            }),
+        }
+    }
+
+    pub(super) fn snapshot(&self) -> TableSnapshot {
+        let inner = self.inner.lock().unwrap();
+        TableSnapshot {
+            id: self.id,
+            name: inner.name.clone(),
+            columns: inner.columns.values().cloned().collect(),
+            next_column_id: inner.next_column_id,
+        }
+    }
+
     pub fn id(&self) -> TableId {
         self.id
     }
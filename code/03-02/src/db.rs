@@ -1,31 +1,51 @@
 //! Top-level structure of the database.
 
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use futures::TryStreamExt;
 use tokio::runtime::Runtime;
 
 use crate::array::DataChunk;
 use crate::binder::{BindError, Binder};
-use crate::catalog::{CatalogRef, DatabaseCatalog};
+use crate::catalog::{CatalogError, CatalogRef, DatabaseCatalog, TableRefId};
 use crate::executor::{ExecuteError, ExecutorBuilder};
 use crate::logical_planner::{LogicalPlanError, LogicalPlanner};
-use crate::parser::{parse, ParserError};
+use crate::parser::{parse, ParsedStatement, ParserError, Statement};
 use crate::physical_planner::{PhysicalPlanError, PhysicalPlanner};
-use crate::storage::{DiskStorage, StorageOptions};
+use crate::storage::{
+    DiskStorage, InMemoryStorage, SessionTxn, SessionTxnRef, StorageError, StorageOptions,
+    StorageRef,
+};
+
+/// The name of the catalog file under the base path.
+pub const CATALOG_FILE_NAME: &str = "catalog.json";
+
+/// The base path to open a database in memory, whose data is lost once it is dropped.
+pub const IN_MEMORY_PATH: &str = ":memory:";
 
 /// The database instance.
+///
+/// Statements are executed in a session. Each statement runs in its own transaction, unless a
+/// transaction is started by `BEGIN` and ended by `COMMIT` or `ROLLBACK`. `DROP TABLE` and
+/// `TRUNCATE TABLE` are not allowed in such a transaction.
 pub struct Database {
     catalog: CatalogRef,
-    executor_builder: ExecutorBuilder,
+    storage: StorageRef,
     runtime: Runtime,
+    /// An optional runtime handle to run executors in parallel.
+    handle: Option<tokio::runtime::Handle>,
+    /// The maximum number of morsels read in parallel by a scan.
+    scan_parallelism: usize,
+    /// The transaction started by `BEGIN`.
+    txn: Mutex<Option<SessionTxnRef>>,
 }
 
 impl Database {
-    /// Create a new database instance.
-    pub fn new(options: StorageOptions) -> Self {
-        let catalog = Arc::new(DatabaseCatalog::new());
-        let storage = Arc::new(DiskStorage::new(options));
+    /// Open a database instance, recovering existing data from `options.base_path`.
+    ///
+    /// If the base path is [`IN_MEMORY_PATH`], the database is opened in memory.
+    pub fn new(options: StorageOptions) -> Result<Self, Error> {
         let parallel = matches!(std::env::var("LIGHT_PARALLEL"), Ok(s) if s == "1");
         let runtime = if parallel {
             tokio::runtime::Builder::new_multi_thread()
@@ -34,12 +54,69 @@ impl Database {
         }
         .build()
         .expect("failed to create tokio runtime");
+        let scan_parallelism = options.scan_parallelism;
+        let storage: StorageRef;
+        let catalog;
+        if options.base_path == Path::new(IN_MEMORY_PATH) {
+            storage = Arc::new(InMemoryStorage::new());
+            catalog = Arc::new(DatabaseCatalog::new());
+        } else {
+            let catalog_path = options.base_path.join(CATALOG_FILE_NAME);
+            let disk_storage = Arc::new(runtime.block_on(DiskStorage::open(options))?);
+            runtime.block_on(async { disk_storage.spawn_compaction_task() });
+            storage = disk_storage;
+            catalog = Arc::new(runtime.block_on(DatabaseCatalog::open(catalog_path))?);
+        }
+
+        // A table may have been persisted in catalog but not yet created in storage.
+        for schema in catalog.all_schemas().values() {
+            for table in schema.all_tables().values() {
+                let id = TableRefId::new(schema.id(), table.id());
+                if storage.get_table(id).is_err() {
+                    let column_descs = (table.all_columns().values())
+                        .map(|c| c.desc().clone())
+                        .collect::<Vec<_>>();
+                    runtime.block_on(storage.add_table(id, &column_descs))?;
+                }
+            }
+        }
+
+        // A table may have been dropped from catalog but not yet from storage.
+        for id in storage.table_ids() {
+            if catalog.get_table(id).is_none() {
+                runtime.block_on(storage.drop_table(id))?;
+            }
+        }
+
         let handle = parallel.then(|| runtime.handle().clone());
-        Database {
-            catalog: catalog.clone(),
-            executor_builder: ExecutorBuilder::new(catalog, storage, handle),
+        Ok(Database {
+            catalog,
+            storage,
             runtime,
+            handle,
+            scan_parallelism,
+            txn: Mutex::new(None),
+        })
+    }
+
+    /// Restore the backup written by `BACKUP TO` at `backup_path` into `options.base_path`, which
+    /// should not exist or be empty, and open it as a new database.
+    ///
+    /// The backup is copied, so that it is not changed by the new database.
+    pub fn restore(backup_path: impl AsRef<Path>, options: StorageOptions) -> Result<Self, Error> {
+        let backup_path = backup_path.as_ref();
+        let base_path = &options.base_path;
+        if base_path
+            .read_dir()
+            .map_or(false, |mut entries| entries.next().is_some())
+        {
+            return Err(Error::Restore(format!("{:?} is not empty", base_path)));
+        }
+        if !backup_path.join(CATALOG_FILE_NAME).exists() {
+            return Err(Error::Restore(format!("{:?} is not a backup", backup_path)));
         }
+        copy_dir(backup_path, base_path)?;
+        Self::new(options)
     }
 
     /// Run SQL queries and return the outputs.
@@ -49,26 +126,112 @@ impl Database {
 
         let mut outputs = vec![];
         for stmt in stmts {
-            let mut binder = Binder::new(self.catalog.clone());
-            let logical_planner = LogicalPlanner::default();
-            let physical_planner = PhysicalPlanner::default();
-
-            let bound_stmt = binder.bind(&stmt)?;
-            debug!("{:#?}", bound_stmt);
-            let logical_plan = logical_planner.plan(bound_stmt)?;
-            debug!("{:#?}", logical_plan);
-            let physical_plan = physical_planner.plan(&logical_plan)?;
-            debug!("{:#?}", physical_plan);
-            let mut executor = self.executor_builder.build(physical_plan);
-            self.runtime.block_on(async {
-                while let Some(chunk) = executor.try_next().await? {
-                    outputs.push(chunk);
+            let sql_stmt = match &stmt {
+                ParsedStatement::Sql { stmt, .. } => Some(stmt),
+                ParsedStatement::Backup { .. } => None,
+            };
+            match sql_stmt {
+                Some(Statement::StartTransaction { .. }) => {
+                    let mut txn = self.txn.lock().unwrap();
+                    if txn.is_some() {
+                        return Err(Error::Transaction("a transaction is already in progress"));
+                    }
+                    *txn = Some(Arc::new(SessionTxn::new(self.storage.clone())));
+                    continue;
                 }
-                Ok(()) as Result<(), Error>
-            })?;
+                Some(Statement::Commit { .. }) => {
+                    let txn = self.take_txn()?;
+                    self.runtime.block_on(txn.commit())?;
+                    continue;
+                }
+                Some(Statement::Rollback { .. }) => {
+                    let txn = self.take_txn()?;
+                    self.runtime.block_on(txn.rollback());
+                    continue;
+                }
+                _ => {}
+            }
+            let explicit_txn = self.txn.lock().unwrap().clone();
+            let txn = match &explicit_txn {
+                Some(txn) => txn.clone(),
+                None => Arc::new(SessionTxn::new(self.storage.clone())),
+            };
+            // DROP and TRUNCATE take effect at once, so they cannot be undone by a transaction
+            let result = match (&explicit_txn, sql_stmt) {
+                (Some(_), Some(Statement::Drop { .. } | Statement::Truncate { .. })) => Err(
+                    Error::Transaction("DROP and TRUNCATE cannot run in a transaction"),
+                ),
+                _ => self.run_statement(&stmt, &txn, &mut outputs),
+            };
+            if let Err(e) = result {
+                // a failed statement rolls back the whole transaction
+                if explicit_txn.is_some() {
+                    self.txn.lock().unwrap().take();
+                }
+                self.runtime.block_on(txn.rollback());
+                return Err(e);
+            }
+            if explicit_txn.is_none() {
+                self.runtime.block_on(txn.commit())?;
+            }
         }
         Ok(outputs)
     }
+
+    /// End the transaction started by `BEGIN`.
+    fn take_txn(&self) -> Result<SessionTxnRef, Error> {
+        (self.txn.lock().unwrap().take()).ok_or(Error::Transaction("no transaction is in progress"))
+    }
+
+    /// Run a statement in `txn` and append the outputs to `outputs`.
+    fn run_statement(
+        &self,
+        stmt: &ParsedStatement,
+        txn: &SessionTxnRef,
+        outputs: &mut Vec<DataChunk>,
+    ) -> Result<(), Error> {
+        let mut binder = Binder::new(self.catalog.clone());
+        let logical_planner = LogicalPlanner::default();
+        let physical_planner = PhysicalPlanner::default();
+
+        let bound_stmt = binder.bind(stmt)?;
+        debug!("{:#?}", bound_stmt);
+        let logical_plan = logical_planner.plan(bound_stmt)?;
+        debug!("{:#?}", logical_plan);
+        let physical_plan = physical_planner.plan(&logical_plan)?;
+        debug!("{:#?}", physical_plan);
+        let executor_builder = ExecutorBuilder::new(
+            self.catalog.clone(),
+            self.storage.clone(),
+            txn.clone(),
+            self.handle.clone(),
+            self.scan_parallelism,
+        );
+        let mut executor = executor_builder.build(physical_plan);
+        self.runtime.block_on(async {
+            while let Some(chunk) = executor.try_next().await? {
+                outputs.push(chunk);
+            }
+            Ok(()) as Result<(), Error>
+        })?;
+        Ok(())
+    }
+}
+
+/// Copy the directory `from` into `to` recursively, and sync the copies to disk.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &path)?;
+        } else {
+            std::fs::copy(entry.path(), &path)?;
+            std::fs::File::open(&path)?.sync_all()?;
+        }
+    }
+    std::fs::File::open(to)?.sync_all()
 }
 
 /// The error type of database operations.
@@ -84,4 +247,14 @@ pub enum Error {
     PhysicalPlan(#[from] PhysicalPlanError),
     #[error("execute error: {0}")]
     Execute(#[from] ExecuteError),
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("catalog error: {0}")]
+    Catalog(#[from] CatalogError),
+    #[error("transaction error: {0}")]
+    Transaction(&'static str),
+    #[error("restore error: {0}")]
+    Restore(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
 }
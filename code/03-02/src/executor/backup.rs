@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use super::*;
+use crate::db::CATALOG_FILE_NAME;
+
+/// The executor of `BACKUP TO` statement.
+pub struct BackupExecutor {
+    pub dir: PathBuf,
+    pub catalog: CatalogRef,
+    pub storage: StorageRef,
+}
+
+impl BackupExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
+    pub async fn execute(self) {
+        self.storage.backup(&self.dir).await?;
+        // back up the catalog after the storage, so that the tables created or dropped in between
+        // are reconciled with the storage by `Database::new` once the backup is opened
+        self.catalog
+            .write_to(&self.dir.join(CATALOG_FILE_NAME))
+            .await?;
+        yield DataChunk::single(1);
+    }
+}
@@ -20,10 +20,22 @@ impl CreateTableExecutor {
             table.add_column(name, desc.clone()).unwrap();
             column_descs.push(desc.clone());
         }
-        self.storage.add_table(
-            TableRefId::new(self.plan.schema_id, table_id),
-            &column_descs,
-        )?;
+        // persist the catalog before creating the table in storage, so that a crash in between
+        // can be recovered by `Database::new`
+        self.catalog.persist().await?;
+        let result = self
+            .storage
+            .add_table(
+                TableRefId::new(self.plan.schema_id, table_id),
+                &column_descs,
+            )
+            .await;
+        if result.is_err() {
+            // the storage rejects the table, e.g. if it does not support the type of a column
+            schema.del_table(table_id);
+            self.catalog.persist().await?;
+        }
+        result?;
         yield DataChunk::single(1);
     }
 }
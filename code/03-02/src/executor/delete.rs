@@ -0,0 +1,44 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::array::DataChunk;
+use crate::binder::BoundExpr;
+use crate::catalog::{ColumnId, TableRefId};
+use crate::types::DataValue;
+
+/// The executor of `DELETE` statement.
+pub struct DeleteExecutor {
+    pub table_ref_id: TableRefId,
+    pub column_ids: Vec<ColumnId>,
+    pub condition: Option<BoundExpr>,
+    pub txn: SessionTxnRef,
+}
+
+impl DeleteExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
+    pub async fn execute(self) {
+        let mut count = 0;
+
+        // The rows are found and deleted in the snapshot of the same transaction.
+        let mut txn = self.txn.table_txn(self.table_ref_id, true).await?;
+        let mut iter = txn.iter(&self.column_ids).await?;
+        while let Some((chunk, handles)) =
+            iter.next_batch_with_handles(PROCESSING_WINDOW_SIZE).await?
+        {
+            let handles = match &self.condition {
+                Some(condition) => {
+                    let array = condition.eval_array(&chunk)?;
+                    (handles.into_iter().enumerate())
+                        .filter(|(idx, _)| array.get(*idx) == DataValue::Bool(true))
+                        .map(|(_, handle)| handle)
+                        .collect_vec()
+                }
+                None => handles,
+            };
+            count += handles.len();
+            txn.delete(&handles)?;
+        }
+
+        yield DataChunk::single(count as i32);
+    }
+}
@@ -0,0 +1,24 @@
+use super::*;
+use crate::physical_planner::PhysicalDropTable;
+
+/// The executor of `DROP TABLE` statement.
+pub struct DropTableExecutor {
+    pub plan: PhysicalDropTable,
+    pub catalog: CatalogRef,
+    pub storage: StorageRef,
+}
+
+impl DropTableExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
+    pub async fn execute(self) {
+        for &id in &self.plan.table_ref_ids {
+            let schema = self.catalog.get_schema(id.schema_id).unwrap();
+            schema.del_table(id.table_id);
+            // persist the catalog before dropping the table in storage, so that a crash in between
+            // can be recovered by `Database::new`
+            self.catalog.persist().await?;
+            self.storage.drop_table(id).await?;
+        }
+        yield DataChunk::single(self.plan.table_ref_ids.len() as i32);
+    }
+}
@@ -1,6 +1,9 @@
+use std::cmp::Ordering;
+
 use crate::array::*;
 use crate::binder::BoundExpr;
 use crate::executor::ExecuteError;
+use crate::parser::BinaryOperator;
 use crate::types::DataValue;
 
 impl BoundExpr {
@@ -11,19 +14,20 @@ impl BoundExpr {
         match &self {
             Self::Constant(v) => Ok(v.clone()),
             Self::ColumnRef(_) => panic!("can not evaluate on ColumnRef"),
+            Self::InputRef(_) => panic!("can not evaluate on InputRef"),
+            Self::BinaryOp(b) => Ok(eval_binary_op(
+                &b.op,
+                &b.left_expr.eval_const()?,
+                &b.right_expr.eval_const()?,
+            )),
         }
     }
 
     /// Evaluate the given expression as an array.
     pub fn eval_array(&self, chunk: &DataChunk) -> Result<ArrayImpl, ExecuteError> {
         match &self {
-            // NOTE:
-            // Currently we assume that the column id is equal to its physical index in the
-            // DataChunk. It is true in a simple `SELECT v FROM t` case, where the child plan of the
-            // Projection is Get. However, in a more complex case with join or aggregation, this
-            // assumption no longer holds. At that time we will convert the ColumnRef into an
-            // InputRef, and resolve the physical index from column id.
-            Self::ColumnRef(v) => Ok(chunk.arrays()[v.column_ref_id.column_id as usize].clone()),
+            Self::ColumnRef(_) => panic!("ColumnRef should be resolved into InputRef by planner"),
+            Self::InputRef(v) => Ok(chunk.arrays()[v.index].clone()),
             Self::Constant(v) => {
                 let mut builder = ArrayBuilderImpl::with_capacity(
                     chunk.cardinality(),
@@ -35,6 +39,67 @@ impl BoundExpr {
                 }
                 Ok(builder.finish())
             }
+            Self::BinaryOp(b) => {
+                let left = b.left_expr.eval_array(chunk)?;
+                let right = b.right_expr.eval_array(chunk)?;
+                let mut builder = ArrayBuilderImpl::with_capacity(
+                    chunk.cardinality(),
+                    &self.return_type().unwrap(),
+                );
+                // TODO: vectorize this
+                for i in 0..chunk.cardinality() {
+                    builder.push(&eval_binary_op(&b.op, &left.get(i), &right.get(i)));
+                }
+                Ok(builder.finish())
+            }
+        }
+    }
+}
+
+/// Evaluate a binary operation on two values.
+///
+/// The logical operations follow the three-valued logic of SQL, and comparisons with NULL
+/// return NULL.
+fn eval_binary_op(op: &BinaryOperator, left: &DataValue, right: &DataValue) -> DataValue {
+    use BinaryOperator::*;
+    use DataValue::{Bool, Null};
+    match op {
+        And => match (left, right) {
+            (Bool(false), _) | (_, Bool(false)) => Bool(false),
+            (Bool(true), Bool(true)) => Bool(true),
+            _ => Null,
+        },
+        Or => match (left, right) {
+            (Bool(true), _) | (_, Bool(true)) => Bool(true),
+            (Bool(false), Bool(false)) => Bool(false),
+            _ => Null,
+        },
+        _ => match compare(left, right) {
+            None => Null,
+            Some(ordering) => Bool(match op {
+                Eq => ordering == Ordering::Equal,
+                NotEq => ordering != Ordering::Equal,
+                Gt => ordering == Ordering::Greater,
+                GtEq => ordering != Ordering::Less,
+                Lt => ordering == Ordering::Less,
+                LtEq => ordering != Ordering::Greater,
+                _ => panic!("unsupported binary operator: {:?}", op),
+            }),
+        },
+    }
+}
+
+/// Compare two values. Integers are compared with floats as floats.
+///
+/// Return `None` if any of them is NULL or they are not comparable.
+fn compare(left: &DataValue, right: &DataValue) -> Option<Ordering> {
+    match (left, right) {
+        (DataValue::Null, _) | (_, DataValue::Null) => None,
+        (DataValue::Int32(l), DataValue::Float64(r)) => (*l as f64).partial_cmp(r),
+        (DataValue::Float64(l), DataValue::Int32(r)) => l.partial_cmp(&(*r as f64)),
+        _ if std::mem::discriminant(left) == std::mem::discriminant(right) => {
+            left.partial_cmp(right)
         }
+        _ => None,
     }
 }
@@ -0,0 +1,30 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::array::DataChunk;
+use crate::binder::BoundExpr;
+use crate::types::DataValue;
+
+/// The executor of filter operation.
+pub struct FilterExecutor {
+    pub expr: BoundExpr,
+    pub child: BoxedExecutor,
+}
+
+impl FilterExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
+    pub async fn execute(self) {
+        #[for_await]
+        for batch in self.child {
+            let batch = batch?;
+            let array = self.expr.eval_array(&batch)?;
+            let visibility = (0..batch.cardinality())
+                .map(|i| array.get(i) == DataValue::Bool(true))
+                .collect_vec();
+            let chunk = batch.filter(&visibility);
+            if chunk.cardinality() > 0 {
+                yield chunk;
+            }
+        }
+    }
+}
@@ -10,14 +10,13 @@ pub struct InsertExecutor {
     pub table_ref_id: TableRefId,
     pub column_ids: Vec<ColumnId>,
     pub catalog: CatalogRef,
-    pub storage: StorageRef,
+    pub txn: SessionTxnRef,
     pub child: BoxedExecutor,
 }
 
 impl InsertExecutor {
     #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
     pub async fn execute(self) {
-        let table = self.storage.get_table(self.table_ref_id)?;
         let catalog = self.catalog.get_table(self.table_ref_id).unwrap();
         // Describe each column of the output chunks.
         // example:
@@ -38,7 +37,7 @@ impl InsertExecutor {
             .collect_vec();
         let mut count = 0;
 
-        let mut txn = table.write().await?;
+        let mut txn = self.txn.table_txn(self.table_ref_id, true).await?;
 
         #[for_await]
         for chunk in self.child {
@@ -47,8 +46,6 @@ impl InsertExecutor {
             txn.append(chunk).await?;
         }
 
-        txn.commit().await?;
-
         yield DataChunk::single(count as i32);
     }
 }
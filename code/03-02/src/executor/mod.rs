@@ -4,25 +4,35 @@ use futures::stream::{BoxStream, StreamExt};
 use futures_async_stream::try_stream;
 
 use crate::array::DataChunk;
-use crate::catalog::CatalogRef;
+use crate::catalog::{CatalogError, CatalogRef};
 use crate::physical_planner::PhysicalPlan;
-use crate::storage::{StorageError, StorageRef};
+use crate::storage::{SessionTxnRef, StorageError, StorageRef};
 
+mod backup;
 mod create;
+mod delete;
+mod drop;
 mod dummy;
 mod evaluator;
 mod explain;
+mod filter;
 mod insert;
 mod projection;
 mod seq_scan;
+mod truncate;
 mod values;
 
+use self::backup::*;
 use self::create::*;
+use self::delete::*;
+use self::drop::*;
 use self::dummy::*;
 use self::explain::*;
+use self::filter::*;
 use self::insert::*;
 use self::projection::*;
 use self::seq_scan::*;
+use self::truncate::*;
 use self::values::*;
 
 /// The maximum chunk length produced by executor at a time.
@@ -33,6 +43,8 @@ const PROCESSING_WINDOW_SIZE: usize = 1024;
 pub enum ExecuteError {
     #[error("storage error: {0}")]
     Storage(#[from] StorageError),
+    #[error("catalog error: {0}")]
+    Catalog(#[from] CatalogError),
 }
 
 /// A type-erased executor object.
@@ -47,10 +59,14 @@ pub type BoxedExecutor = BoxStream<'static, Result<DataChunk, ExecuteError>>;
 pub struct ExecutorBuilder {
     catalog: CatalogRef,
     storage: StorageRef,
+    /// The transaction where the statement is executed.
+    txn: SessionTxnRef,
     /// An optional runtime handle.
     ///
     /// If it is some, spawn the executor to runtime and return a channel receiver.
     handle: Option<tokio::runtime::Handle>,
+    /// The maximum number of morsels read in parallel by a scan.
+    scan_parallelism: usize,
 }
 
 impl ExecutorBuilder {
@@ -58,12 +74,16 @@ impl ExecutorBuilder {
     pub fn new(
         catalog: CatalogRef,
         storage: StorageRef,
+        txn: SessionTxnRef,
         handle: Option<tokio::runtime::Handle>,
+        scan_parallelism: usize,
     ) -> ExecutorBuilder {
         ExecutorBuilder {
             catalog,
             storage,
+            txn,
             handle,
+            scan_parallelism,
         }
     }
 
@@ -81,10 +101,34 @@ impl ExecutorBuilder {
                 table_ref_id: plan.table_ref_id,
                 column_ids: plan.column_ids,
                 catalog: self.catalog.clone(),
-                storage: self.storage.clone(),
+                txn: self.txn.clone(),
                 child: self.build(*plan.child),
             }
             .execute(),
+            PhysicalDelete(plan) => DeleteExecutor {
+                table_ref_id: plan.table_ref_id,
+                column_ids: plan.column_ids,
+                condition: plan.condition,
+                txn: self.txn.clone(),
+            }
+            .execute(),
+            PhysicalDropTable(plan) => DropTableExecutor {
+                plan,
+                catalog: self.catalog.clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
+            PhysicalTruncate(plan) => TruncateExecutor {
+                table_ref_id: plan.table_ref_id,
+                storage: self.storage.clone(),
+            }
+            .execute(),
+            PhysicalBackup(plan) => BackupExecutor {
+                dir: plan.dir.into(),
+                catalog: self.catalog.clone(),
+                storage: self.storage.clone(),
+            }
+            .execute(),
             PhysicalValues(plan) => ValuesExecutor {
                 column_types: plan.column_types,
                 values: plan.values,
@@ -95,7 +139,26 @@ impl ExecutorBuilder {
             PhysicalSeqScan(plan) => SeqScanExecutor {
                 table_ref_id: plan.table_ref_id,
                 column_ids: plan.column_ids,
+                ranges: plan.ranges,
+                as_of: plan.as_of,
                 storage: self.storage.clone(),
+                txn: self.txn.clone(),
+                parallelism: self.scan_parallelism,
+            }
+            .execute(),
+            PhysicalRangeScan(plan) => SeqScanExecutor {
+                table_ref_id: plan.table_ref_id,
+                column_ids: plan.column_ids,
+                ranges: [plan.key_range].into_iter().chain(plan.ranges).collect(),
+                as_of: plan.as_of,
+                storage: self.storage.clone(),
+                txn: self.txn.clone(),
+                parallelism: self.scan_parallelism,
+            }
+            .execute(),
+            PhysicalFilter(plan) => FilterExecutor {
+                expr: plan.expr,
+                child: self.build(*plan.child),
             }
             .execute(),
             PhysicalProjection(plan) => ProjectionExecutor {
@@ -109,9 +172,13 @@ impl ExecutorBuilder {
             // connect it with a channel, and return the receiver as an executor.
             // Therefore, when used with tokio multi-thread runtime, they can run in parallel.
             let (tx, rx) = tokio::sync::mpsc::channel(1);
+            // If the receiver is dropped, e.g. the statement fails or is cancelled, the executor is
+            // dropped too, which aborts its unfinished work.
             handle.spawn(async move {
                 while let Some(e) = executor.next().await {
-                    tx.send(e).await.unwrap();
+                    if tx.send(e).await.is_err() {
+                        break;
+                    }
                 }
             });
             tokio_stream::wrappers::ReceiverStream::new(rx).boxed()
@@ -1,24 +1,92 @@
+use std::collections::VecDeque;
+
+use tokio::sync::mpsc;
+
 use super::*;
 use crate::array::DataChunk;
 use crate::catalog::{ColumnId, TableRefId};
+use crate::storage::{AsOf, BoxedTransaction, BoxedTxnIterator, ColumnRange};
 
-/// The executor of sequential scan operation.
+/// The executor of sequential scan and range scan operation.
+///
+/// If `parallelism` is greater than 1, the scan is split into morsels, and up to `parallelism`
+/// morsels are read in tasks spawned to the current runtime, which run in parallel on a
+/// multi-thread runtime. The chunks are still yielded in the order of the morsels.
+///
+/// If `as_of` is some, an old version of the table is read by a read-only transaction of its own,
+/// instead of the transaction of the session.
 pub struct SeqScanExecutor {
     pub table_ref_id: TableRefId,
     pub column_ids: Vec<ColumnId>,
+    /// The ranges of the columns to scan. The scanned rows may not be in them.
+    pub ranges: Vec<ColumnRange>,
+    pub as_of: Option<AsOf>,
     pub storage: StorageRef,
+    pub txn: SessionTxnRef,
+    pub parallelism: usize,
 }
 
 impl SeqScanExecutor {
     #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
     pub async fn execute(self) {
-        let table = self.storage.get_table(self.table_ref_id)?;
-        let txn = table.read().await?;
+        let mut guard;
+        let mut old_txn = None;
+        let txn: &mut BoxedTransaction = match self.as_of {
+            Some(as_of) => {
+                let table = self.storage.get_table(self.table_ref_id)?;
+                old_txn.insert(table.read_as_of(as_of).await?)
+            }
+            None => {
+                guard = self.txn.table_txn(self.table_ref_id, false).await?;
+                &mut *guard
+            }
+        };
 
-        for chunk in txn.all_chunks().await? {
-            yield chunk;
+        if self.parallelism > 1 {
+            let mut morsels = txn
+                .iter_morsels(&self.column_ids, &self.ranges)
+                .await?
+                .into_iter();
+            let mut running = VecDeque::new();
+            loop {
+                while running.len() < self.parallelism {
+                    match morsels.next() {
+                        Some(iter) => running.push_back(spawn_morsel(iter)),
+                        None => break,
+                    }
+                }
+                let mut rx = match running.pop_front() {
+                    Some(rx) => rx,
+                    None => break,
+                };
+                while let Some(chunk) = rx.recv().await {
+                    yield chunk?;
+                }
+            }
+        } else {
+            let mut iter = txn.iter_with_ranges(&self.column_ids, &self.ranges).await?;
+            while let Some(chunk) = iter.next_batch(PROCESSING_WINDOW_SIZE).await? {
+                yield chunk;
+            }
+        }
+        if let Some(txn) = old_txn {
+            txn.commit().await?;
         }
-
-        txn.commit().await?;
     }
 }
+
+/// Spawn a task to read a morsel, and return the receiver of the chunks.
+///
+/// The task stops once the receiver is dropped.
+fn spawn_morsel(mut iter: BoxedTxnIterator) -> mpsc::Receiver<Result<DataChunk, StorageError>> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(result) = iter.next_batch(PROCESSING_WINDOW_SIZE).await.transpose() {
+            let is_err = result.is_err();
+            if tx.send(result).await.is_err() || is_err {
+                break;
+            }
+        }
+    });
+    rx
+}
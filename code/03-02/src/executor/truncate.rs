@@ -0,0 +1,16 @@
+use super::*;
+use crate::catalog::TableRefId;
+
+/// The executor of `TRUNCATE TABLE` statement.
+pub struct TruncateExecutor {
+    pub table_ref_id: TableRefId,
+    pub storage: StorageRef,
+}
+
+impl TruncateExecutor {
+    #[try_stream(boxed, ok = DataChunk, error = ExecuteError)]
+    pub async fn execute(self) {
+        self.storage.truncate_table(self.table_ref_id).await?;
+        yield DataChunk::single(1);
+    }
+}
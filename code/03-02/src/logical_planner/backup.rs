@@ -0,0 +1,20 @@
+use super::*;
+use crate::binder::BoundBackup;
+
+/// The logical plan of `BACKUP TO`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalBackup {
+    pub dir: String,
+}
+
+impl LogicalPlanner {
+    pub fn plan_backup(&self, stmt: BoundBackup) -> Result<LogicalPlan, LogicalPlanError> {
+        Ok(LogicalBackup { dir: stmt.dir }.into())
+    }
+}
+
+impl Explain for LogicalBackup {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Backup: dir {:?}", self.dir)
+    }
+}
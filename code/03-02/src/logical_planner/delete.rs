@@ -0,0 +1,48 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::binder::{BoundDelete, BoundExpr};
+use crate::catalog::{ColumnId, TableRefId};
+
+/// The logical plan of `DELETE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalDelete {
+    pub table_ref_id: TableRefId,
+    /// The columns scanned to evaluate the condition.
+    pub column_ids: Vec<ColumnId>,
+    /// The condition of rows to delete, which refers to the scanned columns.
+    pub condition: Option<BoundExpr>,
+}
+
+impl LogicalPlanner {
+    pub fn plan_delete(&self, stmt: BoundDelete) -> Result<LogicalPlan, LogicalPlanError> {
+        let mut column_ids = vec![];
+        if let Some(expr) = &stmt.where_clause {
+            expr.collect_column_ids(&mut column_ids);
+        }
+        if column_ids.is_empty() {
+            // at least one column is required to know the number of rows
+            column_ids.push(0);
+        }
+        Ok(LogicalDelete {
+            table_ref_id: stmt.table_ref_id,
+            condition: stmt
+                .where_clause
+                .map(|expr| expr.resolve_input_ref(&column_ids)),
+            column_ids,
+        }
+        .into())
+    }
+}
+
+impl Explain for LogicalDelete {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Delete: table {}, columns [{}], condition: {:?}",
+            self.table_ref_id.table_id,
+            self.column_ids.iter().map(ToString::to_string).join(", "),
+            self.condition
+        )
+    }
+}
@@ -0,0 +1,30 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::binder::BoundDropTable;
+use crate::catalog::TableRefId;
+
+/// The logical plan of `DROP TABLE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalDropTable {
+    pub table_ref_ids: Vec<TableRefId>,
+}
+
+impl LogicalPlanner {
+    pub fn plan_drop_table(&self, stmt: BoundDropTable) -> Result<LogicalPlan, LogicalPlanError> {
+        Ok(LogicalDropTable {
+            table_ref_ids: stmt.table_ref_ids,
+        }
+        .into())
+    }
+}
+
+impl Explain for LogicalDropTable {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "DropTable: tables [{}]",
+            self.table_ref_ids.iter().map(|id| id.table_id).join(", ")
+        )
+    }
+}
@@ -4,15 +4,23 @@ use enum_dispatch::enum_dispatch;
 
 use crate::binder::BoundStatement;
 
+mod backup;
 mod create;
+mod delete;
+mod drop;
 mod explain;
 mod insert;
 mod select;
+mod truncate;
 
+pub use self::backup::*;
 pub use self::create::*;
+pub use self::delete::*;
+pub use self::drop::*;
 pub use self::explain::*;
 pub use self::insert::*;
 pub use self::select::*;
+pub use self::truncate::*;
 
 /// The logical plan.
 #[enum_dispatch(Explain)]
@@ -20,10 +28,15 @@ pub use self::select::*;
 pub enum LogicalPlan {
     LogicalCreateTable,
     LogicalInsert,
+    LogicalDelete,
+    LogicalDropTable,
+    LogicalTruncate,
+    LogicalBackup,
     LogicalValues,
     LogicalExplain,
     LogicalDummy,
     LogicalGet,
+    LogicalFilter,
     LogicalProjection,
 }
 
@@ -50,6 +63,10 @@ impl LogicalPlanner {
         match stmt {
             BoundStatement::CreateTable(stmt) => self.plan_create_table(stmt),
             BoundStatement::Insert(stmt) => self.plan_insert(stmt),
+            BoundStatement::Delete(stmt) => self.plan_delete(stmt),
+            BoundStatement::DropTable(stmt) => self.plan_drop_table(stmt),
+            BoundStatement::Truncate(stmt) => self.plan_truncate(stmt),
+            BoundStatement::Backup(stmt) => self.plan_backup(stmt),
             BoundStatement::Explain(stmt) => self.plan_explain(*stmt),
             BoundStatement::Select(stmt) => self.plan_select(stmt),
         }
@@ -3,11 +3,13 @@
 //! A `select` statement will be planned to a compose of:
 //!
 //! - [`LogicalGet`] (from *) or [`LogicalDummy`] (no from)
+//! - [`LogicalFilter`] (where *)
 //! - [`LogicalProjection`] (select *)
 
 use super::*;
 use crate::binder::{BoundExpr, BoundSelect};
 use crate::catalog::{ColumnId, TableRefId};
+use crate::storage::AsOf;
 
 /// The logical plan of dummy get.
 #[derive(Debug, PartialEq, Clone)]
@@ -18,6 +20,17 @@ pub struct LogicalDummy;
 pub struct LogicalGet {
     pub table_ref_id: TableRefId,
     pub column_ids: Vec<ColumnId>,
+    /// The primary key column of the table.
+    pub primary_key: Option<ColumnId>,
+    /// The version of the table to read.
+    pub as_of: Option<AsOf>,
+}
+
+/// The logical plan of filter operation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalFilter {
+    pub expr: BoundExpr,
+    pub child: LogicalPlanRef,
 }
 
 /// The logical plan of projection.
@@ -30,17 +43,41 @@ pub struct LogicalProjection {
 impl LogicalPlanner {
     pub fn plan_select(&self, stmt: BoundSelect) -> Result<LogicalPlan, LogicalPlanError> {
         let mut plan: LogicalPlan = LogicalDummy.into();
+        let mut select_list = stmt.select_list;
+        let mut where_clause = stmt.where_clause;
 
         if let Some(table_ref) = stmt.from_list.get(0) {
+            // only scan the columns used by the select list and the where clause
+            let mut column_ids = vec![];
+            for expr in select_list.iter().chain(&where_clause) {
+                expr.collect_column_ids(&mut column_ids);
+            }
+            if column_ids.is_empty() {
+                // at least one column is required to know the number of rows
+                column_ids.push(table_ref.column_ids[0]);
+            }
+            select_list = (select_list.into_iter())
+                .map(|expr| expr.resolve_input_ref(&column_ids))
+                .collect();
+            where_clause = where_clause.map(|expr| expr.resolve_input_ref(&column_ids));
             plan = LogicalGet {
                 table_ref_id: table_ref.table_ref_id,
-                column_ids: table_ref.column_ids.clone(),
+                column_ids,
+                primary_key: table_ref.primary_key,
+                as_of: table_ref.as_of,
+            }
+            .into();
+        }
+        if let Some(expr) = where_clause {
+            plan = LogicalFilter {
+                expr,
+                child: plan.into(),
             }
             .into();
         }
-        if !stmt.select_list.is_empty() {
+        if !select_list.is_empty() {
             plan = LogicalProjection {
-                exprs: stmt.select_list,
+                exprs: select_list,
                 child: plan.into(),
             }
             .into();
@@ -57,11 +94,22 @@ impl Explain for LogicalDummy {
 
 impl Explain for LogicalGet {
     fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(
+        write!(
             f,
             "Get: table: {:?}, columns: {:?}",
             self.table_ref_id, self.column_ids
-        )
+        )?;
+        if let Some(as_of) = &self.as_of {
+            write!(f, ", as of {}", as_of)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl Explain for LogicalFilter {
+    fn explain_inner(&self, level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Filter: expr: {:?}", self.expr)?;
+        self.child.explain(level + 1, f)
     }
 }
 
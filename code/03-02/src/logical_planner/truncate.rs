@@ -0,0 +1,24 @@
+use super::*;
+use crate::binder::BoundTruncate;
+use crate::catalog::TableRefId;
+
+/// The logical plan of `TRUNCATE TABLE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogicalTruncate {
+    pub table_ref_id: TableRefId,
+}
+
+impl LogicalPlanner {
+    pub fn plan_truncate(&self, stmt: BoundTruncate) -> Result<LogicalPlan, LogicalPlanError> {
+        Ok(LogicalTruncate {
+            table_ref_id: stmt.table_ref_id,
+        }
+        .into())
+    }
+}
+
+impl Explain for LogicalTruncate {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Truncate: table {}", self.table_ref_id.table_id)
+    }
+}
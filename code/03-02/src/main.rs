@@ -8,9 +8,22 @@ use rustyline::Editor;
 fn main() {
     env_logger::init();
 
-    let db = Database::new(StorageOptions {
-        base_path: "risinglight.db".into(),
-    });
+    // the database is opened in memory if the path is `:memory:`
+    let path = std::env::args().nth(1);
+    let mut options = StorageOptions {
+        base_path: path.as_deref().unwrap_or("risinglight.db").into(),
+        ..Default::default()
+    };
+    if let Ok(s) = std::env::var("LIGHT_SCAN_PARALLELISM") {
+        match s.parse() {
+            Ok(n) if n > 0 => options.scan_parallelism = n,
+            _ => eprintln!(
+                "invalid LIGHT_SCAN_PARALLELISM {:?}, using {}",
+                s, options.scan_parallelism
+            ),
+        }
+    }
+    let db = Database::new(options).expect("failed to open database");
 
     let mut rl = Editor::<()>::new();
     loop {
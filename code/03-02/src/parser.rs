@@ -3,13 +3,181 @@
 //! The parser module directly uses the [`sqlparser`] crate
 //! and re-exports its AST types.
 
+use std::collections::HashMap;
+
 pub use sqlparser::ast::*;
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
 pub use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+/// A statement parsed from SQL, with the extensions which are not in the AST of [`sqlparser`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedStatement {
+    /// A statement parsed by [`sqlparser`].
+    Sql {
+        stmt: Statement,
+        /// The versions to read of the tables by `AS OF`, keyed by the alias of the table, or
+        /// its name if it has no alias.
+        as_of: HashMap<String, Value>,
+    },
+    /// `BACKUP TO '<dir>'`, which writes a backup of the database to the directory.
+    Backup { dir: String },
+}
 
 /// Parse the SQL string into a list of ASTs.
-pub fn parse(sql: &str) -> Result<Vec<Statement>, ParserError> {
+///
+/// A table in the `FROM` clause can be read as of an epoch or a time in the past by
+/// `t AS OF <epoch>` or `t AS OF '<timestamp>'`. The clause is removed from the statement
+/// before it is parsed by [`sqlparser`], and is returned along with it.
+pub fn parse(sql: &str) -> Result<Vec<ParsedStatement>, ParserError> {
     let dialect = PostgreSqlDialect {};
-    Parser::parse_sql(&dialect, sql)
+    let tokens = Tokenizer::new(&dialect, sql).tokenize()?;
+    let mut stmts = vec![];
+    // a semicolon in a string or a quoted identifier is a part of its token
+    for tokens in tokens.split(|token| *token == Token::SemiColon) {
+        // ignore empty statements between successive delimiters
+        if skip_whitespace(tokens, 0).is_none() {
+            continue;
+        }
+        if let Some(dir) = parse_backup(tokens)? {
+            stmts.push(ParsedStatement::Backup { dir });
+            continue;
+        }
+        let (tokens, as_of) = take_as_of(tokens)?;
+        let mut parser = Parser::new(tokens, &dialect);
+        let stmt = parser.parse_statement()?;
+        if parser.peek_token() != Token::EOF {
+            return parser.expected("end of statement", parser.peek_token());
+        }
+        stmts.push(ParsedStatement::Sql { stmt, as_of });
+    }
+    Ok(stmts)
+}
+
+/// Remove the `AS OF <literal>` clauses from the tokens of a statement, and return them keyed by
+/// the alias or the name of the table before them.
+fn take_as_of(tokens: &[Token]) -> Result<(Vec<Token>, HashMap<String, Value>), ParserError> {
+    let mut output: Vec<Token> = vec![];
+    let mut as_of = HashMap::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_word(&tokens[i], "AS") {
+            let literal = skip_whitespace(tokens, i + 1)
+                .filter(|&of| is_word(&tokens[of], "OF"))
+                .and_then(|of| skip_whitespace(tokens, of + 1))
+                .and_then(|j| match &tokens[j] {
+                    Token::Number(n, long) => Some((j, Value::Number(n.clone(), *long))),
+                    Token::SingleQuotedString(s) => Some((j, Value::SingleQuotedString(s.clone()))),
+                    _ => None,
+                });
+            if let Some((j, value)) = literal {
+                let last =
+                    (output.iter().rev()).find(|token| !matches!(token, Token::Whitespace(_)));
+                let table = match last {
+                    Some(Token::Word(w)) => w.value.clone(),
+                    _ => {
+                        return Err(ParserError::ParserError(
+                            "AS OF should follow a table".into(),
+                        ))
+                    }
+                };
+                if as_of.insert(table.clone(), value).is_some() {
+                    return Err(ParserError::ParserError(format!(
+                        "duplicated AS OF of table {}",
+                        table
+                    )));
+                }
+                i = j + 1;
+                continue;
+            }
+        }
+        output.push(tokens[i].clone());
+        i += 1;
+    }
+    Ok((output, as_of))
+}
+
+/// Parse `BACKUP TO '<dir>'`, and return `None` if the statement does not start with `BACKUP`,
+/// which is not a keyword of SQL.
+fn parse_backup(tokens: &[Token]) -> Result<Option<String>, ParserError> {
+    let mut words = tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_)));
+    if !matches!(words.next(), Some(token) if is_word(token, "BACKUP")) {
+        return Ok(None);
+    }
+    match (words.next(), words.next(), words.next()) {
+        (Some(to), Some(Token::SingleQuotedString(dir)), None) if is_word(to, "TO") => {
+            Ok(Some(dir.clone()))
+        }
+        _ => Err(ParserError::ParserError(
+            "BACKUP TO should be followed by a directory".into(),
+        )),
+    }
+}
+
+/// Returns true if the token is the unquoted word, ignoring case.
+fn is_word(token: &Token, word: &str) -> bool {
+    match token {
+        Token::Word(w) => w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word),
+        _ => false,
+    }
+}
+
+/// The position of the first token from `start` which is not a whitespace.
+fn skip_whitespace(tokens: &[Token], start: usize) -> Option<usize> {
+    (start..tokens.len()).find(|&i| !matches!(tokens[i], Token::Whitespace(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_of() {
+        let sql = "select * from t as of 3; select * from t as t1 AS OF '2022-01-01 00:00:00'";
+        let stmts = parse(sql).unwrap();
+        let expected = parse("select * from t; select * from t as t1").unwrap();
+        let as_of = |stmt: &ParsedStatement| match stmt {
+            ParsedStatement::Sql { as_of, .. } => as_of.clone(),
+            _ => panic!("not a SQL statement"),
+        };
+        let stmt = |stmt: &ParsedStatement| match stmt {
+            ParsedStatement::Sql { stmt, .. } => stmt.clone(),
+            _ => panic!("not a SQL statement"),
+        };
+        assert_eq!(stmt(&stmts[0]), stmt(&expected[0]));
+        assert_eq!(stmt(&stmts[1]), stmt(&expected[1]));
+        let number = Value::Number("3".into(), false);
+        assert_eq!(as_of(&stmts[0]), HashMap::from([("t".into(), number)]));
+        let timestamp = Value::SingleQuotedString("2022-01-01 00:00:00".into());
+        assert_eq!(as_of(&stmts[1]), HashMap::from([("t1".into(), timestamp)]));
+
+        // `AS OF` in strings or aliases is not taken
+        let sql = "select 'as of 3' as of from t";
+        assert!(as_of(&parse(sql).unwrap()[0]).is_empty());
+
+        // table hints are not taken as `AS OF`
+        let sql = "select * from t with (3)";
+        assert!(as_of(&parse(sql).unwrap()[0]).is_empty());
+    }
+
+    #[test]
+    fn test_backup() {
+        let sql = "backup to '/tmp/a'; BACKUP TO '/tmp/b'";
+        let backup = |dir: &str| ParsedStatement::Backup { dir: dir.into() };
+        assert_eq!(parse(sql).unwrap(), [backup("/tmp/a"), backup("/tmp/b")]);
+        assert!(parse("backup to '/tmp/a' now").is_err());
+
+        // a prepared statement named `backup` is not a backup
+        let stmts = parse("execute backup ('/tmp/a')").unwrap();
+        assert!(matches!(
+            stmts[0],
+            ParsedStatement::Sql {
+                stmt: Statement::Execute { .. },
+                ..
+            }
+        ));
+    }
 }
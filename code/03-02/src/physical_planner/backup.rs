@@ -0,0 +1,23 @@
+use super::*;
+use crate::logical_planner::LogicalBackup;
+
+/// The physical plan of `BACKUP TO`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalBackup {
+    pub dir: String,
+}
+
+impl PhysicalPlanner {
+    pub fn plan_backup(&self, plan: &LogicalBackup) -> Result<PhysicalPlan, PhysicalPlanError> {
+        Ok(PhysicalBackup {
+            dir: plan.dir.clone(),
+        }
+        .into())
+    }
+}
+
+impl Explain for PhysicalBackup {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Backup: dir {:?}", self.dir)
+    }
+}
@@ -0,0 +1,37 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::binder::BoundExpr;
+use crate::catalog::{ColumnId, TableRefId};
+use crate::logical_planner::LogicalDelete;
+
+/// The physical plan of `DELETE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalDelete {
+    pub table_ref_id: TableRefId,
+    pub column_ids: Vec<ColumnId>,
+    pub condition: Option<BoundExpr>,
+}
+
+impl PhysicalPlanner {
+    pub fn plan_delete(&self, plan: &LogicalDelete) -> Result<PhysicalPlan, PhysicalPlanError> {
+        Ok(PhysicalDelete {
+            table_ref_id: plan.table_ref_id,
+            column_ids: plan.column_ids.clone(),
+            condition: plan.condition.clone(),
+        }
+        .into())
+    }
+}
+
+impl Explain for PhysicalDelete {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Delete: table {}, columns [{}], condition: {:?}",
+            self.table_ref_id.table_id,
+            self.column_ids.iter().map(ToString::to_string).join(", "),
+            self.condition
+        )
+    }
+}
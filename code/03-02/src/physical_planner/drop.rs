@@ -0,0 +1,33 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::catalog::TableRefId;
+use crate::logical_planner::LogicalDropTable;
+
+/// The physical plan of `DROP TABLE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalDropTable {
+    pub table_ref_ids: Vec<TableRefId>,
+}
+
+impl PhysicalPlanner {
+    pub fn plan_drop_table(
+        &self,
+        plan: &LogicalDropTable,
+    ) -> Result<PhysicalPlan, PhysicalPlanError> {
+        Ok(PhysicalDropTable {
+            table_ref_ids: plan.table_ref_ids.clone(),
+        }
+        .into())
+    }
+}
+
+impl Explain for PhysicalDropTable {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "DropTable: tables [{}]",
+            self.table_ref_ids.iter().map(|id| id.table_id).join(", ")
+        )
+    }
+}
@@ -0,0 +1,144 @@
+use std::ops::Bound;
+
+use super::*;
+use crate::binder::BoundExpr;
+use crate::catalog::ColumnId;
+use crate::logical_planner::{LogicalFilter, LogicalPlan};
+use crate::parser::BinaryOperator;
+use crate::storage::ColumnRange;
+use crate::types::{DataTypeKind, DataValue};
+
+/// The physical plan of filter operation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalFilter {
+    pub expr: BoundExpr,
+    pub child: Box<PhysicalPlan>,
+}
+
+impl PhysicalPlanner {
+    pub fn plan_filter(&self, plan: &LogicalFilter) -> Result<PhysicalPlan, PhysicalPlanError> {
+        let child = match &*plan.child {
+            // Push the comparisons between a column and a constant into the scan as column
+            // ranges, so that it can skip blocks with zone maps, or seek to a range of the primary
+            // key. The filter is still required to check every row.
+            LogicalPlan::LogicalGet(get) => {
+                let mut ranges = vec![];
+                collect_ranges(&plan.expr, &get.column_ids, &mut ranges);
+                self.plan_filtered_scan(get, ranges)
+            }
+            child => self.plan(child)?,
+        };
+        Ok(PhysicalFilter {
+            expr: plan.expr.clone(),
+            child: child.into(),
+        }
+        .into())
+    }
+}
+
+/// Collect the conjuncts of `expr` in the form of `column op constant` into the ranges of the
+/// columns. The comparisons on the same column are intersected into one range.
+///
+/// Comparisons in the form of `constant op column` are flipped.
+fn collect_ranges(expr: &BoundExpr, column_ids: &[ColumnId], ranges: &mut Vec<ColumnRange>) {
+    use BinaryOperator::*;
+    let b = match expr {
+        BoundExpr::BinaryOp(b) => b,
+        _ => return,
+    };
+    let (column, op, value) = match (&*b.left_expr, &b.op, &*b.right_expr) {
+        (_, And, _) => {
+            collect_ranges(&b.left_expr, column_ids, ranges);
+            collect_ranges(&b.right_expr, column_ids, ranges);
+            return;
+        }
+        (BoundExpr::InputRef(column), op, BoundExpr::Constant(value)) => {
+            (column, op.clone(), value)
+        }
+        (BoundExpr::Constant(value), op, BoundExpr::InputRef(column)) => {
+            let op = match op {
+                Gt => Lt,
+                GtEq => LtEq,
+                Lt => Gt,
+                LtEq => GtEq,
+                op => op.clone(),
+            };
+            (column, op, value)
+        }
+        _ => return,
+    };
+    // the zone maps can only be compared with values of the same type as the column
+    let same_type = matches!(
+        (column.return_type.kind(), value),
+        (DataTypeKind::Int(_), DataValue::Int32(_))
+            | (
+                DataTypeKind::Float(_) | DataTypeKind::Double,
+                DataValue::Float64(_)
+            )
+            | (
+                DataTypeKind::Char(_) | DataTypeKind::Varchar(_) | DataTypeKind::String,
+                DataValue::String(_)
+            )
+    );
+    if !same_type {
+        return;
+    }
+    let value = value.clone();
+    let (start, end) = match op {
+        Eq => (Bound::Included(value.clone()), Bound::Included(value)),
+        Gt => (Bound::Excluded(value), Bound::Unbounded),
+        GtEq => (Bound::Included(value), Bound::Unbounded),
+        Lt => (Bound::Unbounded, Bound::Excluded(value)),
+        LtEq => (Bound::Unbounded, Bound::Included(value)),
+        _ => return,
+    };
+    let column_id = column_ids[column.index];
+    match ranges.iter_mut().find(|range| range.column_id == column_id) {
+        Some(range) => {
+            range.start = max_start(std::mem::replace(&mut range.start, Bound::Unbounded), start);
+            range.end = min_end(std::mem::replace(&mut range.end, Bound::Unbounded), end);
+        }
+        None => ranges.push(ColumnRange {
+            column_id,
+            start,
+            end,
+        }),
+    }
+}
+
+/// Returns the greater one of two start bounds.
+fn max_start(a: Bound<DataValue>, b: Bound<DataValue>) -> Bound<DataValue> {
+    match (&a, &b) {
+        (Bound::Unbounded, _) => b,
+        (_, Bound::Unbounded) => a,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            if x > y || (x == y && matches!(a, Bound::Excluded(_))) {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+/// Returns the less one of two end bounds.
+fn min_end(a: Bound<DataValue>, b: Bound<DataValue>) -> Bound<DataValue> {
+    match (&a, &b) {
+        (Bound::Unbounded, _) => b,
+        (_, Bound::Unbounded) => a,
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => {
+            if x < y || (x == y && matches!(a, Bound::Excluded(_))) {
+                a
+            } else {
+                b
+            }
+        }
+    }
+}
+
+impl Explain for PhysicalFilter {
+    fn explain_inner(&self, level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Filter: expr: {:?}", self.expr)?;
+        self.child.explain(level + 1, f)
+    }
+}
@@ -2,19 +2,31 @@ use enum_dispatch::enum_dispatch;
 
 use crate::logical_planner::{Explain, LogicalPlan};
 
+mod backup;
 mod create;
+mod delete;
+mod drop;
 mod dummy;
 mod explain;
+mod filter;
 mod insert;
 mod projection;
+mod range_scan;
 mod seq_scan;
+mod truncate;
 
+pub use self::backup::*;
 pub use self::create::*;
+pub use self::delete::*;
+pub use self::drop::*;
 pub use self::dummy::*;
 pub use self::explain::*;
+pub use self::filter::*;
 pub use self::insert::*;
 pub use self::projection::*;
+pub use self::range_scan::*;
 pub use self::seq_scan::*;
+pub use self::truncate::*;
 
 /// The physical plan.
 #[enum_dispatch(Explain)]
@@ -22,10 +34,16 @@ pub use self::seq_scan::*;
 pub enum PhysicalPlan {
     PhysicalCreateTable,
     PhysicalInsert,
+    PhysicalDelete,
+    PhysicalDropTable,
+    PhysicalTruncate,
+    PhysicalBackup,
     PhysicalValues,
     PhysicalExplain,
     PhysicalDummy,
     PhysicalSeqScan,
+    PhysicalRangeScan,
+    PhysicalFilter,
     PhysicalProjection,
 }
 
@@ -50,10 +68,15 @@ impl PhysicalPlanner {
         match plan {
             LogicalCreateTable(plan) => self.plan_create_table(plan),
             LogicalInsert(plan) => self.plan_insert(plan),
+            LogicalDelete(plan) => self.plan_delete(plan),
+            LogicalDropTable(plan) => self.plan_drop_table(plan),
+            LogicalTruncate(plan) => self.plan_truncate(plan),
+            LogicalBackup(plan) => self.plan_backup(plan),
             LogicalValues(plan) => self.plan_values(plan),
             LogicalExplain(plan) => self.plan_explain(plan),
             LogicalDummy(plan) => self.plan_dummy(plan),
             LogicalGet(plan) => self.plan_get(plan),
+            LogicalFilter(plan) => self.plan_filter(plan),
             LogicalProjection(plan) => self.plan_projection(plan),
         }
     }
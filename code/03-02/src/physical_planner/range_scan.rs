@@ -0,0 +1,68 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::catalog::{ColumnId, TableRefId};
+use crate::logical_planner::LogicalGet;
+use crate::storage::{AsOf, ColumnRange};
+
+/// The physical plan of range scan operation, which only reads the rows around a range of the
+/// primary key.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalRangeScan {
+    pub table_ref_id: TableRefId,
+    pub column_ids: Vec<ColumnId>,
+    /// The range of the primary key column.
+    pub key_range: ColumnRange,
+    /// The same as [`PhysicalSeqScan::ranges`], except that the primary key is not included.
+    pub ranges: Vec<ColumnRange>,
+    /// The version of the table to read.
+    pub as_of: Option<AsOf>,
+}
+
+impl PhysicalPlanner {
+    /// Plan a scan with the column ranges pushed from a filter. If the primary key has a range,
+    /// the scan is planned as a range scan.
+    pub fn plan_filtered_scan(
+        &self,
+        plan: &LogicalGet,
+        mut ranges: Vec<ColumnRange>,
+    ) -> PhysicalPlan {
+        let key_range = (ranges.iter())
+            .position(|range| Some(range.column_id) == plan.primary_key)
+            .map(|i| ranges.remove(i));
+        match key_range {
+            Some(key_range) => PhysicalRangeScan {
+                table_ref_id: plan.table_ref_id,
+                column_ids: plan.column_ids.clone(),
+                key_range,
+                ranges,
+                as_of: plan.as_of,
+            }
+            .into(),
+            None => PhysicalSeqScan {
+                table_ref_id: plan.table_ref_id,
+                column_ids: plan.column_ids.clone(),
+                ranges,
+                as_of: plan.as_of,
+            }
+            .into(),
+        }
+    }
+}
+
+impl Explain for PhysicalRangeScan {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RangeScan: table #{}, columns: {:?}, key range: {}",
+            self.table_ref_id.table_id, self.column_ids, self.key_range,
+        )?;
+        if !self.ranges.is_empty() {
+            write!(f, ", ranges: [{}]", self.ranges.iter().join(", "))?;
+        }
+        if let Some(as_of) = &self.as_of {
+            write!(f, ", as of {}", as_of)?;
+        }
+        writeln!(f)
+    }
+}
@@ -1,12 +1,20 @@
+use itertools::Itertools;
+
 use super::*;
 use crate::catalog::{ColumnId, TableRefId};
 use crate::logical_planner::LogicalGet;
+use crate::storage::{AsOf, ColumnRange};
 
 /// The physical plan of sequential scan operation.
 #[derive(Debug, PartialEq, Clone)]
 pub struct PhysicalSeqScan {
     pub table_ref_id: TableRefId,
     pub column_ids: Vec<ColumnId>,
+    /// The ranges of the scanned columns pushed from a filter, which are used to skip blocks.
+    /// The scanned rows may not be in them.
+    pub ranges: Vec<ColumnRange>,
+    /// The version of the table to read.
+    pub as_of: Option<AsOf>,
 }
 
 impl PhysicalPlanner {
@@ -14,6 +22,8 @@ impl PhysicalPlanner {
         Ok(PhysicalSeqScan {
             table_ref_id: plan.table_ref_id,
             column_ids: plan.column_ids.clone(),
+            ranges: vec![],
+            as_of: plan.as_of,
         }
         .into())
     }
@@ -21,10 +31,17 @@ impl PhysicalPlanner {
 
 impl Explain for PhysicalSeqScan {
     fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(
+        write!(
             f,
             "SeqScan: table #{}, columns: {:?}",
             self.table_ref_id.table_id, self.column_ids,
-        )
+        )?;
+        if !self.ranges.is_empty() {
+            write!(f, ", ranges: [{}]", self.ranges.iter().join(", "))?;
+        }
+        if let Some(as_of) = &self.as_of {
+            write!(f, ", as of {}", as_of)?;
+        }
+        writeln!(f)
     }
 }
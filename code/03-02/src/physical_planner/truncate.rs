@@ -0,0 +1,24 @@
+use super::*;
+use crate::catalog::TableRefId;
+use crate::logical_planner::LogicalTruncate;
+
+/// The physical plan of `TRUNCATE TABLE`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PhysicalTruncate {
+    pub table_ref_id: TableRefId,
+}
+
+impl PhysicalPlanner {
+    pub fn plan_truncate(&self, plan: &LogicalTruncate) -> Result<PhysicalPlan, PhysicalPlanError> {
+        Ok(PhysicalTruncate {
+            table_ref_id: plan.table_ref_id,
+        }
+        .into())
+    }
+}
+
+impl Explain for PhysicalTruncate {
+    fn explain_inner(&self, _level: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Truncate: table {}", self.table_ref_id.table_id)
+    }
+}
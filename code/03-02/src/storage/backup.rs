@@ -0,0 +1,161 @@
+//! Backup of the disk storage.
+//!
+//! A backup is a consistent snapshot of all tables, written into a new directory in the same
+//! layout as the storage, so that it can be opened by [`DiskStorage::open`]. The files of
+//! rowsets are never modified once written, so they are hard-linked into the backup, or copied
+//! if the backup is on another file system. Writers keep running during the backup, while the
+//! snapshots hold the rowsets being linked from removal.
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use super::manifest::{Manifest, ManifestOperation};
+use super::rowset::{sync_dir, sync_rowset};
+use super::{
+    dv_file_name, err, DiskStorage, Snapshot, StorageResult, MANIFEST_FILE_NAME, STAGING_DIR_NAME,
+};
+
+impl DiskStorage {
+    /// Write the latest version of all tables into `dir`, which should not exist or be empty.
+    ///
+    /// The manifest of the backup is written at last, so an incomplete backup contains no
+    /// tables when it is opened.
+    pub async fn backup(&self, dir: impl AsRef<Path>) -> StorageResult<()> {
+        let dir = dir.as_ref();
+        match std::fs::read_dir(dir).map(|mut entries| entries.next()) {
+            Ok(Some(_)) => return Err(anyhow!("backup directory {:?} is not empty", dir).into()),
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(err(e)),
+            _ => {}
+        }
+        tokio::fs::create_dir_all(dir).await.map_err(err)?;
+
+        // lock the snapshots in the order of table ids like a commit, so that the backup sees
+        // either all or none of the changes of a commit
+        let snapshots = {
+            let tables = self.tables.read().unwrap();
+            let tables = (tables.values().cloned())
+                .sorted_by_key(|table| (table.id.schema_id, table.id.table_id))
+                .collect_vec();
+            let guards = (tables.iter())
+                .map(|table| table.snapshot.read().unwrap())
+                .collect_vec();
+            let snapshots = guards.iter().map(|s| Snapshot::clone(s)).collect_vec();
+            drop(guards);
+            tables.into_iter().zip(snapshots).collect_vec()
+        };
+
+        let mut operations = vec![];
+        for (table, snapshot) in &snapshots {
+            let table_id = table.id;
+            operations.push(ManifestOperation::Epoch {
+                epoch: snapshot.epoch,
+                timestamp: snapshot.timestamp,
+            });
+            operations.push(ManifestOperation::CreateTable {
+                table_id,
+                column_descs: table.column_descs.to_vec(),
+            });
+            let table_path = dir.join(table_id.table_id.to_string());
+            for rowset in &snapshot.rowsets {
+                let rowset_id = rowset.rowset_id();
+                let rowset_path = table_path.join(rowset_id.to_string());
+                link_dir(&table.rowset_path_of(rowset_id), &rowset_path).await?;
+                sync_rowset(&rowset_path).await?;
+                operations.push(ManifestOperation::AddRowSet {
+                    table_id,
+                    rowset_id,
+                    wal_file_id: None,
+                });
+            }
+            // delete vectors may be removed once their version expires, so they are written
+            // from memory
+            for dv in snapshot.delete_vectors.values().flatten() {
+                let name = dv_file_name(dv.rowset_id(), dv.dv_id());
+                let staging_path = dir.join(STAGING_DIR_NAME).join(&name);
+                dv.write(staging_path, table_path.join(&name)).await?;
+                operations.push(ManifestOperation::AddDeleteVector {
+                    table_id,
+                    rowset_id: dv.rowset_id(),
+                    dv_id: dv.dv_id(),
+                });
+            }
+        }
+        let staging_path = dir.join(STAGING_DIR_NAME);
+        if staging_path.exists() {
+            tokio::fs::remove_dir_all(&staging_path)
+                .await
+                .map_err(err)?;
+        }
+
+        let (manifest, _) = Manifest::open(dir.join(MANIFEST_FILE_NAME))?;
+        manifest.append(&operations).await?;
+        sync_dir(dir).await?;
+        info!("backed up {} tables to {:?}", snapshots.len(), dir);
+        Ok(())
+    }
+}
+
+/// Hard-link the files in the directory `from` into a new directory `to`, or copy them if they
+/// cannot be linked.
+async fn link_dir(from: &Path, to: &Path) -> StorageResult<()> {
+    tokio::fs::create_dir_all(to).await.map_err(err)?;
+    let mut entries = tokio::fs::read_dir(from).await.map_err(err)?;
+    while let Some(entry) = entries.next_entry().await.map_err(err)? {
+        let path = to.join(entry.file_name());
+        if tokio::fs::hard_link(entry.path(), &path).await.is_err() {
+            tokio::fs::copy(entry.path(), &path).await.map_err(err)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{
+        create_test_table, delete_first_row, int_chunk, open_test_storage,
+    };
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let chunks = [int_chunk(0..4), int_chunk(4..8)];
+        let table = create_test_table(&storage, id, &column_descs, chunks).await;
+        delete_first_row(&table).await;
+
+        // a transaction running during the backup is not included
+        let mut txn = table.write().await.unwrap();
+        txn.append(int_chunk(8..10)).await.unwrap();
+        storage.backup(backup_dir.path()).await.unwrap();
+        txn.commit().await.unwrap();
+        assert!(storage.backup(backup_dir.path()).await.is_err());
+
+        // the backup is not changed by the compaction of the storage
+        storage.compact().await.unwrap();
+        drop(table);
+        drop(storage);
+
+        let storage = open_test_storage(backup_dir.path(), options()).await;
+        let mut txn = storage.get_table(id).unwrap().read().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let mut rows = 0;
+        while let Some(chunk) = iter.next_batch(usize::MAX).await.unwrap() {
+            rows += chunk.cardinality();
+        }
+        assert_eq!(rows, 7);
+        drop(iter);
+        txn.commit().await.unwrap();
+    }
+}
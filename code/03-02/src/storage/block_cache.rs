@@ -0,0 +1,175 @@
+//! An LRU cache of column blocks shared by all tables.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use bytes::Bytes;
+
+/// The key of a block in the cache.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct BlockCacheKey {
+    pub rowset_id: u32,
+    pub column_id: u32,
+    pub block_idx: u32,
+}
+
+/// Statistics of a [`BlockCache`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct BlockCacheStats {
+    /// Number of lookups that found the block in the cache.
+    pub hits: u64,
+    /// Number of lookups that missed.
+    pub misses: u64,
+    /// Total size in bytes of the cached blocks.
+    pub size: usize,
+}
+
+/// An LRU cache of the raw data of blocks.
+pub struct BlockCache {
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Inner {
+    /// Maximum total size in bytes of the cached blocks.
+    capacity: usize,
+    /// Total size in bytes of the cached blocks.
+    size: usize,
+    /// Cached blocks with the time of their last access.
+    blocks: HashMap<BlockCacheKey, (Bytes, u64)>,
+    /// Keys of all blocks ordered by the time of their last access.
+    lru: BTreeMap<u64, BlockCacheKey>,
+    /// The logical clock of accesses.
+    clock: u64,
+}
+
+impl BlockCache {
+    /// Create a block cache with `capacity` bytes. A capacity of 0 disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        BlockCache {
+            inner: Mutex::new(Inner {
+                capacity,
+                size: 0,
+                blocks: HashMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a block, and mark it as the most recently used one.
+    pub fn get(&self, key: &BlockCacheKey) -> Option<Bytes> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        match inner.blocks.get_mut(key) {
+            Some((data, last_access)) => {
+                inner.clock += 1;
+                inner.lru.remove(last_access);
+                inner.lru.insert(inner.clock, *key);
+                *last_access = inner.clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(data.clone())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Insert a block, evicting the least recently used blocks if the cache is full.
+    pub fn insert(&self, key: BlockCacheKey, data: Bytes) {
+        let mut inner = self.inner.lock().unwrap();
+        if data.len() > inner.capacity {
+            return;
+        }
+        inner.clock += 1;
+        let clock = inner.clock;
+        inner.size += data.len();
+        if let Some((old, last_access)) = inner.blocks.insert(key, (data, clock)) {
+            inner.size -= old.len();
+            inner.lru.remove(&last_access);
+        }
+        inner.lru.insert(clock, key);
+        while inner.size > inner.capacity {
+            let (&last_access, &key) = inner.lru.iter().next().unwrap();
+            inner.lru.remove(&last_access);
+            let (data, _) = inner.blocks.remove(&key).unwrap();
+            inner.size -= data.len();
+        }
+    }
+
+    pub fn stats(&self) -> BlockCacheStats {
+        BlockCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: self.inner.lock().unwrap().size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage, scan};
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    fn key(block_idx: u32) -> BlockCacheKey {
+        BlockCacheKey {
+            rowset_id: 0,
+            column_id: 0,
+            block_idx,
+        }
+    }
+
+    #[test]
+    fn test_lru() {
+        let cache = BlockCache::new(30);
+        cache.insert(key(0), Bytes::from(vec![0; 10]));
+        cache.insert(key(1), Bytes::from(vec![1; 10]));
+        cache.insert(key(2), Bytes::from(vec![2; 10]));
+        assert!(cache.get(&key(0)).is_some());
+
+        // block 1 is the least recently used one
+        cache.insert(key(3), Bytes::from(vec![3; 10]));
+        assert!(cache.get(&key(1)).is_none());
+        assert_eq!(cache.get(&key(2)).unwrap(), vec![2; 10]);
+
+        // a block larger than the capacity is never cached
+        cache.insert(key(4), Bytes::from(vec![4; 40]));
+        assert!(cache.get(&key(4)).is_none());
+
+        assert_eq!(
+            cache.stats(),
+            BlockCacheStats {
+                hits: 2,
+                misses: 2,
+                size: 30,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, [int_chunk(0..3)]).await;
+
+        for _ in 0..3 {
+            let mut txn = table.read().await.unwrap();
+            let chunks = scan(&mut txn, &[0]).await.unwrap();
+            assert_eq!(chunks[0].arrays()[0].get(2), DataValue::Int32(2));
+            txn.commit().await.unwrap();
+        }
+        let stats = storage.block_cache_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+    }
+}
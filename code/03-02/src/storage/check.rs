@@ -0,0 +1,348 @@
+//! Offline integrity check of the disk storage.
+//!
+//! The check reads the manifest, the WAL and all files of the rowsets and delete vectors under
+//! the base path without modifying them, so it should be run on a stopped database or a backup.
+//! Besides the corruptions, it reports the files not recorded in the manifest, which are left by
+//! unfinished writes and dropped tables and are removed when the storage is opened.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use super::block_cache::BlockCache;
+use super::delete_vector::{self, DeleteVector};
+use super::manifest::Manifest;
+use super::rowset::DiskRowset;
+use super::wal::Wal;
+use super::{
+    dv_file_name, err, DiskStorage, ReplayedManifest, ReplayedTable, StorageResult,
+    MANIFEST_FILE_NAME, STAGING_DIR_NAME, WAL_DIR_NAME,
+};
+use crate::catalog::{ColumnDesc, TableRefId};
+
+/// The result of checking the storage.
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// The tables in the manifest, ordered by their ids.
+    pub tables: Vec<TableReport>,
+
+    /// Problems not in any table, which mean the storage cannot be opened.
+    pub errors: Vec<String>,
+
+    /// Files not in any table, which are removed when the storage is opened.
+    pub warnings: Vec<String>,
+}
+
+impl CheckReport {
+    /// Returns true if no corruption is found.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.tables.iter().all(|table| table.errors.is_empty())
+    }
+}
+
+/// The result of checking a table.
+#[derive(Debug)]
+pub struct TableReport {
+    pub table_id: TableRefId,
+
+    /// Number of rowsets in the latest version.
+    pub rowsets: usize,
+
+    /// Number of rows in the latest version, including the deleted ones.
+    pub rows: usize,
+
+    /// Number of deleted rows in the latest version.
+    pub deleted_rows: usize,
+
+    /// Number of versions retained for time travel, including the latest one.
+    pub versions: usize,
+
+    /// Corruptions and inconsistencies with the manifest.
+    pub errors: Vec<String>,
+
+    /// Files which are not recorded in the manifest, or are not synced on commit and may be lost
+    /// in a crash, which are rebuilt from the WAL or dropped on opening.
+    pub warnings: Vec<String>,
+}
+
+impl DiskStorage {
+    /// Check the integrity of the storage at `base_path`.
+    ///
+    /// Every rowset in the manifest is opened and all blocks of its columns are decoded. A rowset
+    /// only used by the old versions may have been removed once they expired, so it is only
+    /// checked if it still exists.
+    pub async fn check(base_path: impl AsRef<Path>) -> StorageResult<CheckReport> {
+        let base_path = base_path.as_ref();
+        if !base_path.is_dir() {
+            return Err(anyhow!("{:?} is not a directory", base_path).into());
+        }
+        let mut report = CheckReport::default();
+        let manifest_path = base_path.join(MANIFEST_FILE_NAME);
+        if !manifest_path.exists() {
+            report.errors.push("manifest not found".into());
+            return Ok(report);
+        }
+        let replayed = match Manifest::read(&manifest_path).and_then(ReplayedManifest::replay) {
+            Ok(replayed) => replayed,
+            Err(e) => {
+                report.errors.push(format!("invalid manifest: {}", e));
+                return Ok(report);
+            }
+        };
+        let wal_dir = base_path.join(WAL_DIR_NAME);
+        let oldest_wal_file_id = Wal::oldest_file_id(&wal_dir).unwrap_or_default();
+        let wal_rowsets = match Wal::read(&wal_dir) {
+            Ok(records) => (records.iter())
+                .map(|record| (record.table_id, record.rowset_id))
+                .collect(),
+            Err(e) => {
+                report.errors.push(format!("invalid WAL: {}", e));
+                HashSet::new()
+            }
+        };
+        if base_path.join(STAGING_DIR_NAME).exists() {
+            report
+                .warnings
+                .push("staging directory left by unfinished writes".into());
+        }
+
+        // a rowset id is never reused by another table
+        let mut owners: HashMap<u32, TableRefId> = HashMap::new();
+        for (&id, table) in
+            (replayed.tables.iter()).sorted_by_key(|(id, _)| (id.schema_id, id.table_id))
+        {
+            let mut table_report =
+                check_table(base_path, id, table, &wal_rowsets, oldest_wal_file_id).await?;
+            let rowset_ids = (table.history.iter().chain([&table.current]))
+                .flat_map(|version| &version.rowsets)
+                .unique();
+            for &rowset_id in rowset_ids {
+                if let Some(owner) = owners.insert(rowset_id, id) {
+                    table_report.errors.push(format!(
+                        "rowset {} is also in table {}",
+                        rowset_id, owner.table_id
+                    ));
+                }
+            }
+            report.tables.push(table_report);
+        }
+
+        let table_ids: HashSet<u32> = replayed.tables.keys().map(|id| id.table_id).collect();
+        let mut entries = tokio::fs::read_dir(base_path).await.map_err(err)?;
+        while let Some(entry) = entries.next_entry().await.map_err(err)? {
+            let table_id = entry.file_name().to_str().and_then(|s| s.parse().ok());
+            if let Some(table_id) = table_id.filter(|id| !table_ids.contains(id)) {
+                (report.warnings).push(format!("directory of dropped table {}", table_id));
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Check the rowsets and delete vectors of a table, and the files in its directory.
+async fn check_table(
+    base_path: &Path,
+    id: TableRefId,
+    table: &ReplayedTable,
+    wal_rowsets: &HashSet<(TableRefId, u32)>,
+    oldest_wal_file_id: Option<u64>,
+) -> StorageResult<TableReport> {
+    let mut report = TableReport {
+        table_id: id,
+        rowsets: table.current.rowsets.len(),
+        rows: 0,
+        deleted_rows: 0,
+        versions: table.history.len() + 1,
+        errors: vec![],
+        warnings: vec![],
+    };
+    let versions = table.history.iter().chain([&table.current]).collect_vec();
+    for version in &versions {
+        if version.rowsets.iter().unique().count() != version.rowsets.len() {
+            (report.errors).push(format!("duplicate rowsets in epoch {}", version.epoch));
+        }
+        for &(rowset_id, dv_id) in &version.dvs {
+            if !version.rowsets.contains(&rowset_id) {
+                report.errors.push(format!(
+                    "delete vector {} of rowset {} not in epoch {}",
+                    dv_id, rowset_id, version.epoch
+                ));
+            }
+        }
+    }
+
+    let table_path = base_path.join(id.table_id.to_string());
+    let column_descs: Arc<[ColumnDesc]> = table.column_descs.clone().into();
+    let current_rowsets: HashSet<u32> = table.current.rowsets.iter().copied().collect();
+    let current_dvs: HashSet<(u32, u32)> = table.current.dvs.iter().copied().collect();
+    let rowset_ids: HashSet<u32> = versions.iter().flat_map(|v| &v.rowsets).copied().collect();
+    let dv_ids: HashSet<(u32, u32)> = versions.iter().flat_map(|v| &v.dvs).copied().collect();
+
+    let mut row_counts = HashMap::new();
+    for &rowset_id in rowset_ids.iter().sorted() {
+        let rowset_path = table_path.join(rowset_id.to_string());
+        let problem = if !rowset_path.exists() {
+            if !current_rowsets.contains(&rowset_id) {
+                continue;
+            }
+            format!("rowset {} not found", rowset_id)
+        } else {
+            match check_rowset(id, column_descs.clone(), rowset_id, &rowset_path).await {
+                Ok(row_count) => {
+                    row_counts.insert(rowset_id, row_count);
+                    continue;
+                }
+                Err(e) => e.to_string(),
+            }
+        };
+        // the files of a rowset in the WAL may not be synced, and are rebuilt on opening
+        if wal_rowsets.contains(&(id, rowset_id)) {
+            (report.warnings).push(format!("{}, which is rebuilt from WAL", problem));
+        } else if table.may_be_lost(rowset_id, oldest_wal_file_id) {
+            report.warnings.push(format!(
+                "{}, which is not synced on commit and is dropped on opening",
+                problem
+            ));
+        } else {
+            report.errors.push(problem);
+        }
+    }
+
+    let mut dvs: HashMap<u32, Vec<DeleteVector>> = HashMap::new();
+    for &(rowset_id, dv_id) in dv_ids.iter().sorted() {
+        let dv_path = table_path.join(dv_file_name(rowset_id, dv_id));
+        if !dv_path.exists() {
+            if current_dvs.contains(&(rowset_id, dv_id)) {
+                (report.errors).push(format!("delete vector {:?} not found", dv_path));
+            }
+            continue;
+        }
+        let dv = match DeleteVector::open(dv_id, rowset_id, &dv_path).await {
+            Ok(dv) => dv,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                continue;
+            }
+        };
+        let max_row = delete_vector::merge(std::slice::from_ref(&dv))
+            .last()
+            .copied();
+        if let (Some(row), Some(&row_count)) = (max_row, row_counts.get(&rowset_id)) {
+            if row as usize >= row_count {
+                report.errors.push(format!(
+                    "delete vector {:?} deletes row {} of rowset with {} rows",
+                    dv_path, row, row_count
+                ));
+            }
+        }
+        if current_dvs.contains(&(rowset_id, dv_id)) {
+            dvs.entry(rowset_id).or_default().push(dv);
+        }
+    }
+    for rowset_id in &table.current.rowsets {
+        report.rows += row_counts.get(rowset_id).copied().unwrap_or_default();
+        report.deleted_rows += dvs
+            .get(rowset_id)
+            .map_or(0, |dvs| delete_vector::merge(dvs).len());
+    }
+
+    if !table_path.exists() {
+        return Ok(report);
+    }
+    let mut entries = tokio::fs::read_dir(&table_path).await.map_err(err)?;
+    while let Some(entry) = entries.next_entry().await.map_err(err)? {
+        let path = entry.path();
+        let recorded = if path.extension() == Some("del".as_ref()) {
+            let name = entry.file_name();
+            (dv_ids.iter()).any(|&(rowset_id, dv_id)| name == *dv_file_name(rowset_id, dv_id))
+        } else {
+            match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                Some(rowset_id) => rowset_ids.contains(&rowset_id),
+                None => false,
+            }
+        };
+        if !recorded {
+            (report.warnings).push(format!("{:?} is not recorded in manifest", path));
+        }
+    }
+    Ok(report)
+}
+
+/// Decode all blocks of the rowset, and return the number of rows in it.
+async fn check_rowset(
+    table_id: TableRefId,
+    column_descs: Arc<[ColumnDesc]>,
+    rowset_id: u32,
+    rowset_path: &Path,
+) -> StorageResult<usize> {
+    // the blocks are not cached, as each of them is read only once
+    let block_cache = Arc::new(BlockCache::new(0));
+    let rowset = DiskRowset::open(
+        table_id,
+        column_descs,
+        block_cache,
+        rowset_id,
+        rowset_path.into(),
+    )
+    .await?;
+    rowset.verify().await?;
+    Ok(rowset.row_count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::ArrayImpl;
+    use crate::storage::tests::{create_test_table, delete_first_row, open_test_storage};
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = TableRefId::new(0, 1);
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let storage = open_test_storage(dir.path(), options()).await;
+        let columns = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            DataTypeKind::Int(None).nullable().to_column(),
+        ];
+        let chunks = [0..4, 4..10].map(|rows| {
+            let array = ArrayImpl::Int32(rows.collect());
+            [array.clone(), array].into_iter().collect()
+        });
+        let table = create_test_table(&storage, id, &columns, chunks).await;
+        delete_first_row(&table).await;
+        let rowset_path =
+            table.rowset_path_of(table.snapshot.read().unwrap().rowsets[0].rowset_id());
+        drop(table);
+        drop(storage);
+        // the rowsets in the WAL are rebuilt when the storage is opened
+        drop(open_test_storage(dir.path(), options()).await);
+
+        let report = DiskStorage::check(dir.path()).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert!(report.warnings.is_empty());
+        let table = &report.tables[0];
+        assert_eq!((table.rowsets, table.rows, table.deleted_rows), (2, 10, 1));
+
+        // flip a byte of a column file, and leave the directory of a dropped table
+        let column_path = rowset_path.join("1.col");
+        let mut data = std::fs::read(&column_path).unwrap();
+        data[0] ^= 0xff;
+        std::fs::write(&column_path, data).unwrap();
+        std::fs::create_dir(dir.path().join("42")).unwrap();
+
+        let report = DiskStorage::check(dir.path()).await.unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.tables[0].errors.len(), 1);
+        assert!(report.tables[0].errors[0].contains("checksum mismatch"));
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
@@ -1,25 +1,974 @@
+//! Encoding and decoding of on-disk columns.
+//!
+//! A column file (`.col`) is a sequence of blocks. Each block holds a fixed number
+//! of consecutive rows, and is about [`BLOCK_SIZE`] bytes before compression. The offset,
+//! length, checksum, row count, first key and zone map of each block are recorded in the block
+//! index (`.idx`).
+//!
+//! A block is laid out as:
+//!
+//! ```plain
+//! | encoding (u8) | row count (u32) | validity bitmap | values |
+//! ```
+//!
+//! The validity bitmap has one bit per row (LSB first), and is padded to whole bytes.
+//! A cleared bit means the row is NULL. The value of a NULL row is still encoded, as the
+//! default value of the type. The builder encodes the values of a block with every
+//! [`BlockEncoding`] applicable to the type, and keeps the smallest one:
+//!
+//! - `Plain`:
+//!   - `INT` and `DOUBLE`: fixed-width little-endian values.
+//!   - `BOOLEAN`: a bitmap of values, in the same format as the validity bitmap.
+//!   - `VARCHAR`: the end offset (u32) of each value, followed by the UTF-8 data.
+//! - `RunLength`:
+//!   - `INT` and `DOUBLE`: `| run count (u32) | value | run length (u32) | value | ... |`.
+//!   - `BOOLEAN`: `| first value (u8) | run count (u32) | run length (u32) | ... |`, where the runs
+//!     have alternating values.
+//! - `Delta` (`INT` and `DOUBLE`): `| first value (i64) | bit width (u8) | deltas |`, where the
+//!   zigzag-encoded differences between adjacent values are bit-packed. A `DOUBLE` is mapped to its
+//!   bit pattern.
+//! - `Dictionary` (`VARCHAR`): `| dictionary size (u32) | dictionary | bit width (u8) | codes |`,
+//!   where the dictionary holds the distinct values in `Plain` format, and the bit-packed codes are
+//!   indexes into the dictionary.
+
+use std::collections::HashMap;
+
 use anyhow::anyhow;
+use bitvec::prelude::{BitVec, Lsb0};
 use bytes::{Buf, BufMut};
 
+use super::index::BlockIndex;
 use super::StorageResult;
-use crate::array::{Array, ArrayBuilder, I32Array, I32ArrayBuilder};
+use crate::array::*;
+use crate::types::{DataType, DataTypeKind, DataValue};
 
-/// Encode an `I32Array` into a `Vec<u8>`.
-pub fn encode_int32_column(a: &I32Array, mut buffer: impl BufMut) -> StorageResult<()> {
-    for item in a.iter() {
-        if let Some(item) = item {
-            buffer.put_i32_le(*item);
-        } else {
-            return Err(anyhow!("nullable encoding not supported!").into());
+/// The target size of a block.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// The encoding of values in a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockEncoding {
+    Plain,
+    RunLength,
+    Delta,
+    Dictionary,
+}
+
+impl BlockEncoding {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Plain => 0,
+            Self::RunLength => 1,
+            Self::Delta => 2,
+            Self::Dictionary => 3,
         }
     }
-    Ok(())
+
+    fn from_tag(tag: u8) -> StorageResult<Self> {
+        Ok(match tag {
+            0 => Self::Plain,
+            1 => Self::RunLength,
+            2 => Self::Delta,
+            3 => Self::Dictionary,
+            _ => return Err(anyhow!("invalid block encoding: {}", tag).into()),
+        })
+    }
+}
+
+/// A primitive type with a fixed-width little-endian encoding.
+pub trait FixedWidth: Primitive {
+    /// Number of bytes of an encoded value.
+    const WIDTH: usize;
+
+    fn encode(&self, buffer: impl BufMut);
+
+    fn decode(buffer: impl Buf) -> Self;
+
+    /// Map the value to an `i64` losslessly, which is used by the delta encoding.
+    fn to_i64(self) -> i64;
+
+    fn from_i64(value: i64) -> Self;
+}
+
+impl FixedWidth for i32 {
+    const WIDTH: usize = 4;
+
+    fn encode(&self, mut buffer: impl BufMut) {
+        buffer.put_i32_le(*self);
+    }
+
+    fn decode(mut buffer: impl Buf) -> Self {
+        buffer.get_i32_le()
+    }
+
+    fn to_i64(self) -> i64 {
+        self as i64
+    }
+
+    fn from_i64(value: i64) -> Self {
+        value as i32
+    }
+}
+
+impl FixedWidth for f64 {
+    const WIDTH: usize = 8;
+
+    fn encode(&self, mut buffer: impl BufMut) {
+        buffer.put_f64_le(*self);
+    }
+
+    fn decode(mut buffer: impl Buf) -> Self {
+        buffer.get_f64_le()
+    }
+
+    fn to_i64(self) -> i64 {
+        self.to_bits() as i64
+    }
+
+    fn from_i64(value: i64) -> Self {
+        f64::from_bits(value as u64)
+    }
+}
+
+/// Builds a block of a [`PrimitiveArray`] with fixed-width values.
+#[derive(Default)]
+pub struct PrimitiveBlockBuilder<T: FixedWidth> {
+    valid: BitVec<u8, Lsb0>,
+    values: Vec<T>,
+}
+
+impl<T: FixedWidth> PrimitiveBlockBuilder<T> {
+    /// Append a value to the block.
+    pub fn push(&mut self, item: Option<&T>) {
+        self.valid.push(item.is_some());
+        self.values.push(item.cloned().unwrap_or_default());
+    }
+
+    /// Estimated size of the block without compression.
+    pub fn estimated_size(&self) -> usize {
+        5 + bitmap_len(self.valid.len()) + self.values.len() * T::WIDTH
+    }
+
+    /// Write the block into `buffer` with the smallest encoding.
+    pub fn finish(self, buffer: impl BufMut) {
+        let values = &self.values;
+        let candidates = [
+            (BlockEncoding::Plain, encode_plain(values)),
+            (BlockEncoding::RunLength, encode_run_length(values)),
+            (BlockEncoding::Delta, encode_delta(values)),
+        ];
+        put_smallest(buffer, &self.valid, candidates);
+    }
+}
+
+/// Builds a block of a [`BoolArray`].
+#[derive(Default)]
+pub struct BoolBlockBuilder {
+    valid: BitVec<u8, Lsb0>,
+    values: BitVec<u8, Lsb0>,
+}
+
+impl BoolBlockBuilder {
+    /// Append a value to the block.
+    pub fn push(&mut self, item: Option<&bool>) {
+        self.valid.push(item.is_some());
+        self.values.push(item.cloned().unwrap_or_default());
+    }
+
+    /// Estimated size of the block without compression.
+    pub fn estimated_size(&self) -> usize {
+        5 + bitmap_len(self.valid.len()) * 2
+    }
+
+    /// Write the block into `buffer` with the smallest encoding.
+    pub fn finish(self, buffer: impl BufMut) {
+        let candidates = [
+            (BlockEncoding::Plain, self.values.as_raw_slice().to_vec()),
+            (
+                BlockEncoding::RunLength,
+                encode_bool_run_length(&self.values),
+            ),
+        ];
+        put_smallest(buffer, &self.valid, candidates);
+    }
+}
+
+/// Builds a block of an [`Utf8Array`].
+#[derive(Default)]
+pub struct Utf8BlockBuilder {
+    valid: BitVec<u8, Lsb0>,
+    /// End offsets of values in `data`.
+    offsets: Vec<usize>,
+    data: Vec<u8>,
 }
 
-pub fn decode_int32_column(mut data: impl Buf) -> StorageResult<I32Array> {
-    let mut builder = I32ArrayBuilder::with_capacity(data.remaining() / 4);
-    while data.has_remaining() {
-        builder.push(Some(&data.get_i32_le()));
+impl Utf8BlockBuilder {
+    /// Append a value to the block.
+    pub fn push(&mut self, item: Option<&str>) {
+        self.valid.push(item.is_some());
+        self.data
+            .extend_from_slice(item.unwrap_or_default().as_bytes());
+        self.offsets.push(self.data.len());
+    }
+
+    /// Estimated size of the block without compression.
+    pub fn estimated_size(&self) -> usize {
+        5 + bitmap_len(self.valid.len()) + self.offsets.len() * 4 + self.data.len()
+    }
+
+    /// Write the block into `buffer` with the smallest encoding.
+    pub fn finish(self, buffer: impl BufMut) {
+        let mut start = 0;
+        let values = (self.offsets.iter())
+            .map(|&end| {
+                let value = &self.data[start..end];
+                start = end;
+                value
+            })
+            .collect::<Vec<_>>();
+        let candidates = [
+            (BlockEncoding::Plain, encode_plain_utf8(&values)),
+            (BlockEncoding::Dictionary, encode_dictionary(&values)),
+        ];
+        put_smallest(buffer, &self.valid, candidates);
+    }
+}
+
+pub type I32BlockBuilder = PrimitiveBlockBuilder<i32>;
+pub type F64BlockBuilder = PrimitiveBlockBuilder<f64>;
+
+/// Embeds all types of block builders.
+pub enum BlockBuilderImpl {
+    Bool(BoolBlockBuilder),
+    Int32(I32BlockBuilder),
+    Float64(F64BlockBuilder),
+    Utf8(Utf8BlockBuilder),
+}
+
+impl BlockBuilderImpl {
+    /// Create a new block builder from data type.
+    pub fn new(ty: &DataType) -> StorageResult<Self> {
+        Ok(match ty.kind() {
+            DataTypeKind::Boolean => Self::Bool(BoolBlockBuilder::default()),
+            DataTypeKind::Int(_) => Self::Int32(I32BlockBuilder::default()),
+            DataTypeKind::Float(_) | DataTypeKind::Double => {
+                Self::Float64(F64BlockBuilder::default())
+            }
+            DataTypeKind::Char(_) | DataTypeKind::Varchar(_) | DataTypeKind::String => {
+                Self::Utf8(Utf8BlockBuilder::default())
+            }
+            kind => return Err(anyhow!("unsupported data type: {}", kind).into()),
+        })
+    }
+
+    /// Create an empty block builder of the same type.
+    fn new_empty(&self) -> Self {
+        match self {
+            Self::Bool(_) => Self::Bool(BoolBlockBuilder::default()),
+            Self::Int32(_) => Self::Int32(I32BlockBuilder::default()),
+            Self::Float64(_) => Self::Float64(F64BlockBuilder::default()),
+            Self::Utf8(_) => Self::Utf8(Utf8BlockBuilder::default()),
+        }
+    }
+
+    /// Append the value at `idx` of `array` to the block.
+    pub fn push_from(&mut self, array: &ArrayImpl, idx: usize) -> StorageResult<()> {
+        match (self, array) {
+            (Self::Bool(builder), ArrayImpl::Bool(a)) => builder.push(a.get(idx)),
+            (Self::Int32(builder), ArrayImpl::Int32(a)) => builder.push(a.get(idx)),
+            (Self::Float64(builder), ArrayImpl::Float64(a)) => builder.push(a.get(idx)),
+            (Self::Utf8(builder), ArrayImpl::Utf8(a)) => builder.push(a.get(idx)),
+            _ => return Err(anyhow!("column type mismatch").into()),
+        }
+        Ok(())
+    }
+
+    /// Estimated size of the block.
+    pub fn estimated_size(&self) -> usize {
+        match self {
+            Self::Bool(builder) => builder.estimated_size(),
+            Self::Int32(builder) => builder.estimated_size(),
+            Self::Float64(builder) => builder.estimated_size(),
+            Self::Utf8(builder) => builder.estimated_size(),
+        }
+    }
+
+    /// Write the block into `buffer`.
+    pub fn finish(self, buffer: impl BufMut) {
+        match self {
+            Self::Bool(builder) => builder.finish(buffer),
+            Self::Int32(builder) => builder.finish(buffer),
+            Self::Float64(builder) => builder.finish(buffer),
+            Self::Utf8(builder) => builder.finish(buffer),
+        }
+    }
+}
+
+/// Returns an error if the storage can not encode values of `ty`.
+pub fn check_data_type(ty: &DataType) -> StorageResult<()> {
+    BlockBuilderImpl::new(ty).map(drop)
+}
+
+/// Builds a column file and its block index.
+pub struct ColumnBuilder {
+    block_size: usize,
+    /// Builder of the current block.
+    block: BlockBuilderImpl,
+    /// The first key and the number of rows of the current block.
+    first_key: DataValue,
+    row_count: usize,
+    /// The zone map of the current block.
+    null_count: usize,
+    min: DataValue,
+    max: DataValue,
+    /// Content of the column file.
+    data: Vec<u8>,
+    index: Vec<BlockIndex>,
+}
+
+impl ColumnBuilder {
+    /// Create a new column builder, which splits data into blocks of about `block_size` bytes.
+    ///
+    /// Returns an error if the storage can not encode values of `ty`.
+    pub fn new(ty: &DataType, block_size: usize) -> StorageResult<Self> {
+        Ok(ColumnBuilder {
+            block_size,
+            block: BlockBuilderImpl::new(ty)?,
+            first_key: DataValue::Null,
+            row_count: 0,
+            null_count: 0,
+            min: DataValue::Null,
+            max: DataValue::Null,
+            data: vec![],
+            index: vec![],
+        })
+    }
+
+    /// Append an array to the column.
+    pub fn append(&mut self, array: &ArrayImpl) -> StorageResult<()> {
+        for idx in 0..array.len() {
+            let value = array.get(idx);
+            if self.row_count == 0 {
+                self.first_key = value.clone();
+            }
+            if value == DataValue::Null {
+                self.null_count += 1;
+            } else if !matches!(value, DataValue::Float64(v) if v.is_nan()) {
+                // NaN is left out, since it is not ordered with other values and in no range
+                if self.min == DataValue::Null || value < self.min {
+                    self.min = value.clone();
+                }
+                if self.max == DataValue::Null || value > self.max {
+                    self.max = value;
+                }
+            }
+            self.block.push_from(array, idx)?;
+            self.row_count += 1;
+            if self.block.estimated_size() >= self.block_size {
+                self.finish_block();
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_block(&mut self) {
+        let empty = self.block.new_empty();
+        let block = std::mem::replace(&mut self.block, empty);
+        let offset = self.data.len();
+        block.finish(&mut self.data);
+        self.index.push(BlockIndex {
+            offset: offset as u64,
+            length: (self.data.len() - offset) as u64,
+            checksum: crc32fast::hash(&self.data[offset..]),
+            row_count: self.row_count as u32,
+            null_count: self.null_count as u32,
+            first_key: std::mem::replace(&mut self.first_key, DataValue::Null),
+            min: std::mem::replace(&mut self.min, DataValue::Null),
+            max: std::mem::replace(&mut self.max, DataValue::Null),
+        });
+        self.row_count = 0;
+        self.null_count = 0;
+    }
+
+    /// Finish the column and return the content of the column file and its block index.
+    pub fn finish(mut self) -> (Vec<u8>, Vec<BlockIndex>) {
+        if self.row_count != 0 {
+            self.finish_block();
+        }
+        (self.data, self.index)
+    }
+}
+
+/// Decode a block of type `ty` from `data`.
+pub fn decode_block(data: impl Buf, ty: &DataType) -> StorageResult<ArrayImpl> {
+    Ok(match ty.kind() {
+        DataTypeKind::Boolean => decode_bool_block(data)?.into(),
+        DataTypeKind::Int(_) => decode_primitive_block::<i32>(data)?.into(),
+        DataTypeKind::Float(_) | DataTypeKind::Double => {
+            decode_primitive_block::<f64>(data)?.into()
+        }
+        DataTypeKind::Char(_) | DataTypeKind::Varchar(_) | DataTypeKind::String => {
+            decode_utf8_block(data)?.into()
+        }
+        kind => return Err(anyhow!("unsupported data type: {}", kind).into()),
+    })
+}
+
+pub fn decode_primitive_block<T: FixedWidth>(
+    mut data: impl Buf,
+) -> StorageResult<PrimitiveArray<T>> {
+    let (encoding, valid) = get_header(&mut data)?;
+    let len = valid.len();
+    let values = match encoding {
+        BlockEncoding::Plain => decode_plain::<T>(&mut data, len)?,
+        BlockEncoding::RunLength => decode_run_length::<T>(&mut data, len)?,
+        BlockEncoding::Delta => decode_delta::<T>(&mut data, len)?,
+        BlockEncoding::Dictionary => {
+            return Err(anyhow!("invalid encoding for primitive: {:?}", encoding).into())
+        }
+    };
+    expect_remaining(&data, 0)?;
+    let mut builder = PrimitiveArrayBuilder::<T>::with_capacity(len);
+    for (is_valid, value) in valid.iter().by_vals().zip(values) {
+        builder.push(is_valid.then(|| &value));
     }
     Ok(builder.finish())
 }
+
+pub fn decode_bool_block(mut data: impl Buf) -> StorageResult<BoolArray> {
+    let (encoding, valid) = get_header(&mut data)?;
+    let len = valid.len();
+    let values = match encoding {
+        BlockEncoding::Plain => {
+            expect_remaining(&data, bitmap_len(len))?;
+            get_bitmap(&mut data, len)
+        }
+        BlockEncoding::RunLength => decode_bool_run_length(&mut data, len)?,
+        _ => return Err(anyhow!("invalid encoding for boolean: {:?}", encoding).into()),
+    };
+    expect_remaining(&data, 0)?;
+    let mut builder = BoolArrayBuilder::with_capacity(len);
+    for (is_valid, value) in valid.iter().by_vals().zip(values.iter().by_vals()) {
+        builder.push(is_valid.then(|| &value));
+    }
+    Ok(builder.finish())
+}
+
+pub fn decode_utf8_block(mut data: impl Buf) -> StorageResult<Utf8Array> {
+    let (encoding, valid) = get_header(&mut data)?;
+    let len = valid.len();
+    let mut builder = Utf8ArrayBuilder::with_capacity(len);
+    match encoding {
+        BlockEncoding::Plain => {
+            let values = decode_plain_utf8(&mut data, len)?;
+            expect_remaining(&data, 0)?;
+            for (is_valid, value) in valid.iter().by_vals().zip(values) {
+                builder.push(is_valid.then(|| value.as_str()));
+            }
+        }
+        BlockEncoding::Dictionary => {
+            let dict_len = get_u32(&mut data)? as usize;
+            let dict = decode_plain_utf8(&mut data, dict_len)?;
+            let codes = get_packed(&mut data, len)?;
+            expect_remaining(&data, 0)?;
+            for (is_valid, code) in valid.iter().by_vals().zip(codes) {
+                let value = dict
+                    .get(code as usize)
+                    .ok_or_else(|| anyhow!("invalid dictionary code: {}", code))?;
+                builder.push(is_valid.then(|| value.as_str()));
+            }
+        }
+        _ => return Err(anyhow!("invalid encoding for varchar: {:?}", encoding).into()),
+    }
+    Ok(builder.finish())
+}
+
+fn encode_plain<T: FixedWidth>(values: &[T]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(values.len() * T::WIDTH);
+    for value in values {
+        value.encode(&mut buffer);
+    }
+    buffer
+}
+
+fn decode_plain<T: FixedWidth>(mut data: impl Buf, len: usize) -> StorageResult<Vec<T>> {
+    expect_at_least(&data, len * T::WIDTH)?;
+    Ok((0..len).map(|_| T::decode(&mut data)).collect())
+}
+
+fn encode_run_length<T: FixedWidth>(values: &[T]) -> Vec<u8> {
+    let mut runs: Vec<(T, u32)> = vec![];
+    for &value in values {
+        match runs.last_mut() {
+            // compare the bits, so that `-0.0` is not merged into `0.0` and NaNs are merged
+            Some((last, count)) if last.to_i64() == value.to_i64() => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    let mut buffer = vec![];
+    buffer.put_u32_le(runs.len() as u32);
+    for (value, count) in runs {
+        value.encode(&mut buffer);
+        buffer.put_u32_le(count);
+    }
+    buffer
+}
+
+fn decode_run_length<T: FixedWidth>(mut data: impl Buf, len: usize) -> StorageResult<Vec<T>> {
+    let runs = get_u32(&mut data)? as usize;
+    expect_at_least(&data, runs * (T::WIDTH + 4))?;
+    let mut values = Vec::with_capacity(len);
+    for _ in 0..runs {
+        let value = T::decode(&mut data);
+        let count = data.get_u32_le() as usize;
+        if values.len() + count > len {
+            return Err(anyhow!("run lengths exceed the row count {}", len).into());
+        }
+        values.extend(std::iter::repeat(value).take(count));
+    }
+    if values.len() != len {
+        return Err(anyhow!("run lengths do not match the row count {}", len).into());
+    }
+    Ok(values)
+}
+
+fn encode_delta<T: FixedWidth>(values: &[T]) -> Vec<u8> {
+    let mut buffer = vec![];
+    let first = values.first().map_or(0, |v| v.to_i64());
+    buffer.put_i64_le(first);
+    let deltas = (values.iter().zip(values.iter().skip(1)))
+        .map(|(prev, next)| zigzag(next.to_i64().wrapping_sub(prev.to_i64())))
+        .collect::<Vec<_>>();
+    put_packed(&mut buffer, &deltas);
+    buffer
+}
+
+fn decode_delta<T: FixedWidth>(mut data: impl Buf, len: usize) -> StorageResult<Vec<T>> {
+    expect_at_least(&data, 8)?;
+    let mut value = data.get_i64_le();
+    let deltas = get_packed(&mut data, len.saturating_sub(1))?;
+    let mut values = Vec::with_capacity(len);
+    if len > 0 {
+        values.push(T::from_i64(value));
+    }
+    for delta in deltas {
+        value = value.wrapping_add(unzigzag(delta));
+        values.push(T::from_i64(value));
+    }
+    Ok(values)
+}
+
+fn encode_bool_run_length(values: &BitVec<u8, Lsb0>) -> Vec<u8> {
+    let mut runs: Vec<u32> = vec![];
+    let mut last = None;
+    for value in values.iter().by_vals() {
+        match runs.last_mut() {
+            Some(count) if last == Some(value) => *count += 1,
+            _ => runs.push(1),
+        }
+        last = Some(value);
+    }
+    let mut buffer = vec![];
+    buffer.put_u8((!values.is_empty() && values[0]) as u8);
+    buffer.put_u32_le(runs.len() as u32);
+    for count in runs {
+        buffer.put_u32_le(count);
+    }
+    buffer
+}
+
+fn decode_bool_run_length(mut data: impl Buf, len: usize) -> StorageResult<BitVec<u8, Lsb0>> {
+    expect_at_least(&data, 5)?;
+    let mut value = data.get_u8() != 0;
+    let runs = data.get_u32_le() as usize;
+    expect_at_least(&data, runs * 4)?;
+    let mut values = BitVec::with_capacity(len);
+    for _ in 0..runs {
+        let count = data.get_u32_le() as usize;
+        if values.len() + count > len {
+            return Err(anyhow!("run lengths exceed the row count {}", len).into());
+        }
+        values.extend(std::iter::repeat(value).take(count));
+        value = !value;
+    }
+    if values.len() != len {
+        return Err(anyhow!("run lengths do not match the row count {}", len).into());
+    }
+    Ok(values)
+}
+
+fn encode_plain_utf8(values: &[&[u8]]) -> Vec<u8> {
+    let mut buffer = vec![];
+    let mut end = 0;
+    for value in values {
+        end += value.len();
+        buffer.put_u32_le(end as u32);
+    }
+    for value in values {
+        buffer.put_slice(value);
+    }
+    buffer
+}
+
+fn decode_plain_utf8(mut data: impl Buf, len: usize) -> StorageResult<Vec<String>> {
+    if data.remaining() < len * 4 {
+        return Err(anyhow!("block is too short to contain offsets").into());
+    }
+    let offsets = (0..len)
+        .map(|_| data.get_u32_le() as usize)
+        .collect::<Vec<_>>();
+    expect_at_least(&data, offsets.last().cloned().unwrap_or(0))?;
+    let mut values = Vec::with_capacity(len);
+    let mut start = 0;
+    for end in offsets {
+        if end < start {
+            return Err(anyhow!("invalid offset: {} < {}", end, start).into());
+        }
+        let bytes = data.copy_to_bytes(end - start).to_vec();
+        values.push(String::from_utf8(bytes).map_err(|e| anyhow!(e))?);
+        start = end;
+    }
+    Ok(values)
+}
+
+fn encode_dictionary(values: &[&[u8]]) -> Vec<u8> {
+    let mut dict = vec![];
+    let mut codes = HashMap::new();
+    let codes = (values.iter())
+        .map(|&value| {
+            *codes.entry(value).or_insert_with(|| {
+                dict.push(value);
+                dict.len() as u64 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut buffer = vec![];
+    buffer.put_u32_le(dict.len() as u32);
+    buffer.put_slice(&encode_plain_utf8(&dict));
+    put_packed(&mut buffer, &codes);
+    buffer
+}
+
+/// Write the block header and the smallest encoded values into `buffer`.
+fn put_smallest<const N: usize>(
+    mut buffer: impl BufMut,
+    valid: &BitVec<u8, Lsb0>,
+    candidates: [(BlockEncoding, Vec<u8>); N],
+) {
+    let (encoding, values) = (candidates.into_iter())
+        .min_by_key(|(_, values)| values.len())
+        .unwrap();
+    buffer.put_u8(encoding.tag());
+    buffer.put_u32_le(valid.len() as u32);
+    buffer.put_slice(valid.as_raw_slice());
+    buffer.put_slice(&values);
+}
+
+/// Read the encoding, the row count and the validity bitmap.
+fn get_header(mut data: impl Buf) -> StorageResult<(BlockEncoding, BitVec<u8, Lsb0>)> {
+    if data.remaining() < 5 {
+        return Err(anyhow!("block is too short to contain a header").into());
+    }
+    let encoding = BlockEncoding::from_tag(data.get_u8())?;
+    let len = data.get_u32_le() as usize;
+    if data.remaining() < bitmap_len(len) {
+        return Err(anyhow!("block is too short to contain {} rows", len).into());
+    }
+    Ok((encoding, get_bitmap(data, len)))
+}
+
+/// Write a bit width (u8), followed by `values` bit-packed with that width.
+fn put_packed(mut buffer: impl BufMut, values: &[u64]) {
+    let width = values
+        .iter()
+        .map(|v| 64 - v.leading_zeros())
+        .max()
+        .unwrap_or(0) as usize;
+    buffer.put_u8(width as u8);
+    let mut bits = BitVec::<u8, Lsb0>::with_capacity(values.len() * width);
+    for value in values {
+        bits.extend((0..width).map(|i| (value >> i) & 1 == 1));
+    }
+    buffer.put_slice(bits.as_raw_slice());
+}
+
+/// Read `len` values written by [`put_packed`].
+fn get_packed(mut data: impl Buf, len: usize) -> StorageResult<Vec<u64>> {
+    expect_at_least(&data, 1)?;
+    let width = data.get_u8() as usize;
+    if width > 64 {
+        return Err(anyhow!("invalid bit width: {}", width).into());
+    }
+    expect_at_least(&data, bitmap_len(len * width))?;
+    let bits = get_bitmap(data, len * width);
+    let values = (0..len)
+        .map(|i| {
+            (0..width).fold(0, |value, bit| {
+                value | (bits[i * width + bit] as u64) << bit
+            })
+        })
+        .collect();
+    Ok(values)
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Number of bytes of a bitmap with `len` bits.
+fn bitmap_len(len: usize) -> usize {
+    (len + 7) / 8
+}
+
+fn get_bitmap(mut data: impl Buf, len: usize) -> BitVec<u8, Lsb0> {
+    let mut bitmap = vec![0; bitmap_len(len)];
+    data.copy_to_slice(&mut bitmap);
+    let mut bitmap = BitVec::<u8, Lsb0>::from_vec(bitmap);
+    bitmap.truncate(len);
+    bitmap
+}
+
+fn get_u32(mut data: impl Buf) -> StorageResult<u32> {
+    expect_at_least(&data, 4)?;
+    Ok(data.get_u32_le())
+}
+
+fn expect_remaining(data: &impl Buf, expected: usize) -> StorageResult<()> {
+    if data.remaining() != expected {
+        return Err(anyhow!(
+            "block size mismatch: expected {} bytes, found {} bytes",
+            expected,
+            data.remaining()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn expect_at_least(data: &impl Buf, expected: usize) -> StorageResult<()> {
+    if data.remaining() < expected {
+        return Err(anyhow!(
+            "block is truncated: expected at least {} bytes, found {} bytes",
+            expected,
+            data.remaining()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::index::{decode_index, encode_index};
+    use crate::types::DataTypeExt;
+
+    /// Build a column with small blocks, and decode it block by block.
+    fn roundtrip(array: ArrayImpl, ty: DataType) {
+        let mut builder = ColumnBuilder::new(&ty, 64).unwrap();
+        builder.append(&array).unwrap();
+        builder.append(&array).unwrap();
+        let (data, index) = builder.finish();
+        assert!(index.len() > 1);
+
+        let mut buffer = vec![];
+        encode_index(&index, &mut buffer);
+        let index = decode_index(&buffer[..]).unwrap();
+
+        let mut decoded = vec![];
+        for block in &index {
+            let start = block.offset as usize;
+            let end = start + block.length as usize;
+            let array = decode_block(&data[start..end], &ty).unwrap();
+            assert_eq!(array.len(), block.row_count as usize);
+            assert_eq!(array.get(0), block.first_key);
+            let mut values = (0..array.len())
+                .map(|i| array.get(i))
+                .filter(|v| *v != DataValue::Null)
+                .collect::<Vec<_>>();
+            assert_eq!(block.null_count as usize, array.len() - values.len());
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(
+                block.min,
+                values.first().cloned().unwrap_or(DataValue::Null)
+            );
+            assert_eq!(block.max, values.last().cloned().unwrap_or(DataValue::Null));
+            decoded.extend((0..array.len()).map(|i| array.get(i)));
+        }
+        let expected = (0..array.len())
+            .chain(0..array.len())
+            .map(|i| array.get(i))
+            .collect::<Vec<_>>();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_column_roundtrip() {
+        let ints = (0..100)
+            .map(|x| if x % 3 == 0 { None } else { Some(x) })
+            .collect::<I32Array>();
+        roundtrip(ints.into(), DataTypeKind::Int(None).nullable());
+
+        let floats = (0..100)
+            .map(|x| {
+                if x % 3 == 0 {
+                    None
+                } else {
+                    Some(x as f64 / 7.0)
+                }
+            })
+            .collect::<F64Array>();
+        roundtrip(floats.into(), DataTypeKind::Double.nullable());
+
+        let bools = (0..1000)
+            .map(|x| if x % 3 == 0 { None } else { Some(x % 2 == 0) })
+            .collect::<BoolArray>();
+        roundtrip(bools.into(), DataTypeKind::Boolean.nullable());
+
+        let strings = (0..100)
+            .map(|x| {
+                if x % 3 == 0 {
+                    None
+                } else {
+                    Some(x.to_string())
+                }
+            })
+            .collect::<Utf8Array>();
+        roundtrip(strings.into(), DataTypeKind::Varchar(None).nullable());
+    }
+
+    /// Build a single block, check that it roundtrips and return its encoding.
+    fn encoding_of(array: ArrayImpl, ty: DataType) -> BlockEncoding {
+        let mut builder = ColumnBuilder::new(&ty, BLOCK_SIZE).unwrap();
+        builder.append(&array).unwrap();
+        let (data, index) = builder.finish();
+        assert_eq!(index.len(), 1);
+        let decoded = decode_block(&data[..], &ty).unwrap();
+        assert_eq!(
+            (0..decoded.len())
+                .map(|i| decoded.get(i))
+                .collect::<Vec<_>>(),
+            (0..array.len()).map(|i| array.get(i)).collect::<Vec<_>>(),
+        );
+        BlockEncoding::from_tag(data[0]).unwrap()
+    }
+
+    #[test]
+    fn test_choose_encoding() {
+        let int = || DataTypeKind::Int(None).nullable();
+        let sorted = (0..1000).map(|x| x / 3).collect::<I32Array>();
+        assert_eq!(encoding_of(sorted.into(), int()), BlockEncoding::Delta);
+        let repeated = (0..1000)
+            .map(|x| if x < 500 { None } else { Some(7) })
+            .collect::<I32Array>();
+        assert_eq!(
+            encoding_of(repeated.into(), int()),
+            BlockEncoding::RunLength
+        );
+        let extremes = [i32::MIN, i32::MAX, 0, -1, i32::MIN]
+            .into_iter()
+            .collect::<I32Array>();
+        assert_eq!(encoding_of(extremes.into(), int()), BlockEncoding::Plain);
+        let random = (0..1000)
+            .map(|x: i32| x.wrapping_mul(-1640531535))
+            .collect::<I32Array>();
+        assert_eq!(encoding_of(random.into(), int()), BlockEncoding::Plain);
+
+        let floats = (0..1000)
+            .map(|x| if x < 500 { 0.5 } else { 1.5 })
+            .collect::<F64Array>();
+        let ty = DataTypeKind::Double.nullable();
+        assert_eq!(encoding_of(floats.into(), ty), BlockEncoding::RunLength);
+
+        let bool = || DataTypeKind::Boolean.nullable();
+        let runs = (0..1000).map(|x| x < 300).collect::<BoolArray>();
+        assert_eq!(encoding_of(runs.into(), bool()), BlockEncoding::RunLength);
+        let alternating = (0..1000).map(|x| x % 2 == 0).collect::<BoolArray>();
+        assert_eq!(
+            encoding_of(alternating.into(), bool()),
+            BlockEncoding::Plain
+        );
+
+        let varchar = || DataTypeKind::Varchar(None).nullable();
+        let strings = (0..1000)
+            .map(|x| {
+                if x % 5 == 0 {
+                    None
+                } else {
+                    Some(["apple", "banana", "cherry"][x % 3])
+                }
+            })
+            .collect::<Utf8Array>();
+        assert_eq!(
+            encoding_of(strings.into(), varchar()),
+            BlockEncoding::Dictionary
+        );
+        let unique = (0..1000)
+            .map(|x| Some(x.to_string()))
+            .collect::<Utf8Array>();
+        assert_eq!(encoding_of(unique.into(), varchar()), BlockEncoding::Plain);
+    }
+
+    #[test]
+    fn test_delta_extremes() {
+        let values = [i32::MIN, i32::MAX, 0, -1, i32::MIN, 1];
+        let data = encode_delta(&values);
+        assert_eq!(
+            decode_delta::<i32>(&data[..], values.len()).unwrap(),
+            values
+        );
+        let values = [f64::MIN, f64::MAX, 0.0, -0.0, f64::NAN.abs(), 1.5];
+        let data = encode_delta(&values);
+        let decoded = decode_delta::<f64>(&data[..], values.len()).unwrap();
+        let bits = |v: &[f64]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&decoded), bits(&values));
+    }
+
+    #[test]
+    fn test_run_length_float_bits() {
+        let values = [0.0, 0.0, -0.0, -0.0, f64::NAN, f64::NAN, 0.0];
+        let data = encode_run_length(&values);
+        assert_eq!(u32::from_le_bytes(data[..4].try_into().unwrap()), 4);
+        let decoded = decode_run_length::<f64>(&data[..], values.len()).unwrap();
+        let bits = |v: &[f64]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&decoded), bits(&values));
+    }
+
+    #[test]
+    fn test_zone_map_nan() {
+        let ty = DataTypeKind::Double.nullable();
+        let floats = [Some(f64::NAN), None, Some(2.0), Some(1.0), Some(f64::NAN)];
+        let mut builder = ColumnBuilder::new(&ty, BLOCK_SIZE).unwrap();
+        builder
+            .append(&floats.into_iter().collect::<F64Array>().into())
+            .unwrap();
+        let (_, index) = builder.finish();
+
+        assert_eq!(index[0].null_count, 1);
+        assert_eq!(index[0].min, DataValue::Float64(1.0));
+        assert_eq!(index[0].max, DataValue::Float64(2.0));
+    }
+
+    #[test]
+    fn test_truncated_block() {
+        let ty = DataTypeKind::Int(None).not_null();
+        let mut builder = ColumnBuilder::new(&ty, BLOCK_SIZE).unwrap();
+        builder
+            .append(&ArrayImpl::Int32((0..10).collect()))
+            .unwrap();
+        let (data, _) = builder.finish();
+
+        assert!(decode_block(&data[..data.len() - 1], &ty).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_type() {
+        let ty = DataTypeKind::Date.nullable();
+        assert!(ColumnBuilder::new(&ty, BLOCK_SIZE).is_err());
+        assert!(decode_block(&[][..], &ty).is_err());
+    }
+}
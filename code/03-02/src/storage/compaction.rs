@@ -0,0 +1,298 @@
+//! Compaction merges small rowsets and drops deleted rows.
+//!
+//! Every committed transaction adds new rowsets to a table, and every `DELETE` adds new delete
+//! vectors. Compaction picks the rowsets that are small or have many deleted rows, writes their
+//! remaining rows into a new rowset, and replaces them in one manifest batch. Transactions
+//! holding the old snapshot keep reading the old rowsets, whose files are removed once the last
+//! transaction is finished and the old version of the table expires.
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+
+use itertools::Itertools;
+
+use super::delete_vector::{self, DeleteVector};
+use super::manifest::ManifestOperation;
+use super::rowset::{DiskRowset, RowSetBuilder};
+use super::{DiskStorage, DiskTable, Snapshot, StorageResult};
+use crate::catalog::ColumnId;
+
+/// The maximum number of rows read from a rowset at a time during compaction.
+const COMPACTION_BATCH_SIZE: usize = 1024;
+
+impl DiskStorage {
+    /// Compact all tables once, and remove their expired versions.
+    pub async fn compact(&self) -> StorageResult<()> {
+        let tables = self.tables.read().unwrap().values().cloned().collect_vec();
+        for table in tables {
+            table.expire_versions().await;
+            table.compact().await?;
+        }
+        Ok(())
+    }
+
+    /// Spawn a task to compact all tables every `compaction_interval`.
+    ///
+    /// The task exits once the storage is dropped.
+    pub fn spawn_compaction_task(self: &Arc<Self>) {
+        let interval = match self.options.compaction_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let storage = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let storage = match storage.upgrade() {
+                    Some(storage) => storage,
+                    None => return,
+                };
+                if let Err(e) = storage.compact().await {
+                    warn!("failed to compact: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// The rowsets visible to the in-flight write transactions of a table, which are not compacted.
+///
+/// A write transaction may delete rows from any rowset of its snapshot, and its delete vectors
+/// cannot be published once the rowset is compacted away. Therefore compaction skips these
+/// rowsets until the transactions are finished.
+#[derive(Default)]
+pub(super) struct RowSetPins {
+    /// The number of in-flight write transactions that see each rowset.
+    counts: Mutex<HashMap<u32, usize>>,
+}
+
+impl RowSetPins {
+    /// Pin the rowsets of a snapshot until the returned [`RowSetPin`] is dropped.
+    ///
+    /// The snapshot should be locked, so that compaction cannot replace its rowsets meanwhile.
+    pub(super) fn pin(self: &Arc<Self>, snapshot: &Snapshot) -> RowSetPin {
+        let rowset_ids = snapshot
+            .rowsets
+            .iter()
+            .map(|rowset| rowset.rowset_id())
+            .collect_vec();
+        let mut counts = self.counts.lock().unwrap();
+        for id in &rowset_ids {
+            *counts.entry(*id).or_default() += 1;
+        }
+        RowSetPin {
+            pins: self.clone(),
+            rowset_ids,
+        }
+    }
+
+    fn is_pinned(&self, rowset_id: u32) -> bool {
+        self.counts.lock().unwrap().contains_key(&rowset_id)
+    }
+}
+
+/// Unpins the rowsets of a write transaction once dropped.
+pub(super) struct RowSetPin {
+    pins: Arc<RowSetPins>,
+    rowset_ids: Vec<u32>,
+}
+
+impl Drop for RowSetPin {
+    fn drop(&mut self) {
+        let mut counts = self.pins.counts.lock().unwrap();
+        for id in &self.rowset_ids {
+            if let Some(count) = counts.get_mut(id) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(id);
+                }
+            }
+        }
+    }
+}
+
+impl DiskTable {
+    /// Merge the small or delete-heavy rowsets into a new rowset.
+    ///
+    /// The rowsets visible to in-flight write transactions are skipped. Return `false` if there is
+    /// nothing to compact, or the rowsets are changed by a concurrent transaction during the
+    /// compaction.
+    pub async fn compact(&self) -> StorageResult<bool> {
+        let snapshot = self.snapshot.read().unwrap().clone();
+        let no_dvs: &[DeleteVector] = &[];
+        let dvs_of = |rowset: &DiskRowset| {
+            (snapshot.delete_vectors.get(&rowset.rowset_id())).map_or(no_dvs, Vec::as_slice)
+        };
+
+        // pick the rowsets to compact, until the new rowset is large enough
+        let mut inputs = vec![];
+        let mut rows = 0;
+        let mut has_deletes = false;
+        for rowset in &snapshot.rowsets {
+            if self.pins.is_pinned(rowset.rowset_id()) {
+                continue;
+            }
+            let deleted = delete_vector::merge(dvs_of(rowset)).len();
+            let delete_heavy = deleted > 0
+                && deleted as f64
+                    >= rowset.row_count() as f64 * self.options.compaction_delete_ratio;
+            let small = rowset.row_count() < self.options.compaction_small_rows;
+            if !small && !delete_heavy {
+                continue;
+            }
+            if rows >= self.options.memtable_max_rows {
+                break;
+            }
+            inputs.push(rowset.clone());
+            rows += rowset.row_count() - deleted;
+            has_deletes |= deleted > 0;
+        }
+        if inputs.len() < 2 && !has_deletes {
+            return Ok(false);
+        }
+
+        // write the remaining rows into a new rowset
+        let column_ids = (0..self.column_descs.len() as ColumnId).collect_vec();
+        let mut builder = RowSetBuilder::new(self.id, self.column_descs.clone())?;
+        for rowset in &inputs {
+            let mut iter = rowset.iter(&column_ids, delete_vector::merge(dvs_of(rowset)), &[])?;
+            while let Some(chunk) = iter.next_batch(COMPACTION_BATCH_SIZE).await? {
+                builder.append(chunk)?;
+            }
+        }
+        let output = if rows > 0 {
+            let rowset_id = self.rowset_id_generator.fetch_add(1, SeqCst);
+            let rowset = builder
+                .flush(
+                    self.block_cache.clone(),
+                    rowset_id,
+                    self.staging_path_of(rowset_id.to_string()),
+                    self.rowset_path_of(rowset_id),
+                    true,
+                )
+                .await?;
+            Some(rowset)
+        } else {
+            None
+        };
+
+        // replace the rowsets if they are not changed or pinned since the compaction started
+        let appended = {
+            let mut current = self.snapshot.write().unwrap();
+            let changed = inputs.iter().any(|input| {
+                let id = input.rowset_id();
+                !(current.rowsets.iter()).any(|rowset| rowset.rowset_id() == id)
+                    || current.delete_vectors.get(&id) != snapshot.delete_vectors.get(&id)
+                    || self.pins.is_pinned(id)
+            });
+            if changed {
+                if let Some(rowset) = &output {
+                    rowset.mark_obsolete();
+                }
+                info!("compaction of table {:?} is cancelled", self.id);
+                return Ok(false);
+            }
+
+            let mut operations = (output.iter())
+                .map(|rowset| ManifestOperation::AddRowSet {
+                    table_id: self.id,
+                    rowset_id: rowset.rowset_id(),
+                    wal_file_id: None,
+                })
+                .collect_vec();
+            operations.extend(inputs.iter().map(|rowset| ManifestOperation::DeleteRowSet {
+                table_id: self.id,
+                rowset_id: rowset.rowset_id(),
+            }));
+            let (epoch, timestamp) = self.epoch_generator.next();
+            operations.insert(0, ManifestOperation::Epoch { epoch, timestamp });
+            let appended = self.manifest.write(&operations)?;
+            self.commit_version(&mut current, epoch, timestamp);
+
+            let input_ids = inputs.iter().map(|rowset| rowset.rowset_id()).collect_vec();
+            current
+                .rowsets
+                .retain(|rowset| !input_ids.contains(&rowset.rowset_id()));
+            current.rowsets.extend(output.clone());
+            for id in &input_ids {
+                current.delete_vectors.remove(id);
+            }
+            appended
+        };
+        // the input rowsets are removed once their version expires, after the manifest is synced
+        self.manifest.sync(appended, None).await?;
+        self.expire_versions().await;
+        info!(
+            "compacted {} rowsets of table {:?} into {:?}",
+            inputs.len(),
+            self.id,
+            output.map(|rowset| rowset.rowset_id())
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{
+        create_test_table, delete_first_row, int_chunk, open_test_storage, wait_removed,
+    };
+    use crate::storage::{DiskTransaction, StorageOptions};
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    async fn values(txn: &mut DiskTransaction) -> Vec<DataValue> {
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let mut values = vec![];
+        while let Some(chunk) = iter.next_batch(usize::MAX).await.unwrap() {
+            values.extend((0..chunk.cardinality()).map(|i| chunk.arrays()[0].get(i)));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let chunks = (0..3).map(|i| int_chunk(i * 2..i * 2 + 2));
+        let table = create_test_table(&storage, id, &column_descs, chunks).await;
+        delete_first_row(&table).await;
+        let old_paths = (0..3).map(|i| table.rowset_path_of(i)).collect_vec();
+
+        let mut old_txn = table.read().await.unwrap();
+        assert!(table.compact().await.unwrap());
+        assert_eq!(table.snapshot.read().unwrap().rowsets.len(), 1);
+        assert!(!dir.path().join("0").join("0_0.del").exists());
+
+        // the old rowsets are still readable by the old transaction
+        assert_eq!(values(&mut old_txn).await.len(), 5);
+        assert!(old_paths.iter().all(|path| path.exists()));
+        old_txn.commit().await.unwrap();
+        for path in &old_paths {
+            assert!(wait_removed(path).await);
+        }
+
+        let expected = (1..6).map(DataValue::Int32).collect_vec();
+        let mut txn = table.read().await.unwrap();
+        assert_eq!(values(&mut txn).await, expected);
+        txn.commit().await.unwrap();
+        assert!(!table.compact().await.unwrap());
+        drop(table);
+        drop(storage);
+
+        let storage = open_test_storage(dir.path(), options()).await;
+        let table = storage.get_table(id).unwrap();
+        assert_eq!(table.snapshot.read().unwrap().rowsets.len(), 1);
+        let mut txn = table.read().await.unwrap();
+        assert_eq!(values(&mut txn).await, expected);
+        txn.commit().await.unwrap();
+    }
+}
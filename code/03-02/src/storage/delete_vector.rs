@@ -0,0 +1,207 @@
+//! Delete vectors record the deleted rows of a rowset.
+//!
+//! Rowsets are never modified after they are written. Instead, each transaction that deletes
+//! rows from a rowset writes the offsets of these rows into a new delete vector file (`.del`):
+//!
+//! ```plain
+//! | row count (u32) | row offset (u32) | row offset (u32) | ... | checksum (u32) |
+//! ```
+//!
+//! where the checksum is the CRC32 of all preceding bytes. A row is deleted if it appears in
+//! any delete vector of its rowset.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::{Buf, BufMut};
+
+use super::rowset::{sync_dir, write_file};
+use super::{err, StorageResult};
+
+/// The offsets of deleted rows in a rowset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteVector {
+    /// Id of the delete vector.
+    dv_id: u32,
+
+    /// Id of the rowset where the rows are deleted.
+    rowset_id: u32,
+
+    /// Offsets of the deleted rows in ascending order.
+    rows: Arc<[u32]>,
+}
+
+impl DeleteVector {
+    /// Create a delete vector of the given rows.
+    pub fn new(dv_id: u32, rowset_id: u32, mut rows: Vec<u32>) -> Self {
+        rows.sort_unstable();
+        rows.dedup();
+        DeleteVector {
+            dv_id,
+            rowset_id,
+            rows: rows.into(),
+        }
+    }
+
+    /// Open an existing delete vector at `path`.
+    pub async fn open(dv_id: u32, rowset_id: u32, path: impl AsRef<Path>) -> StorageResult<Self> {
+        let path = path.as_ref();
+        let data = tokio::fs::read(path).await.map_err(err)?;
+        let rows =
+            decode(&data).map_err(|e| anyhow!("delete vector {:?} is corrupted: {}", path, e))?;
+        Ok(Self::new(dv_id, rowset_id, rows))
+    }
+
+    /// Write the delete vector to `staging_path`, then move it to `path`.
+    pub async fn write(
+        &self,
+        staging_path: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+    ) -> StorageResult<()> {
+        let staging_path = staging_path.as_ref();
+        let path = path.as_ref();
+        let mut buffer = vec![];
+        encode(&self.rows, &mut buffer);
+
+        let staging_dir = staging_path.parent().unwrap();
+        tokio::fs::create_dir_all(staging_dir).await.map_err(err)?;
+        write_file(staging_path, &buffer, true).await?;
+        sync_dir(staging_dir).await?;
+
+        let parent = path.parent().unwrap();
+        tokio::fs::create_dir_all(parent).await.map_err(err)?;
+        tokio::fs::rename(staging_path, path).await.map_err(err)?;
+        sync_dir(parent).await?;
+        Ok(())
+    }
+
+    pub fn dv_id(&self) -> u32 {
+        self.dv_id
+    }
+
+    pub fn rowset_id(&self) -> u32 {
+        self.rowset_id
+    }
+}
+
+/// Merge the delete vectors of a rowset into the offsets of all deleted rows in ascending order.
+pub fn merge(dvs: &[DeleteVector]) -> Arc<[u32]> {
+    if let [dv] = dvs {
+        return dv.rows.clone();
+    }
+    let mut rows = dvs
+        .iter()
+        .flat_map(|dv| dv.rows.iter().copied())
+        .collect::<Vec<_>>();
+    rows.sort_unstable();
+    rows.dedup();
+    rows.into()
+}
+
+fn encode(rows: &[u32], buffer: &mut Vec<u8>) {
+    let start = buffer.len();
+    buffer.put_u32_le(rows.len() as u32);
+    for &row in rows {
+        buffer.put_u32_le(row);
+    }
+    let checksum = crc32fast::hash(&buffer[start..]);
+    buffer.put_u32_le(checksum);
+}
+
+fn decode(data: &[u8]) -> StorageResult<Vec<u32>> {
+    if data.len() < 8 {
+        return Err(anyhow!("too short to contain a row count and a checksum").into());
+    }
+    let (mut data, mut checksum) = data.split_at(data.len() - 4);
+    if crc32fast::hash(data) != checksum.get_u32_le() {
+        return Err(anyhow!("checksum mismatch").into());
+    }
+    let len = data.get_u32_le() as usize;
+    if data.remaining() != len * 4 {
+        return Err(anyhow!("expect {} rows, but got {} bytes", len, data.remaining()).into());
+    }
+    Ok((0..len).map(|_| data.get_u32_le()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::array::DataChunk;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage, scan};
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    #[test]
+    fn roundtrip() {
+        let mut buffer = vec![];
+        encode(&[1, 5, 7], &mut buffer);
+        assert_eq!(decode(&buffer).unwrap(), vec![1, 5, 7]);
+
+        buffer[4] ^= 1;
+        assert!(decode(&buffer).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let dvs = [
+            DeleteVector::new(0, 0, vec![5, 1]),
+            DeleteVector::new(1, 0, vec![3, 5]),
+        ];
+        assert_eq!(&*merge(&dvs), &[1, 3, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            memtable_max_rows: 4,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, [int_chunk(0..8)]).await;
+
+        // delete the even numbers
+        let mut snapshot = table.read().await.unwrap();
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        while let Some((chunk, handles)) = iter.next_batch_with_handles(3).await.unwrap() {
+            let handles = (handles.into_iter().enumerate())
+                .filter(
+                    |(i, _)| matches!(chunk.arrays()[0].get(*i), DataValue::Int32(v) if v % 2 == 0),
+                )
+                .map(|(_, handle)| handle)
+                .collect_vec();
+            txn.delete(&handles).unwrap();
+        }
+        txn.commit().await.unwrap();
+
+        let values = |chunks: Vec<DataChunk>| {
+            let all = DataChunk::concat(&chunks);
+            (0..all.cardinality())
+                .map(|i| all.arrays()[0].get(i))
+                .collect_vec()
+        };
+        let odd = [1, 3, 5, 7].into_iter().map(DataValue::Int32).collect_vec();
+        // the transaction started before the deletion still sees all rows
+        assert_eq!(values(scan(&mut snapshot, &[0]).await.unwrap()).len(), 8);
+        snapshot.commit().await.unwrap();
+        let mut txn = table.read().await.unwrap();
+        assert_eq!(values(scan(&mut txn, &[0]).await.unwrap()), odd);
+        txn.commit().await.unwrap();
+        drop(table);
+        drop(storage);
+
+        // delete vectors are recovered
+        let storage = open_test_storage(dir.path(), options()).await;
+        let table = storage.get_table(id).unwrap();
+        let mut txn = table.read().await.unwrap();
+        assert_eq!(values(scan(&mut txn, &[0]).await.unwrap()), odd);
+        txn.commit().await.unwrap();
+    }
+}
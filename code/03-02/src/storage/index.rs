@@ -0,0 +1,150 @@
+//! The block index of a column.
+//!
+//! An index file (`.idx`) is laid out as:
+//!
+//! ```plain
+//! | block count (u32) | block index | block index | ... | checksum (u32) |
+//! ```
+//!
+//! where the checksum is the CRC32 of all preceding bytes, and each block index is:
+//!
+//! ```plain
+//! | offset (u64) | length (u64) | checksum (u32) | row count (u32) | null count (u32) |
+//! | first key | min | max |
+//! ```
+//!
+//! The checksum of a block index is the CRC32 of the block in the column file. The null count,
+//! min and max of the blocks are the zone map of the column, which allows scans to skip the
+//! blocks that can not match a filter.
+
+use anyhow::anyhow;
+use bytes::{Buf, BufMut};
+
+use super::StorageResult;
+use crate::types::DataValue;
+
+/// The location and summary of a block in the column file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockIndex {
+    /// Offset of the block in the column file.
+    pub offset: u64,
+    /// Number of bytes of the block.
+    pub length: u64,
+    /// CRC32 of the block.
+    pub checksum: u32,
+    /// Number of rows in the block.
+    pub row_count: u32,
+    /// Number of NULL rows in the block.
+    pub null_count: u32,
+    /// Value of the first row in the block.
+    pub first_key: DataValue,
+    /// The minimum non-NULL value in the block, or NULL if all rows are NULL.
+    pub min: DataValue,
+    /// The maximum non-NULL value in the block, or NULL if all rows are NULL.
+    pub max: DataValue,
+}
+
+/// Encode the block indexes of a column into `buffer`.
+pub fn encode_index(index: &[BlockIndex], buffer: &mut Vec<u8>) {
+    let start = buffer.len();
+    buffer.put_u32_le(index.len() as u32);
+    for block in index {
+        buffer.put_u64_le(block.offset);
+        buffer.put_u64_le(block.length);
+        buffer.put_u32_le(block.checksum);
+        buffer.put_u32_le(block.row_count);
+        buffer.put_u32_le(block.null_count);
+        encode_value(&block.first_key, &mut *buffer);
+        encode_value(&block.min, &mut *buffer);
+        encode_value(&block.max, &mut *buffer);
+    }
+    let checksum = crc32fast::hash(&buffer[start..]);
+    buffer.put_u32_le(checksum);
+}
+
+/// Decode the block indexes of a column from `data`.
+pub fn decode_index(data: &[u8]) -> StorageResult<Vec<BlockIndex>> {
+    if data.len() < 4 {
+        return Err(anyhow!("index is too short to contain a checksum").into());
+    }
+    let (mut data, mut checksum) = data.split_at(data.len() - 4);
+    let checksum = checksum.get_u32_le();
+    if crc32fast::hash(data) != checksum {
+        return Err(anyhow!("index checksum mismatch").into());
+    }
+    let len = get_u32(&mut data)? as usize;
+    let mut index = Vec::with_capacity(len);
+    for _ in 0..len {
+        if data.remaining() < 28 {
+            return Err(anyhow!("index is too short to contain {} blocks", len).into());
+        }
+        index.push(BlockIndex {
+            offset: data.get_u64_le(),
+            length: data.get_u64_le(),
+            checksum: data.get_u32_le(),
+            row_count: data.get_u32_le(),
+            null_count: data.get_u32_le(),
+            first_key: decode_value(&mut data)?,
+            min: decode_value(&mut data)?,
+            max: decode_value(&mut data)?,
+        });
+    }
+    if data.has_remaining() {
+        return Err(anyhow!("unexpected trailing bytes in index").into());
+    }
+    Ok(index)
+}
+
+/// Encode a value with a type tag.
+pub fn encode_value(value: &DataValue, mut buffer: impl BufMut) {
+    match value {
+        DataValue::Null => buffer.put_u8(0),
+        DataValue::Bool(v) => {
+            buffer.put_u8(1);
+            buffer.put_u8(*v as u8);
+        }
+        DataValue::Int32(v) => {
+            buffer.put_u8(2);
+            buffer.put_i32_le(*v);
+        }
+        DataValue::Float64(v) => {
+            buffer.put_u8(3);
+            buffer.put_f64_le(*v);
+        }
+        DataValue::String(v) => {
+            buffer.put_u8(4);
+            buffer.put_u32_le(v.len() as u32);
+            buffer.put_slice(v.as_bytes());
+        }
+    }
+}
+
+/// Decode a value encoded by [`encode_value`].
+pub fn decode_value(mut data: impl Buf) -> StorageResult<DataValue> {
+    if !data.has_remaining() {
+        return Err(anyhow!("missing value").into());
+    }
+    let value = match data.get_u8() {
+        0 => DataValue::Null,
+        1 if data.remaining() >= 1 => DataValue::Bool(data.get_u8() != 0),
+        2 if data.remaining() >= 4 => DataValue::Int32(data.get_i32_le()),
+        3 if data.remaining() >= 8 => DataValue::Float64(data.get_f64_le()),
+        4 => {
+            let len = get_u32(&mut data)? as usize;
+            if data.remaining() < len {
+                return Err(anyhow!("string value is truncated").into());
+            }
+            let bytes = data.copy_to_bytes(len).to_vec();
+            DataValue::String(String::from_utf8(bytes).map_err(|e| anyhow!(e))?)
+        }
+        tag => return Err(anyhow!("invalid or truncated value with tag {}", tag).into()),
+    };
+    Ok(value)
+}
+
+fn get_u32(mut data: impl Buf) -> StorageResult<u32> {
+    if data.remaining() < 4 {
+        return Err(anyhow!("unexpected end of data").into());
+    }
+    Ok(data.get_u32_le())
+}
@@ -0,0 +1,424 @@
+//! Iterators that read a table block by block.
+//!
+//! A [`RowSetIterator`] reads the blocks of a rowset lazily, and a [`DiskTxnIterator`] chains the
+//! iterators of all rowsets in a transaction. Both of them yield chunks of at most the expected
+//! number of rows, so that the memory of a scan is bounded no matter how large the table is.
+//! Rows recorded in the delete vectors are skipped, and so are the blocks excluded by the zone
+//! maps, which are never read from disk.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use itertools::Itertools;
+
+use super::rowset::DiskRowset;
+use super::{RowHandle, StorageResult, TxnIterator};
+use crate::array::{ArrayBuilderImpl, ArrayImpl, DataChunk};
+use crate::catalog::ColumnId;
+
+/// An iterator over some columns of a rowset.
+pub struct RowSetIterator {
+    rowset: DiskRowset,
+
+    /// Cursors of the scanned columns.
+    columns: Vec<ColumnCursor>,
+
+    /// Offsets of the deleted rows in ascending order, which are skipped by the iterator.
+    deleted: Arc<[u32]>,
+
+    /// Row ranges `[start, end)` to skip without reading, in ascending order.
+    skipped: Vec<(u32, u32)>,
+
+    /// Index of the next range in `skipped`.
+    next_skipped: usize,
+
+    /// Offset of the next row to read.
+    next_row: u32,
+
+    /// Number of rows not yet read.
+    remaining_rows: usize,
+}
+
+/// The read position in a column.
+struct ColumnCursor {
+    column_idx: usize,
+
+    /// Index of the next block to read.
+    next_block_idx: usize,
+
+    /// The current block, and the offset of the next row in it.
+    block: Option<(ArrayImpl, usize)>,
+}
+
+impl RowSetIterator {
+    pub(super) fn new(
+        rowset: DiskRowset,
+        column_ids: &[ColumnId],
+        deleted: Arc<[u32]>,
+        skipped: Vec<(u32, u32)>,
+    ) -> Self {
+        RowSetIterator {
+            columns: column_ids
+                .iter()
+                .map(|&id| ColumnCursor {
+                    column_idx: id as usize,
+                    next_block_idx: 0,
+                    block: None,
+                })
+                .collect_vec(),
+            deleted,
+            skipped,
+            next_skipped: 0,
+            next_row: 0,
+            remaining_rows: rowset.row_count(),
+            rowset,
+        }
+    }
+
+    /// Read the next chunk with at most `expected_size` rows.
+    ///
+    /// Return `None` if the rowset is exhausted.
+    pub async fn next_batch(&mut self, expected_size: usize) -> StorageResult<Option<DataChunk>> {
+        let batch = self.next_visible_batch(expected_size).await?;
+        Ok(batch.map(|(chunk, _, _)| chunk))
+    }
+
+    /// Read the next chunk with at most `expected_size` rows, along with the handles of the rows.
+    ///
+    /// Return `None` if the rowset is exhausted.
+    pub async fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, Vec<RowHandle>)>> {
+        let rowset_id = self.rowset.rowset_id();
+        let batch = self.next_visible_batch(expected_size).await?;
+        Ok(batch.map(|(chunk, start, visibility)| {
+            let offsets = match visibility {
+                Some(visibility) => visibility.iter().positions(|&v| v).collect_vec(),
+                None => (0..chunk.cardinality()).collect_vec(),
+            };
+            let handles = (offsets.into_iter())
+                .map(|idx| RowHandle {
+                    rowset_id,
+                    row_offset: start + idx as u32,
+                })
+                .collect();
+            (chunk, handles)
+        }))
+    }
+
+    /// Read the next chunk that contains any row not deleted.
+    ///
+    /// Return the chunk of visible rows, the offset of the first row read, and the visibility
+    /// of the rows read if any of them is deleted.
+    async fn next_visible_batch(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, u32, Option<Vec<bool>>)>> {
+        loop {
+            self.skip_ranges().await?;
+            let mut size = expected_size.min(self.remaining_rows);
+            if let Some(&(start, _)) = self.skipped.get(self.next_skipped) {
+                // stop before the next skipped range
+                size = size.min((start - self.next_row) as usize);
+            }
+            if size == 0 {
+                return Ok(None);
+            }
+            let mut arrays = vec![];
+            for cursor in &mut self.columns {
+                arrays.push(cursor.next_array(&self.rowset, size).await?);
+            }
+            let chunk: DataChunk = arrays.into_iter().collect();
+            let start = self.next_row;
+            let end = start + size as u32;
+            self.next_row = end;
+            self.remaining_rows -= size;
+
+            let first = self.deleted.partition_point(|&row| row < start);
+            let last = self.deleted.partition_point(|&row| row < end);
+            let deleted = &self.deleted[first..last];
+            if deleted.is_empty() {
+                return Ok(Some((chunk, start, None)));
+            }
+            if deleted.len() == size {
+                continue;
+            }
+            let mut visibility = vec![true; size];
+            for &row in deleted {
+                visibility[(row - start) as usize] = false;
+            }
+            return Ok(Some((chunk.filter(&visibility), start, Some(visibility))));
+        }
+    }
+
+    /// Skip the rows in the skipped range where the next row is.
+    async fn skip_ranges(&mut self) -> StorageResult<()> {
+        while let Some(&(start, end)) = self.skipped.get(self.next_skipped) {
+            if self.next_row < start {
+                break;
+            }
+            if self.next_row < end {
+                let rows = (end - self.next_row) as usize;
+                for cursor in &mut self.columns {
+                    cursor.skip(&self.rowset, rows).await?;
+                }
+                self.next_row = end;
+                self.remaining_rows -= rows;
+            }
+            self.next_skipped += 1;
+        }
+        Ok(())
+    }
+}
+
+impl ColumnCursor {
+    /// Skip the next `rows` rows of the column. The blocks where all rows are skipped are not
+    /// read.
+    async fn skip(&mut self, rowset: &DiskRowset, mut rows: usize) -> StorageResult<()> {
+        while rows > 0 {
+            if let Some((block, offset)) = &mut self.block {
+                if *offset < block.len() {
+                    let n = rows.min(block.len() - *offset);
+                    *offset += n;
+                    rows -= n;
+                    continue;
+                }
+            }
+            let block_rows = match rowset
+                .block_indexes(self.column_idx)
+                .get(self.next_block_idx)
+            {
+                Some(block) => block.row_count as usize,
+                None => return Err(anyhow!("column has fewer rows than expected").into()),
+            };
+            if rows >= block_rows {
+                self.block = None;
+                self.next_block_idx += 1;
+                rows -= block_rows;
+            } else {
+                let block = rowset
+                    .read_block(self.column_idx, self.next_block_idx)
+                    .await?;
+                self.next_block_idx += 1;
+                self.block = Some((block, 0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the next `size` rows of the column.
+    async fn next_array(&mut self, rowset: &DiskRowset, size: usize) -> StorageResult<ArrayImpl> {
+        let desc = &rowset.column_descs()[self.column_idx];
+        let mut builder = ArrayBuilderImpl::with_capacity(size, desc.datatype());
+        let mut rows = 0;
+        while rows < size {
+            let exhausted = match &self.block {
+                Some((block, offset)) => *offset >= block.len(),
+                None => true,
+            };
+            if exhausted {
+                if self.next_block_idx >= rowset.block_count(self.column_idx) {
+                    return Err(anyhow!("column has fewer rows than expected").into());
+                }
+                let block = rowset
+                    .read_block(self.column_idx, self.next_block_idx)
+                    .await?;
+                self.next_block_idx += 1;
+                self.block = Some((block, 0));
+            }
+            let (block, offset) = self.block.as_mut().unwrap();
+            let n = (size - rows).min(block.len() - *offset);
+            if *offset == 0 && n == block.len() {
+                builder.append(block);
+            } else {
+                for i in *offset..*offset + n {
+                    builder.push(&block.get(i));
+                }
+            }
+            *offset += n;
+            rows += n;
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// An iterator over some columns of all rowsets in a transaction.
+pub struct DiskTxnIterator {
+    iters: VecDeque<RowSetIterator>,
+}
+
+impl DiskTxnIterator {
+    pub(super) fn new(iters: Vec<RowSetIterator>) -> Self {
+        DiskTxnIterator {
+            iters: iters.into(),
+        }
+    }
+
+    /// Read the next chunk with at most `expected_size` rows.
+    ///
+    /// Return `None` if all rowsets are exhausted.
+    pub async fn next_batch(&mut self, expected_size: usize) -> StorageResult<Option<DataChunk>> {
+        while let Some(iter) = self.iters.front_mut() {
+            if let Some(chunk) = iter.next_batch(expected_size).await? {
+                return Ok(Some(chunk));
+            }
+            self.iters.pop_front();
+        }
+        Ok(None)
+    }
+
+    /// Read the next chunk with at most `expected_size` rows, along with the handles of the rows.
+    ///
+    /// Return `None` if all rowsets are exhausted.
+    pub async fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, Vec<RowHandle>)>> {
+        while let Some(iter) = self.iters.front_mut() {
+            if let Some(batch) = iter.next_batch_with_handles(expected_size).await? {
+                return Ok(Some(batch));
+            }
+            self.iters.pop_front();
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl TxnIterator for DiskTxnIterator {
+    async fn next_batch(&mut self, expected_size: usize) -> StorageResult<Option<DataChunk>> {
+        DiskTxnIterator::next_batch(self, expected_size).await
+    }
+
+    async fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, Vec<RowHandle>)>> {
+        DiskTxnIterator::next_batch_with_handles(self, expected_size).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage, scan};
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    #[tokio::test]
+    async fn test_column_pruning() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            DataTypeKind::Boolean.not_null().to_column(),
+            DataTypeKind::Double.not_null().to_column(),
+        ];
+        let chunk: DataChunk = [
+            ArrayImpl::Int32([1, 2].into_iter().collect()),
+            ArrayImpl::Bool([true, false].into_iter().collect()),
+            ArrayImpl::Float64([0.5, 1.5].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        let table = create_test_table(&storage, id, &column_descs, [chunk]).await;
+
+        // columns that are not requested are never read
+        let rowset_path = table.rowset_path_of(0);
+        std::fs::remove_file(rowset_path.join("1.col")).unwrap();
+
+        let mut txn = table.read().await.unwrap();
+        let chunks = scan(&mut txn, &[2, 0]).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].arrays().len(), 2);
+        assert_eq!(chunks[0].arrays()[0].get(1), DataValue::Float64(1.5));
+        assert_eq!(chunks[0].arrays()[1].get(1), DataValue::Int32(2));
+        assert!(scan(&mut txn, &[1]).await.is_err());
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_iterator() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(
+            dir.path(),
+            StorageOptions {
+                memtable_max_rows: 50000,
+                ..Default::default()
+            },
+        )
+        .await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            DataTypeKind::Varchar(None).nullable().to_column(),
+        ];
+        // two rowsets of 50000 and 30000 rows, both spanning multiple blocks
+        let chunk: DataChunk = [
+            ArrayImpl::Int32((0..80000).collect()),
+            ArrayImpl::Utf8((0..80000).map(|i| Some(i.to_string())).collect()),
+        ]
+        .into_iter()
+        .collect();
+        let table = create_test_table(&storage, id, &column_descs, [chunk]).await;
+
+        let mut txn = table.read().await.unwrap();
+        let mut iter = txn.iter(&[1, 0]).await.unwrap();
+        let mut sizes = vec![];
+        let mut next = 0;
+        while let Some(chunk) = iter.next_batch(1024).await.unwrap() {
+            sizes.push(chunk.cardinality());
+            for i in 0..chunk.cardinality() {
+                assert_eq!(
+                    chunk.arrays()[0].get(i),
+                    DataValue::String(next.to_string())
+                );
+                assert_eq!(chunk.arrays()[1].get(i), DataValue::Int32(next));
+                next += 1;
+            }
+        }
+        assert_eq!(next, 80000);
+        assert!(sizes.iter().all(|&size| size <= 1024));
+        assert_eq!(sizes.len(), 49 + 30);
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_morsels() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let chunks = [int_chunk(0..200_000), int_chunk(200_000..200_010)];
+        let table = create_test_table(&storage, id, &column_descs, chunks).await;
+
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let (_, handles) = iter
+            .next_batch_with_handles(100_000)
+            .await
+            .unwrap()
+            .unwrap();
+        txn.delete(&handles[1..]).unwrap();
+        drop(iter);
+
+        // reading the morsels in order yields the same rows as a sequential scan
+        let expected = DataChunk::concat(&scan(&mut txn, &[0]).await.unwrap());
+        let morsels = txn.iter_morsels(&[0], &[]).await.unwrap();
+        assert!(morsels.len() > 3);
+        let mut chunks = vec![];
+        for mut iter in morsels {
+            while let Some(chunk) = iter.next_batch(usize::MAX).await.unwrap() {
+                chunks.push(chunk);
+            }
+        }
+        assert!(DataChunk::concat(&chunks) == expected);
+        assert_eq!(expected.cardinality(), 100_011);
+        txn.abort();
+    }
+}
@@ -0,0 +1,287 @@
+//! The manifest of the storage.
+//!
+//! The manifest is an append-only log of all operations that change the set of
+//! tables, rowsets and delete vectors. Each line of the manifest file is a JSON array
+//! of operations, which are applied atomically. On startup, the storage replays the
+//! manifest to recover its state.
+//!
+//! The batches of commits are synced together with the WAL according to
+//! [`WalSyncMode`](super::WalSyncMode), while other batches are synced once appended.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use super::{err, run_blocking, StorageResult};
+use crate::catalog::{ColumnDesc, TableRefId};
+
+/// An operation recorded in the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ManifestOperation {
+    CreateTable {
+        table_id: TableRefId,
+        column_descs: Vec<ColumnDesc>,
+    },
+    AddRowSet {
+        table_id: TableRefId,
+        rowset_id: u32,
+        /// The WAL file recording the rows of the rowset, if it is not synced on commit.
+        #[serde(default)]
+        wal_file_id: Option<u64>,
+    },
+    DeleteRowSet {
+        table_id: TableRefId,
+        rowset_id: u32,
+    },
+    AddDeleteVector {
+        table_id: TableRefId,
+        rowset_id: u32,
+        dv_id: u32,
+    },
+    DropTable {
+        table_id: TableRefId,
+    },
+    /// The epoch and the time in milliseconds of the commit made by the following operations.
+    Epoch {
+        epoch: u64,
+        timestamp: u64,
+    },
+}
+
+/// The manifest file.
+///
+/// Batches are first written to an in-memory buffer, which can be done under the locks of the
+/// tables, so that they are appended in the order of epochs. The buffer is appended to the file
+/// on a blocking thread by [`Manifest::flush`] or [`Manifest::sync`].
+pub struct Manifest {
+    /// Shared with the blocking threads that write to the file.
+    inner: Arc<ManifestInner>,
+
+    /// Number of bytes appended to the manifest that are synced.
+    synced: AtomicU64,
+
+    /// Serializes the syncs, so that the commits waiting for a sync share the next one.
+    sync_lock: tokio::sync::Mutex<()>,
+}
+
+struct ManifestInner {
+    /// Locked before `buffer` while the buffer is appended to the file.
+    file: Mutex<File>,
+
+    buffer: Mutex<ManifestBuffer>,
+}
+
+#[derive(Default)]
+struct ManifestBuffer {
+    /// The batches written but not yet appended to the file.
+    data: Vec<u8>,
+
+    /// Number of bytes written to the manifest since it is opened.
+    appended: u64,
+}
+
+impl Manifest {
+    /// Open the manifest at `path`, and return all operations recorded in it.
+    ///
+    /// A partially written batch at the end of the file, which is left by a crash,
+    /// is discarded.
+    pub fn open(path: impl AsRef<Path>) -> StorageResult<(Self, Vec<ManifestOperation>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(err)?;
+        let (operations, valid_len) = read_operations(&mut file)?;
+        file.set_len(valid_len).map_err(err)?;
+
+        let manifest = Manifest {
+            inner: Arc::new(ManifestInner {
+                file: Mutex::new(file),
+                buffer: Mutex::default(),
+            }),
+            synced: AtomicU64::new(0),
+            sync_lock: tokio::sync::Mutex::new(()),
+        };
+        Ok((manifest, operations))
+    }
+
+    /// Read all operations recorded in the manifest at `path` without modifying it.
+    pub fn read(path: impl AsRef<Path>) -> StorageResult<Vec<ManifestOperation>> {
+        let mut file = File::open(path).map_err(err)?;
+        let (operations, _) = read_operations(&mut file)?;
+        Ok(operations)
+    }
+
+    /// Append a batch of operations to the manifest, and sync it to the disk.
+    pub async fn append(&self, operations: &[ManifestOperation]) -> StorageResult<()> {
+        let appended = self.write(operations)?;
+        self.sync(appended, None).await
+    }
+
+    /// Write a batch of operations to the buffer of the manifest, and return the number of bytes
+    /// to sync by [`Manifest::sync`] to make the batch durable.
+    pub fn write(&self, operations: &[ManifestOperation]) -> StorageResult<u64> {
+        let mut line = serde_json::to_string(operations).map_err(err)?;
+        line.push('\n');
+        let mut buffer = self.inner.buffer.lock().unwrap();
+        buffer.data.extend_from_slice(line.as_bytes());
+        buffer.appended += line.len() as u64;
+        Ok(buffer.appended)
+    }
+
+    /// Append the written batches to the file without syncing it.
+    pub async fn flush(&self) -> StorageResult<()> {
+        let inner = self.inner.clone();
+        run_blocking(move || inner.flush(false)).await?;
+        Ok(())
+    }
+
+    /// Sync all batches written to the manifest.
+    pub async fn sync_all(&self) -> StorageResult<()> {
+        let appended = self.inner.buffer.lock().unwrap().appended;
+        self.sync(appended, None).await
+    }
+
+    /// Sync the manifest until at least `appended` bytes are synced, after waiting for `delay`.
+    pub async fn sync(&self, appended: u64, delay: Option<Duration>) -> StorageResult<()> {
+        let _guard = self.sync_lock.lock().await;
+        if self.synced.load(SeqCst) >= appended {
+            return Ok(());
+        }
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let inner = self.inner.clone();
+        let appended = run_blocking(move || inner.flush(true)).await?;
+        self.synced.fetch_max(appended, SeqCst);
+        Ok(())
+    }
+}
+
+impl ManifestInner {
+    /// Append the buffer to the file and sync it if `sync` is true, and return the number of
+    /// bytes appended to the file.
+    fn flush(&self, sync: bool) -> StorageResult<u64> {
+        // the file is locked before taking the buffer, so that the batches are appended in order
+        let mut file = self.file.lock().unwrap();
+        let (data, appended) = {
+            let mut buffer = self.buffer.lock().unwrap();
+            (std::mem::take(&mut buffer.data), buffer.appended)
+        };
+        file.write_all(&data).map_err(err)?;
+        if sync {
+            file.sync_data().map_err(err)?;
+        }
+        Ok(appended)
+    }
+}
+
+/// Read all operations from the manifest file, and return them with the length of the file
+/// without the partially written batch at the end.
+fn read_operations(file: &mut File) -> StorageResult<(Vec<ManifestOperation>, u64)> {
+    let mut operations = vec![];
+    let mut valid_len = 0;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let len = reader.read_line(&mut line).map_err(err)?;
+        if len == 0 {
+            break;
+        }
+        let batch = match line.strip_suffix('\n') {
+            Some(line) => serde_json::from_str::<Vec<ManifestOperation>>(line).ok(),
+            None => None,
+        };
+        match batch {
+            Some(batch) => operations.extend(batch),
+            None if reader.fill_buf().map_err(err)?.is_empty() => {
+                warn!("discard a partially written batch at the end of manifest");
+                break;
+            }
+            None => return Err(anyhow!("invalid manifest record: {}", line).into()),
+        }
+        valid_len += len as u64;
+    }
+    Ok((operations, valid_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_discard_partial_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let op = ManifestOperation::AddRowSet {
+            table_id: TableRefId::new(0, 1),
+            rowset_id: 2,
+            wal_file_id: None,
+        };
+
+        let (manifest, ops) = Manifest::open(&path).unwrap();
+        assert!(ops.is_empty());
+        manifest.append(&[op.clone()]).await.unwrap();
+        drop(manifest);
+
+        // Simulate a crash in the middle of writing a batch.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[{\"AddRowSet\"").unwrap();
+        drop(file);
+
+        let (manifest, ops) = Manifest::open(&path).unwrap();
+        assert_eq!(ops, vec![op.clone()]);
+        manifest.append(&[op.clone()]).await.unwrap();
+        drop(manifest);
+
+        let (_, ops) = Manifest::open(&path).unwrap();
+        assert_eq!(ops, vec![op.clone(), op]);
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        let ops = (0..2)
+            .map(|rowset_id| ManifestOperation::AddRowSet {
+                table_id: TableRefId::new(0, 1),
+                rowset_id,
+                wal_file_id: Some(3),
+            })
+            .collect::<Vec<_>>();
+
+        // a batch written before the WAL file of a rowset is recorded
+        std::fs::write(
+            &path,
+            "[{\"AddRowSet\":{\"table_id\":{\"schema_id\":0,\"table_id\":1},\"rowset_id\":2}}]\n",
+        )
+        .unwrap();
+        let (manifest, old_ops) = Manifest::open(&path).unwrap();
+        let old_op = ManifestOperation::AddRowSet {
+            table_id: TableRefId::new(0, 1),
+            rowset_id: 2,
+            wal_file_id: None,
+        };
+        assert_eq!(old_ops, vec![old_op.clone()]);
+
+        // the batches written before a sync are synced together
+        let appended = (ops.iter())
+            .map(|op| manifest.write(std::slice::from_ref(op)).unwrap())
+            .collect::<Vec<_>>();
+        manifest.sync(appended[0], None).await.unwrap();
+        assert_eq!(manifest.synced.load(SeqCst), appended[1]);
+        drop(manifest);
+
+        let (_, new_ops) = Manifest::open(&path).unwrap();
+        assert_eq!(new_ops, [vec![old_op], ops].concat());
+    }
+}
@@ -0,0 +1,403 @@
+//! In-memory storage.
+//!
+//! The in-memory storage keeps the chunks of each table in memory, which are lost once the
+//! storage is dropped. It is useful for tests that do not need persistence.
+//!
+//! Like the disk storage, a transaction reads the snapshot of the table at its start, together
+//! with its own writes. The appended chunks and the deleted rows become visible to the
+//! transactions started after the commit.
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicBool, AtomicU32};
+use std::sync::{Arc, RwLock};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use itertools::Itertools;
+
+use super::{
+    BoxedTransaction, BoxedTxnIterator, ColumnRange, RowHandle, Storage, StorageResult,
+    StorageTableRef, Table, Transaction, TxnIterator,
+};
+use crate::array::DataChunk;
+use crate::catalog::{ColumnDesc, ColumnId, TableRefId};
+
+/// In-memory storage.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    /// All tables in the current storage engine.
+    tables: RwLock<HashMap<TableRefId, Arc<InMemoryTable>>>,
+}
+
+impl InMemoryStorage {
+    /// Create a new in-memory storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a table.
+    pub fn get_table(&self, id: TableRefId) -> StorageResult<Arc<InMemoryTable>> {
+        let tables = self.tables.read().unwrap();
+        tables
+            .get(&id)
+            .ok_or_else(|| anyhow!("table not found: {:?}", id).into())
+            .cloned()
+    }
+}
+
+/// A table in the in-memory storage.
+///
+/// The table is a handle to the shared states, which is cheap to clone.
+#[derive(Clone)]
+pub struct InMemoryTable {
+    /// Id of the table.
+    id: TableRefId,
+
+    /// Generator for chunk id.
+    chunk_id_generator: Arc<AtomicU32>,
+
+    /// The latest committed snapshot.
+    snapshot: Arc<RwLock<Arc<Snapshot>>>,
+
+    /// Whether the table is dropped.
+    dropped: Arc<AtomicBool>,
+}
+
+/// The chunks of a table and the deleted rows.
+#[derive(Clone, Default)]
+struct Snapshot {
+    /// Chunks with their ids, in the order of appending.
+    chunks: Vec<(u32, DataChunk)>,
+
+    /// Offsets of the deleted rows keyed by the id of their chunk.
+    deleted_rows: HashMap<u32, HashSet<u32>>,
+}
+
+impl InMemoryTable {
+    fn new(id: TableRefId) -> Self {
+        InMemoryTable {
+            id,
+            chunk_id_generator: Arc::new(AtomicU32::new(0)),
+            snapshot: Arc::new(RwLock::new(Arc::new(Snapshot::default()))),
+            dropped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Start a transaction which only contains write.
+    pub fn write(&self) -> InMemoryTransaction {
+        self.begin(false)
+    }
+
+    /// Start a transaction which only contains read.
+    pub fn read(&self) -> InMemoryTransaction {
+        self.begin(true)
+    }
+
+    fn begin(&self, read_only: bool) -> InMemoryTransaction {
+        InMemoryTransaction {
+            read_only,
+            table: self.clone(),
+            snapshot: self.snapshot.read().unwrap().clone(),
+            appended: vec![],
+            deleted_rows: HashMap::new(),
+        }
+    }
+}
+
+/// A transaction on an in-memory table.
+pub struct InMemoryTransaction {
+    /// If this txn is read only.
+    read_only: bool,
+
+    /// Reference to table object
+    table: InMemoryTable,
+
+    /// The snapshot at the start of the transaction
+    snapshot: Arc<Snapshot>,
+
+    /// Chunks appended by the transaction with their ids, which become visible on commit
+    appended: Vec<(u32, DataChunk)>,
+
+    /// Offsets of the rows deleted by the transaction keyed by chunk id, which become
+    /// invisible on commit
+    deleted_rows: HashMap<u32, HashSet<u32>>,
+}
+
+impl InMemoryTransaction {
+    /// Append a chunk to the table.
+    pub fn append(&mut self, chunk: DataChunk) -> StorageResult<()> {
+        if self.read_only {
+            return Err(anyhow!("cannot append chunks in read only txn!").into());
+        }
+        if chunk.cardinality() > 0 {
+            let chunk_id = self.table.chunk_id_generator.fetch_add(1, SeqCst);
+            self.appended.push((chunk_id, chunk));
+        }
+        Ok(())
+    }
+
+    /// Delete the rows of the given handles, which are read from this transaction.
+    pub fn delete(&mut self, handles: &[RowHandle]) -> StorageResult<()> {
+        if self.read_only {
+            return Err(anyhow!("cannot delete rows in read only txn!").into());
+        }
+        for handle in handles {
+            self.deleted_rows
+                .entry(handle.rowset_id)
+                .or_default()
+                .insert(handle.row_offset);
+        }
+        Ok(())
+    }
+
+    /// Commit the transactions on different tables atomically.
+    pub fn commit_all(txns: Vec<InMemoryTransaction>) -> StorageResult<()> {
+        // lock the snapshots in the order of table ids to avoid deadlocks
+        let mut txns = (txns.into_iter())
+            .filter(|txn| !txn.appended.is_empty() || !txn.deleted_rows.is_empty())
+            .collect_vec();
+        txns.sort_by_key(|txn| (txn.table.id.schema_id, txn.table.id.table_id));
+        let tables = txns.iter().map(|txn| txn.table.clone()).collect_vec();
+        let mut snapshots = (tables.iter())
+            .map(|table| table.snapshot.write().unwrap())
+            .collect_vec();
+        if let Some(table) = tables.iter().find(|table| table.dropped.load(SeqCst)) {
+            return Err(anyhow!("table {:?} is dropped", table.id).into());
+        }
+        for (current, txn) in snapshots.iter_mut().zip(txns) {
+            let snapshot = Arc::make_mut(&mut **current);
+            snapshot.chunks.extend(txn.appended);
+            for (chunk_id, rows) in txn.deleted_rows {
+                snapshot
+                    .deleted_rows
+                    .entry(chunk_id)
+                    .or_default()
+                    .extend(rows);
+            }
+        }
+        Ok(())
+    }
+
+    /// Create an iterator over the given columns of the table.
+    ///
+    /// The iterator reads the snapshot at the start of the transaction, together with the rows
+    /// appended and deleted by the transaction so far.
+    pub fn iter(&self, column_ids: &[ColumnId]) -> InMemoryTxnIterator {
+        let no_rows = HashSet::new();
+        let chunks = (self.snapshot.chunks.iter().chain(&self.appended))
+            .filter_map(|(chunk_id, chunk)| {
+                let deleted = self.snapshot.deleted_rows.get(chunk_id).unwrap_or(&no_rows);
+                let deleted_by_txn = self.deleted_rows.get(chunk_id).unwrap_or(&no_rows);
+                let rows = (0..chunk.cardinality() as u32)
+                    .filter(|row| !deleted.contains(row) && !deleted_by_txn.contains(row))
+                    .collect_vec();
+                if rows.is_empty() {
+                    return None;
+                }
+                let chunk = (column_ids.iter())
+                    .map(|&id| chunk.arrays()[id as usize].clone())
+                    .collect();
+                Some((*chunk_id, chunk, rows))
+            })
+            .collect();
+        InMemoryTxnIterator { chunks }
+    }
+}
+
+/// An iterator over some columns of all chunks in a transaction.
+pub struct InMemoryTxnIterator {
+    /// The remaining chunks with their ids and the offsets of their visible rows.
+    chunks: VecDeque<(u32, DataChunk, Vec<u32>)>,
+}
+
+impl InMemoryTxnIterator {
+    /// Read the next chunk with at most `expected_size` rows, along with the handles of the rows.
+    ///
+    /// Return `None` if all chunks are exhausted.
+    pub fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> Option<(DataChunk, Vec<RowHandle>)> {
+        let (chunk_id, chunk, rows) = self.chunks.front_mut()?;
+        let size = expected_size.min(rows.len());
+        let batch = rows.drain(..size).collect_vec();
+        let indices = batch.iter().map(|&row| row as usize).collect_vec();
+        let handles = (batch.into_iter())
+            .map(|row_offset| RowHandle {
+                rowset_id: *chunk_id,
+                row_offset,
+            })
+            .collect();
+        let output = (chunk.take(&indices), handles);
+        if rows.is_empty() {
+            self.chunks.pop_front();
+        }
+        Some(output)
+    }
+}
+
+#[async_trait]
+impl Storage for InMemoryStorage {
+    async fn add_table(&self, id: TableRefId, _column_descs: &[ColumnDesc]) -> StorageResult<()> {
+        let mut tables = self.tables.write().unwrap();
+        if tables.contains_key(&id) {
+            return Err(anyhow!("table already exists: {:?}", id).into());
+        }
+        tables.insert(id, InMemoryTable::new(id).into());
+        Ok(())
+    }
+
+    fn get_table(&self, id: TableRefId) -> StorageResult<StorageTableRef> {
+        let table = InMemoryStorage::get_table(self, id)?;
+        Ok(table)
+    }
+
+    fn table_ids(&self) -> Vec<TableRefId> {
+        self.tables.read().unwrap().keys().copied().collect()
+    }
+
+    async fn drop_table(&self, id: TableRefId) -> StorageResult<()> {
+        let mut tables = self.tables.write().unwrap();
+        let table = (tables.remove(&id)).ok_or_else(|| anyhow!("table not found: {:?}", id))?;
+        // hold the lock of the snapshot, so that no transaction is committing to the table
+        let _snapshot = table.snapshot.write().unwrap();
+        table.dropped.store(true, SeqCst);
+        Ok(())
+    }
+
+    async fn truncate_table(&self, id: TableRefId) -> StorageResult<()> {
+        let table = InMemoryStorage::get_table(self, id)?;
+        *table.snapshot.write().unwrap() = Arc::new(Snapshot::default());
+        Ok(())
+    }
+
+    async fn commit_all(&self, txns: Vec<BoxedTransaction>) -> StorageResult<()> {
+        let mut memory_txns = vec![];
+        for txn in txns {
+            match txn.into_any().downcast::<InMemoryTransaction>() {
+                Ok(txn) => memory_txns.push(*txn),
+                Err(_) => return Err(anyhow!("not a transaction of the in-memory storage").into()),
+            }
+        }
+        InMemoryTransaction::commit_all(memory_txns)
+    }
+}
+
+#[async_trait]
+impl Table for InMemoryTable {
+    async fn write(&self) -> StorageResult<BoxedTransaction> {
+        Ok(Box::new(InMemoryTable::write(self)))
+    }
+
+    async fn read(&self) -> StorageResult<BoxedTransaction> {
+        Ok(Box::new(InMemoryTable::read(self)))
+    }
+}
+
+#[async_trait]
+impl Transaction for InMemoryTransaction {
+    async fn append(&mut self, chunk: DataChunk) -> StorageResult<()> {
+        InMemoryTransaction::append(self, chunk)
+    }
+
+    fn delete(&mut self, handles: &[RowHandle]) -> StorageResult<()> {
+        InMemoryTransaction::delete(self, handles)
+    }
+
+    fn upgrade(&mut self) {
+        self.read_only = false;
+    }
+
+    async fn iter_with_ranges(
+        &mut self,
+        column_ids: &[ColumnId],
+        _ranges: &[ColumnRange],
+    ) -> StorageResult<BoxedTxnIterator> {
+        Ok(Box::new(InMemoryTransaction::iter(self, column_ids)))
+    }
+
+    async fn commit(self: Box<Self>) -> StorageResult<()> {
+        InMemoryTransaction::commit_all(vec![*self])
+    }
+
+    fn abort(self: Box<Self>) {}
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send> {
+        self
+    }
+}
+
+#[async_trait]
+impl TxnIterator for InMemoryTxnIterator {
+    async fn next_batch(&mut self, expected_size: usize) -> StorageResult<Option<DataChunk>> {
+        let batch = InMemoryTxnIterator::next_batch_with_handles(self, expected_size);
+        Ok(batch.map(|(chunk, _)| chunk))
+    }
+
+    async fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, Vec<RowHandle>)>> {
+        Ok(InMemoryTxnIterator::next_batch_with_handles(
+            self,
+            expected_size,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::ArrayImpl;
+    use crate::types::DataValue;
+
+    async fn values(txn: &mut dyn Transaction) -> Vec<DataValue> {
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let mut values = vec![];
+        while let Some(chunk) = iter.next_batch(2).await.unwrap() {
+            values.extend((0..chunk.cardinality()).map(|i| chunk.arrays()[0].get(i)));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_storage() {
+        let storage: Arc<dyn Storage> = Arc::new(InMemoryStorage::new());
+        let ids = [TableRefId::new(0, 0), TableRefId::new(0, 1)];
+        for id in ids {
+            storage.add_table(id, &[]).await.unwrap();
+        }
+        let chunk: DataChunk = [ArrayImpl::Int32((1..4).collect())].into_iter().collect();
+        let int = |v: &[i32]| v.iter().map(|&v| DataValue::Int32(v)).collect_vec();
+
+        let mut txns = vec![];
+        for id in ids {
+            let mut txn = storage.get_table(id).unwrap().write().await.unwrap();
+            txn.append(chunk.clone()).await.unwrap();
+            txns.push(txn);
+        }
+        let mut iter = txns[0].iter(&[0]).await.unwrap();
+        let (_, handles) = iter.next_batch_with_handles(1).await.unwrap().unwrap();
+        txns[0].delete(&handles).unwrap();
+        assert_eq!(values(&mut *txns[0]).await, int(&[2, 3]));
+
+        // other transactions only see the committed snapshot
+        let mut other = storage.get_table(ids[0]).unwrap().read().await.unwrap();
+        storage.commit_all(txns).await.unwrap();
+        assert_eq!(values(&mut *other).await, int(&[]));
+
+        for (id, expected) in ids.into_iter().zip([int(&[2, 3]), int(&[1, 2, 3])]) {
+            let mut txn = storage.get_table(id).unwrap().read().await.unwrap();
+            assert_eq!(values(&mut *txn).await, expected);
+        }
+
+        // the writes of an aborted transaction are discarded
+        let mut txn = storage.get_table(ids[1]).unwrap().write().await.unwrap();
+        txn.append(chunk).await.unwrap();
+        txn.abort();
+        let mut txn = storage.get_table(ids[1]).unwrap().read().await.unwrap();
+        assert_eq!(values(&mut *txn).await, int(&[1, 2, 3]));
+    }
+}
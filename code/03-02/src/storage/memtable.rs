@@ -0,0 +1,160 @@
+//! The in-memory write buffer of a transaction.
+//!
+//! Appended chunks are accumulated in a [`MemTable`]. Once the number of rows or the
+//! estimated size reaches the threshold in [`StorageOptions`], the buffered rows are sealed
+//! into a chunk, which is then flushed into a new rowset.
+//!
+//! Each transaction has its own memtable, which bounds the rowsets written by a large insert.
+//! The rows of a small transaction are still flushed into a rowset of their own on commit, so
+//! many small inserts produce many small rowsets, which are merged later by compaction.
+
+use std::mem::discriminant;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use super::{StorageOptions, StorageResult};
+use crate::array::{ArrayBuilderImpl, DataChunk};
+use crate::catalog::ColumnDesc;
+use crate::types::DataValue;
+
+pub struct MemTable {
+    /// Columns of the table.
+    column_descs: Arc<[ColumnDesc]>,
+
+    /// Builders of all columns.
+    builders: Vec<ArrayBuilderImpl>,
+
+    /// Number of rows in the builders.
+    row_count: usize,
+
+    /// Estimated size in bytes of the rows in the builders.
+    byte_size: usize,
+
+    /// Maximum number of rows before sealing.
+    max_rows: usize,
+
+    /// Maximum estimated size in bytes before sealing.
+    max_bytes: usize,
+}
+
+impl MemTable {
+    pub fn new(column_descs: Arc<[ColumnDesc]>, options: &StorageOptions) -> Self {
+        MemTable {
+            builders: new_builders(&column_descs),
+            column_descs,
+            row_count: 0,
+            byte_size: 0,
+            max_rows: options.memtable_max_rows.max(1),
+            max_bytes: options.memtable_max_bytes,
+        }
+    }
+
+    /// Append a chunk, and return all chunks sealed during the appending.
+    pub fn append(&mut self, chunk: DataChunk) -> StorageResult<Vec<DataChunk>> {
+        if chunk.arrays().len() != self.builders.len() {
+            return Err(anyhow!(
+                "expect {} columns, but got {}",
+                self.builders.len(),
+                chunk.arrays().len()
+            )
+            .into());
+        }
+        for (builder, array) in self.builders.iter().zip_eq(chunk.arrays()) {
+            if discriminant(builder) != discriminant(&ArrayBuilderImpl::from_type_of_array(array)) {
+                return Err(anyhow!("column type mismatch").into());
+            }
+        }
+
+        let mut sealed = vec![];
+        for row in 0..chunk.cardinality() {
+            for (builder, array) in self.builders.iter_mut().zip_eq(chunk.arrays()) {
+                let value = array.get(row);
+                self.byte_size += estimated_size(&value);
+                builder.push(&value);
+            }
+            self.row_count += 1;
+            if self.row_count >= self.max_rows || self.byte_size >= self.max_bytes {
+                sealed.extend(self.seal());
+            }
+        }
+        Ok(sealed)
+    }
+
+    /// Seal all buffered rows into a chunk. Return `None` if the memtable is empty.
+    pub fn seal(&mut self) -> Option<DataChunk> {
+        if self.row_count == 0 {
+            return None;
+        }
+        self.row_count = 0;
+        self.byte_size = 0;
+        let builders = std::mem::replace(&mut self.builders, new_builders(&self.column_descs));
+        Some(
+            builders
+                .into_iter()
+                .map(|builder| builder.finish())
+                .collect(),
+        )
+    }
+}
+
+fn new_builders(column_descs: &[ColumnDesc]) -> Vec<ArrayBuilderImpl> {
+    column_descs
+        .iter()
+        .map(|desc| ArrayBuilderImpl::with_capacity(0, desc.datatype()))
+        .collect_vec()
+}
+
+/// Estimate the in-memory size of a value.
+fn estimated_size(value: &DataValue) -> usize {
+    match value {
+        DataValue::Null => 1,
+        DataValue::Bool(_) => 1,
+        DataValue::Int32(_) => 4,
+        DataValue::Float64(_) => 8,
+        DataValue::String(s) => s.len() + 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage, scan};
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_memtable_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(
+            dir.path(),
+            StorageOptions {
+                memtable_max_rows: 4,
+                ..Default::default()
+            },
+        )
+        .await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, []).await;
+
+        let mut txn = table.write().await.unwrap();
+        for i in 0..3 {
+            txn.append(int_chunk(i * 3..i * 3 + 3)).await.unwrap();
+        }
+        // sealed rowsets are not visible before commit
+        assert!(table.snapshot.read().unwrap().rowsets.is_empty());
+        txn.commit().await.unwrap();
+
+        let mut txn = table.read().await.unwrap();
+        let chunks = scan(&mut txn, &[0]).await.unwrap();
+        let sizes = chunks.iter().map(|c| c.cardinality()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![4, 4, 1]);
+        let all = DataChunk::concat(&chunks);
+        for i in 0..9 {
+            assert_eq!(all.arrays()[0].get(i), DataValue::Int32(i as i32));
+        }
+        txn.commit().await.unwrap();
+    }
+}
@@ -1,52 +1,336 @@
 //! On-disk storage
 
+mod backup;
+mod block_cache;
+mod check;
 mod column;
+mod compaction;
+mod delete_vector;
+mod index;
+mod iterator;
+mod manifest;
+mod memory;
+mod memtable;
 mod rowset;
+mod session_txn;
+mod sort_index;
+mod version;
+mod wal;
+mod zone_map;
 
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::atomic::AtomicU32;
-use std::sync::{Arc, RwLock};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicBool, AtomicU32};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use anyhow::anyhow;
+use async_trait::async_trait;
+use itertools::Itertools;
 
+use self::block_cache::BlockCache;
+pub use self::block_cache::BlockCacheStats;
+pub use self::check::{CheckReport, TableReport};
+use self::compaction::{RowSetPin, RowSetPins};
+use self::delete_vector::DeleteVector;
+pub use self::iterator::{DiskTxnIterator, RowSetIterator};
+use self::manifest::{Manifest, ManifestOperation};
+pub use self::memory::{InMemoryStorage, InMemoryTable, InMemoryTransaction, InMemoryTxnIterator};
+use self::memtable::MemTable;
 use self::rowset::{DiskRowset, RowSetBuilder};
+pub use self::session_txn::{SessionTxn, SessionTxnRef};
+pub use self::version::AsOf;
+use self::version::EpochGenerator;
+pub use self::wal::WalSyncMode;
+use self::wal::{Wal, WalRecord, WAL_MAX_ROWS};
+pub use self::zone_map::ColumnRange;
 use crate::array::DataChunk;
-use crate::catalog::{ColumnDesc, TableRefId};
+use crate::catalog::{ColumnDesc, ColumnId, TableRefId};
+
+/// The name of the manifest file under the base path.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The name of the directory under the base path where rowsets are written before publishing.
+const STAGING_DIR_NAME: &str = "staging";
+
+/// The name of the directory of the WAL files under the base path.
+const WAL_DIR_NAME: &str = "wal";
+
+/// The number of rows in a morsel of a parallel scan, unless the rowset is smaller.
+const MORSEL_ROWS: usize = 1 << 16;
 
 /// The error type of storage operations.
 #[derive(thiserror::Error, Debug)]
-#[error("{0:?}")]
-pub struct StorageError(#[from] anyhow::Error);
+pub enum StorageError {
+    /// The data on disk does not pass the validation, e.g. a checksum mismatch.
+    #[error(
+        "data corrupted in table {}, rowset {rowset_id}, column {column_id}: {reason}",
+        table_id.table_id
+    )]
+    Corrupted {
+        table_id: TableRefId,
+        rowset_id: u32,
+        column_id: ColumnId,
+        reason: String,
+    },
+    #[error("{0:?}")]
+    Other(#[from] anyhow::Error),
+}
 
 /// A specialized `Result` type for storage operations.
 pub type StorageResult<T> = std::result::Result<T, StorageError>;
 
-pub type StorageRef = Arc<DiskStorage>;
-pub type StorageTableRef = Arc<DiskTable>;
+pub type StorageRef = Arc<dyn Storage>;
+pub type StorageTableRef = Arc<dyn Table>;
+pub type BoxedTransaction = Box<dyn Transaction>;
+pub type BoxedTxnIterator = Box<dyn TxnIterator>;
+
+/// A storage engine, which is implemented by [`DiskStorage`] and [`InMemoryStorage`].
+#[async_trait]
+pub trait Storage: Send + Sync + 'static {
+    /// Add a table. Returns an error if the storage does not support the type of a column.
+    async fn add_table(&self, id: TableRefId, column_descs: &[ColumnDesc]) -> StorageResult<()>;
+
+    /// Get a table.
+    fn get_table(&self, id: TableRefId) -> StorageResult<StorageTableRef>;
+
+    /// Get the ids of all tables.
+    fn table_ids(&self) -> Vec<TableRefId>;
+
+    /// Drop a table.
+    ///
+    /// The data of the table is removed once all transactions on the table are finished.
+    /// Transactions that wrote to the table can no longer commit.
+    async fn drop_table(&self, id: TableRefId) -> StorageResult<()>;
+
+    /// Remove all rows of a table.
+    ///
+    /// Transactions started before the truncation still read the old rows.
+    async fn truncate_table(&self, id: TableRefId) -> StorageResult<()>;
+
+    /// Commit the transactions on different tables atomically.
+    ///
+    /// All transactions should be started on the tables of this storage. If the commit fails,
+    /// all transactions are aborted.
+    async fn commit_all(&self, txns: Vec<BoxedTransaction>) -> StorageResult<()>;
+
+    /// Write a consistent snapshot of all tables into `dir`, which can be opened as a new
+    /// storage.
+    async fn backup(&self, dir: &Path) -> StorageResult<()> {
+        Err(anyhow!("backup is not supported: {:?}", dir).into())
+    }
+}
+
+/// A table of a storage engine.
+#[async_trait]
+pub trait Table: Send + Sync {
+    /// Start a transaction which may contain writes.
+    async fn write(&self) -> StorageResult<BoxedTransaction>;
+
+    /// Start a transaction which only contains read.
+    async fn read(&self) -> StorageResult<BoxedTransaction>;
+
+    /// Start a transaction which only reads the version of the table as of an epoch or a time
+    /// in the past.
+    async fn read_as_of(&self, as_of: AsOf) -> StorageResult<BoxedTransaction> {
+        Err(anyhow!("time travel is not supported: as of {}", as_of).into())
+    }
+}
+
+/// A transaction on a table, which reads a snapshot of the table together with its own writes.
+///
+/// A transaction dropped without committing or aborting is aborted.
+#[async_trait]
+pub trait Transaction: Send + 'static {
+    /// Append a chunk to the table.
+    async fn append(&mut self, chunk: DataChunk) -> StorageResult<()>;
+
+    /// Delete the rows of the given handles, which are read from this transaction.
+    fn delete(&mut self, handles: &[RowHandle]) -> StorageResult<()>;
+
+    /// Allow a transaction started by [`Table::read`] to write, while still reading the same
+    /// snapshot. Do nothing if the transaction may already write.
+    fn upgrade(&mut self);
+
+    /// Create an iterator over the given columns of the table, which may skip the rows out of
+    /// `ranges`. The chunks yielded by the iterator contain exactly these columns in order.
+    async fn iter_with_ranges(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<BoxedTxnIterator>;
+
+    /// Create an iterator over the given columns of the table.
+    async fn iter(&mut self, column_ids: &[ColumnId]) -> StorageResult<BoxedTxnIterator> {
+        self.iter_with_ranges(column_ids, &[]).await
+    }
+
+    /// Split the scan of [`Transaction::iter_with_ranges`] into morsels, which can be read in
+    /// parallel. Reading the morsels in order yields the same rows as the single iterator.
+    async fn iter_morsels(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<Vec<BoxedTxnIterator>> {
+        Ok(vec![self.iter_with_ranges(column_ids, ranges).await?])
+    }
+
+    /// Commit the transaction.
+    async fn commit(self: Box<Self>) -> StorageResult<()>;
+
+    /// Abort the transaction, discarding its writes.
+    fn abort(self: Box<Self>);
+
+    /// Convert the transaction into [`Any`], so that a storage can downcast its own
+    /// transactions in [`Storage::commit_all`].
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send>;
+}
+
+/// An iterator over the rows read by a transaction.
+#[async_trait]
+pub trait TxnIterator: Send {
+    /// Read the next chunk with at most `expected_size` rows.
+    ///
+    /// Return `None` if all rows are read.
+    async fn next_batch(&mut self, expected_size: usize) -> StorageResult<Option<DataChunk>>;
+
+    /// Read the next chunk with at most `expected_size` rows, along with the handles of the rows.
+    ///
+    /// Return `None` if all rows are read.
+    async fn next_batch_with_handles(
+        &mut self,
+        expected_size: usize,
+    ) -> StorageResult<Option<(DataChunk, Vec<RowHandle>)>>;
+}
+
+/// The position of a row in a table, which is used to delete the row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RowHandle {
+    pub rowset_id: u32,
+    pub row_offset: u32,
+}
 
 /// On-disk storage.
 pub struct DiskStorage {
     /// All tables in the current storage engine.
-    tables: RwLock<HashMap<TableRefId, StorageTableRef>>,
+    tables: RwLock<HashMap<TableRefId, Arc<DiskTable>>>,
 
     /// Generator for RowSet id.
     rowset_id_generator: Arc<AtomicU32>,
 
+    /// Generator for delete vector id.
+    dv_id_generator: Arc<AtomicU32>,
+
+    /// Generator for the epochs of commits.
+    epoch_generator: Arc<EpochGenerator>,
+
     /// The storage options.
     options: Arc<StorageOptions>,
+
+    /// The manifest of the storage.
+    manifest: Arc<Manifest>,
+
+    /// The WAL of the storage.
+    wal: Arc<Wal>,
+
+    /// The block cache shared by all tables.
+    block_cache: Arc<BlockCache>,
 }
 
 pub struct StorageOptions {
     /// The directory of the storage
     pub base_path: PathBuf,
+
+    /// The maximum number of rows in the memtable of a transaction before it is flushed into a
+    /// rowset.
+    pub memtable_max_rows: usize,
+
+    /// The maximum estimated size in bytes of the memtable of a transaction before it is
+    /// flushed into a rowset.
+    pub memtable_max_bytes: usize,
+
+    /// The capacity in bytes of the block cache. The cache is disabled if it is 0.
+    pub block_cache_capacity: usize,
+
+    /// The interval between background compactions. Background compaction is disabled if it
+    /// is `None`.
+    pub compaction_interval: Option<Duration>,
+
+    /// Rowsets with fewer rows are merged by compaction.
+    pub compaction_small_rows: usize,
+
+    /// Rowsets with at least this fraction of rows deleted are rewritten by compaction.
+    pub compaction_delete_ratio: f64,
+
+    /// When to sync the WAL and the manifest on commit.
+    pub wal_sync_mode: WalSyncMode,
+
+    /// A checkpoint is taken after a commit once the current WAL file reaches this size in
+    /// bytes.
+    pub wal_checkpoint_bytes: u64,
+
+    /// How long an old version of a table can still be read after it is replaced by a commit.
+    pub version_retention: Duration,
+
+    /// The maximum number of morsels read in parallel by a scan. Each table is scanned
+    /// sequentially if it is 1, which is the default.
+    ///
+    /// The morsels are read in parallel only on a multi-thread runtime. On the default
+    /// current-thread runtime of the database, a parallelism greater than 1 only adds the cost of
+    /// the tasks and channels.
+    pub scan_parallelism: usize,
+}
+
+impl Default for StorageOptions {
+    fn default() -> Self {
+        StorageOptions {
+            base_path: "risinglight.db".into(),
+            memtable_max_rows: 1 << 20,
+            memtable_max_bytes: 64 << 20,
+            block_cache_capacity: 256 << 20,
+            compaction_interval: Some(Duration::from_secs(10)),
+            compaction_small_rows: 1 << 16,
+            compaction_delete_ratio: 0.5,
+            wal_sync_mode: WalSyncMode::EveryCommit,
+            wal_checkpoint_bytes: 16 << 20,
+            version_retention: Duration::ZERO,
+            scan_parallelism: 1,
+        }
+    }
 }
 
 pub fn err(error: impl Into<anyhow::Error>) -> StorageError {
-    StorageError(error.into())
+    StorageError::Other(error.into())
+}
+
+/// Run a blocking file operation on a thread where blocking is allowed.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> StorageResult<T> + Send + 'static,
+) -> StorageResult<T> {
+    tokio::task::spawn_blocking(f).await.map_err(err)?
+}
+
+/// Remove the directory at `path` on a blocking thread if called in the runtime, which is used
+/// when a table or a rowset is no longer referenced.
+fn remove_dir_in_background(path: PathBuf) {
+    let remove = move || match std::fs::remove_dir_all(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("failed to remove {:?}: {}", path, e)
+        }
+        _ => {}
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => drop(handle.spawn_blocking(remove)),
+        Err(_) => remove(),
+    }
 }
 
 /// An on-disk table.
+///
+/// The table is a handle to the shared states, which is cheap to clone.
+#[derive(Clone)]
 pub struct DiskTable {
     /// Id of the table.
     id: TableRefId,
@@ -60,39 +344,568 @@ pub struct DiskTable {
     /// Generator for RowSet id.
     rowset_id_generator: Arc<AtomicU32>,
 
-    /// RowSets in the table
-    rowsets: RwLock<Vec<DiskRowset>>,
+    /// Generator for delete vector id.
+    dv_id_generator: Arc<AtomicU32>,
+
+    /// Generator for the epochs of commits.
+    epoch_generator: Arc<EpochGenerator>,
+
+    /// The manifest of the storage.
+    manifest: Arc<Manifest>,
+
+    /// The WAL of the storage.
+    wal: Arc<Wal>,
+
+    /// The block cache shared by all tables.
+    block_cache: Arc<BlockCache>,
+
+    /// RowSets and delete vectors in the table
+    snapshot: Arc<RwLock<Snapshot>>,
+
+    /// The versions replaced by later commits in the order of epochs, which are retained for
+    /// time travel. It is locked after `snapshot`.
+    history: Arc<Mutex<VecDeque<Snapshot>>>,
+
+    /// The rowsets visible to the in-flight write transactions, which are not compacted.
+    pins: Arc<RowSetPins>,
+
+    /// Removes the directory of the table once it is dropped and all its clones are dropped.
+    guard: Arc<TableGuard>,
+}
+
+/// Removes the directory of a table once the table is dropped and no longer referenced.
+struct TableGuard {
+    table_path: PathBuf,
+    dropped: AtomicBool,
+}
+
+impl Drop for TableGuard {
+    fn drop(&mut self) {
+        if !self.dropped.load(SeqCst) {
+            return;
+        }
+        info!("removing dropped table {:?}", self.table_path);
+        remove_dir_in_background(std::mem::take(&mut self.table_path));
+    }
+}
+
+/// The rowsets of a table and their delete vectors.
+#[derive(Clone, Default)]
+struct Snapshot {
+    rowsets: Vec<DiskRowset>,
+
+    /// Delete vectors keyed by the id of their rowset.
+    delete_vectors: HashMap<u32, Vec<DeleteVector>>,
+
+    /// The epoch of the commit that created this version.
+    epoch: u64,
+
+    /// The time in milliseconds of the commit that created this version.
+    timestamp: u64,
+}
+
+/// The ids of the rowsets and delete vectors in a version of a table replayed from the manifest.
+#[derive(Clone, Default)]
+struct VersionIds {
+    rowsets: Vec<u32>,
+    dvs: Vec<(u32, u32)>,
+    epoch: u64,
+    timestamp: u64,
+}
+
+/// A table replayed from the manifest.
+struct ReplayedTable {
+    column_descs: Vec<ColumnDesc>,
+    history: Vec<VersionIds>,
+    current: VersionIds,
+
+    /// The WAL files recording the rows of the rowsets not synced on commit, keyed by rowset id.
+    wal_file_ids: HashMap<u32, u64>,
+}
+
+impl ReplayedTable {
+    /// The version changed by the operations of `epoch`, which replaces the current version if
+    /// it is from an earlier epoch.
+    fn version_at(&mut self, epoch: u64, timestamp: u64) -> &mut VersionIds {
+        if self.current.epoch != epoch {
+            self.history.push(self.current.clone());
+            self.current.epoch = epoch;
+            self.current.timestamp = timestamp;
+        }
+        &mut self.current
+    }
+
+    /// Returns true if the rowset is not synced on commit, and may be lost in a crash together
+    /// with its WAL records: it is recorded in a WAL file not removed by a checkpoint, or it is
+    /// only in the old versions, as a lost rowset is only removed from the latest version.
+    fn may_be_lost(&self, rowset_id: u32, oldest_wal_file_id: Option<u64>) -> bool {
+        match self.wal_file_ids.get(&rowset_id) {
+            Some(&file_id) => {
+                matches!(oldest_wal_file_id, Some(oldest) if file_id >= oldest)
+                    || !self.current.rowsets.contains(&rowset_id)
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the old versions that have expired at `now`.
+    fn expire_versions(&mut self, retention: u64, now: u64) {
+        let replaced_at = |i: usize| match self.history.get(i + 1) {
+            Some(version) => version.timestamp,
+            None => self.current.timestamp,
+        };
+        let expired = (0..self.history.len())
+            .take_while(|&i| replaced_at(i) + retention <= now)
+            .count();
+        self.history.drain(..expired);
+    }
+}
+
+/// The state of the storage replayed from the manifest.
+struct ReplayedManifest {
+    tables: HashMap<TableRefId, ReplayedTable>,
+    next_rowset_id: u32,
+    next_dv_id: u32,
+    last_epoch: u64,
+    last_timestamp: u64,
+}
+
+impl ReplayedManifest {
+    /// Replay the operations recorded in the manifest.
+    fn replay(operations: Vec<ManifestOperation>) -> StorageResult<Self> {
+        let mut tables: HashMap<TableRefId, ReplayedTable> = HashMap::new();
+        let mut next_rowset_id = 0;
+        let mut next_dv_id = 0;
+        let (mut epoch, mut timestamp) = (0, 0);
+        let (mut last_epoch, mut last_timestamp) = (0, 0);
+        for op in operations {
+            match op {
+                ManifestOperation::Epoch {
+                    epoch: op_epoch,
+                    timestamp: op_timestamp,
+                } => {
+                    epoch = op_epoch;
+                    timestamp = op_timestamp;
+                    last_epoch = last_epoch.max(epoch);
+                    last_timestamp = last_timestamp.max(timestamp);
+                }
+                ManifestOperation::CreateTable {
+                    table_id,
+                    column_descs,
+                } => {
+                    let table = ReplayedTable {
+                        column_descs,
+                        history: vec![],
+                        current: VersionIds {
+                            epoch,
+                            timestamp,
+                            ..Default::default()
+                        },
+                        wal_file_ids: HashMap::new(),
+                    };
+                    tables.insert(table_id, table);
+                }
+                ManifestOperation::AddRowSet {
+                    table_id,
+                    rowset_id,
+                    wal_file_id,
+                } => {
+                    let table = tables
+                        .get_mut(&table_id)
+                        .ok_or_else(|| anyhow!("rowset added to unknown table: {:?}", table_id))?;
+                    table.version_at(epoch, timestamp).rowsets.push(rowset_id);
+                    if let Some(file_id) = wal_file_id {
+                        table.wal_file_ids.insert(rowset_id, file_id);
+                    }
+                    next_rowset_id = next_rowset_id.max(rowset_id + 1);
+                }
+                ManifestOperation::DeleteRowSet {
+                    table_id,
+                    rowset_id,
+                } => {
+                    let version = tables
+                        .get_mut(&table_id)
+                        .ok_or_else(|| {
+                            anyhow!("rowset deleted from unknown table: {:?}", table_id)
+                        })?
+                        .version_at(epoch, timestamp);
+                    version.rowsets.retain(|&id| id != rowset_id);
+                    version.dvs.retain(|&(id, _)| id != rowset_id);
+                }
+                ManifestOperation::AddDeleteVector {
+                    table_id,
+                    rowset_id,
+                    dv_id,
+                } => {
+                    tables
+                        .get_mut(&table_id)
+                        .ok_or_else(|| {
+                            anyhow!("delete vector added to unknown table: {:?}", table_id)
+                        })?
+                        .version_at(epoch, timestamp)
+                        .dvs
+                        .push((rowset_id, dv_id));
+                    next_dv_id = next_dv_id.max(dv_id + 1);
+                }
+                ManifestOperation::DropTable { table_id } => {
+                    tables
+                        .remove(&table_id)
+                        .ok_or_else(|| anyhow!("drop unknown table: {:?}", table_id))?;
+                }
+            }
+        }
+        Ok(ReplayedManifest {
+            tables,
+            next_rowset_id,
+            next_dv_id,
+            last_epoch,
+            last_timestamp,
+        })
+    }
 }
 
 impl DiskStorage {
-    /// Create a new in-memory storage.
-    pub fn new(options: StorageOptions) -> Self {
-        DiskStorage {
+    /// Open the storage at `options.base_path`.
+    ///
+    /// All tables, rowsets and delete vectors are recovered from the manifest, and the rowsets
+    /// recorded in the WAL are rebuilt. A rowset not synced on commit is dropped with its delete
+    /// vectors if its files are torn and its WAL records are lost, which happens if the system
+    /// crashes before the WAL is synced.
+    pub async fn open(options: StorageOptions) -> StorageResult<Self> {
+        tokio::fs::create_dir_all(&options.base_path)
+            .await
+            .map_err(err)?;
+        let (base_path, wal_sync_mode) = (options.base_path.clone(), options.wal_sync_mode);
+        let ((manifest, operations), (wal, records), oldest_wal_file_id) =
+            run_blocking(move || {
+                let manifest = Manifest::open(base_path.join(MANIFEST_FILE_NAME))?;
+                let wal = Wal::open(base_path.join(WAL_DIR_NAME), wal_sync_mode)?;
+                let oldest_wal_file_id = Wal::oldest_file_id(base_path.join(WAL_DIR_NAME))?;
+                Ok((manifest, wal, oldest_wal_file_id))
+            })
+            .await?;
+
+        let ReplayedManifest {
+            tables: replayed_tables,
+            next_rowset_id,
+            next_dv_id,
+            last_epoch,
+            last_timestamp,
+        } = ReplayedManifest::replay(operations)?;
+
+        let retention = options.version_retention.as_millis() as u64;
+        let storage = DiskStorage {
             tables: RwLock::new(HashMap::new()),
+            block_cache: Arc::new(BlockCache::new(options.block_cache_capacity)),
             options: Arc::new(options),
-            rowset_id_generator: Arc::new(AtomicU32::new(0)),
+            rowset_id_generator: Arc::new(AtomicU32::new(next_rowset_id)),
+            dv_id_generator: Arc::new(AtomicU32::new(next_dv_id)),
+            epoch_generator: Arc::new(EpochGenerator::new(last_epoch, last_timestamp)),
+            manifest: Arc::new(manifest),
+            wal: Arc::new(wal),
+        };
+        let mut table_records: HashMap<TableRefId, Vec<WalRecord>> = HashMap::new();
+        for record in records {
+            table_records
+                .entry(record.table_id)
+                .or_default()
+                .push(record);
+        }
+        for (id, mut replayed) in replayed_tables {
+            replayed.expire_versions(retention, version::now());
+            let versions = replayed.history.iter().chain([&replayed.current]);
+            let rowset_ids: HashSet<u32> =
+                (versions.clone().flat_map(|v| &v.rowsets).copied()).collect();
+            let dv_ids: HashSet<(u32, u32)> = (versions.flat_map(|v| &v.dvs).copied()).collect();
+
+            let table = storage.new_table(id, &replayed.column_descs);
+            // rebuild the rowsets which may not be synced, unless they are no longer in the table
+            let mut rebuilt = HashSet::new();
+            for record in table_records.remove(&id).unwrap_or_default() {
+                if rowset_ids.contains(&record.rowset_id) {
+                    table.rebuild_rowset(&record).await?;
+                    rebuilt.insert(record.rowset_id);
+                }
+            }
+            let mut rowsets = HashMap::new();
+            let mut lost = vec![];
+            for &rowset_id in &rowset_ids {
+                let rowset = DiskRowset::open(
+                    id,
+                    table.column_descs.clone(),
+                    table.block_cache.clone(),
+                    rowset_id,
+                    table.rowset_path_of(rowset_id),
+                )
+                .await;
+                // the files may be torn if the WAL records are lost as well
+                let may_be_lost = !rebuilt.contains(&rowset_id)
+                    && replayed.may_be_lost(rowset_id, oldest_wal_file_id);
+                let rowset = match rowset {
+                    Ok(rowset) if may_be_lost => rowset.verify().await.map(|_| rowset),
+                    rowset => rowset,
+                };
+                match rowset {
+                    Ok(rowset) => {
+                        rowsets.insert(rowset_id, rowset);
+                    }
+                    Err(e) if may_be_lost => {
+                        warn!(
+                            "dropping rowset {} of table {:?} lost in a crash: {}",
+                            rowset_id, id, e
+                        );
+                        if replayed.current.rowsets.contains(&rowset_id) {
+                            lost.push(rowset_id);
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            let mut dvs = HashMap::new();
+            for &(rowset_id, dv_id) in &dv_ids {
+                if !rowsets.contains_key(&rowset_id) {
+                    continue;
+                }
+                let dv = DeleteVector::open(dv_id, rowset_id, table.dv_path_of(rowset_id, dv_id))
+                    .await?;
+                dvs.insert((rowset_id, dv_id), dv);
+            }
+            let snapshot_of = |version: &VersionIds| {
+                let mut delete_vectors: HashMap<u32, Vec<DeleteVector>> = HashMap::new();
+                for id in &version.dvs {
+                    if let Some(dv) = dvs.get(id) {
+                        delete_vectors.entry(id.0).or_default().push(dv.clone());
+                    }
+                }
+                Snapshot {
+                    rowsets: version
+                        .rowsets
+                        .iter()
+                        .filter_map(|id| rowsets.get(id).cloned())
+                        .collect(),
+                    delete_vectors,
+                    epoch: version.epoch,
+                    timestamp: version.timestamp,
+                }
+            };
+            *table.history.lock().unwrap() = replayed.history.iter().map(snapshot_of).collect();
+            let mut snapshot = snapshot_of(&replayed.current);
+            if !lost.is_empty() {
+                // otherwise the lost rowsets are taken as corrupted once the WAL is checkpointed
+                let (epoch, timestamp) = storage.epoch_generator.next();
+                let mut operations = vec![ManifestOperation::Epoch { epoch, timestamp }];
+                operations.extend(
+                    lost.iter()
+                        .map(|&rowset_id| ManifestOperation::DeleteRowSet {
+                            table_id: id,
+                            rowset_id,
+                        }),
+                );
+                storage.manifest.append(&operations).await?;
+                table.commit_version(&mut snapshot, epoch, timestamp);
+            }
+            *table.snapshot.write().unwrap() = snapshot;
+            info!(
+                "recovered table {:?} with {} rowsets and {} delete vectors in {} versions",
+                id,
+                rowsets.len(),
+                dvs.len(),
+                replayed.history.len() + 1,
+            );
+            storage.tables.write().unwrap().insert(id, table.into());
         }
+        storage.remove_garbage().await?;
+        // all rowsets recorded in the WAL are rebuilt
+        storage.wal.checkpoint().await?;
+        Ok(storage)
     }
 
-    /// Add a table.
-    pub fn add_table(&self, id: TableRefId, column_descs: &[ColumnDesc]) -> StorageResult<()> {
-        let mut tables = self.tables.write().unwrap();
-        let table = DiskTable {
+    /// Remove the files left by unfinished writes and dropped tables: the staging directory,
+    /// and table directories, rowset directories and delete vectors not recorded in the manifest.
+    async fn remove_garbage(&self) -> StorageResult<()> {
+        let staging_path = self.options.base_path.join(STAGING_DIR_NAME);
+        if staging_path.exists() {
+            info!("removing staging directory {:?}", staging_path);
+            tokio::fs::remove_dir_all(&staging_path)
+                .await
+                .map_err(err)?;
+        }
+        let tables = self.tables.read().unwrap().values().cloned().collect_vec();
+        let table_ids = tables
+            .iter()
+            .map(|table| table.id.table_id)
+            .collect::<HashSet<_>>();
+        let mut entries = (tokio::fs::read_dir(&self.options.base_path).await).map_err(err)?;
+        while let Some(entry) = entries.next_entry().await.map_err(err)? {
+            let table_id = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                Some(id) => id,
+                None => continue,
+            };
+            if !table_ids.contains(&table_id) {
+                warn!("removing dropped table {:?}", entry.path());
+                tokio::fs::remove_dir_all(entry.path()).await.map_err(err)?;
+            }
+        }
+        for table in tables {
+            let table_path = table.table_path();
+            if !table_path.exists() {
+                continue;
+            }
+            let (rowset_ids, dv_paths) = {
+                let snapshot = table.snapshot.read().unwrap();
+                let history = table.history.lock().unwrap();
+                let versions = history.iter().chain([&*snapshot]);
+                let rowset_ids = (versions.clone().flat_map(|v| &v.rowsets))
+                    .map(|rowset| rowset.rowset_id())
+                    .collect::<HashSet<_>>();
+                let dv_paths = (versions.flat_map(|v| v.delete_vectors.values().flatten()))
+                    .map(|dv| table.dv_path_of(dv.rowset_id(), dv.dv_id()))
+                    .collect::<HashSet<_>>();
+                (rowset_ids, dv_paths)
+            };
+            let mut entries = tokio::fs::read_dir(&table_path).await.map_err(err)?;
+            while let Some(entry) = entries.next_entry().await.map_err(err)? {
+                let path = entry.path();
+                if path.extension() == Some("del".as_ref()) {
+                    if !dv_paths.contains(&path) {
+                        warn!("removing unrecorded delete vector {:?}", path);
+                        tokio::fs::remove_file(&path).await.map_err(err)?;
+                    }
+                    continue;
+                }
+                let rowset_id = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                if !rowset_ids.contains(&rowset_id) {
+                    warn!("removing unrecorded rowset {:?}", path);
+                    tokio::fs::remove_dir_all(&path).await.map_err(err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn new_table(&self, id: TableRefId, column_descs: &[ColumnDesc]) -> DiskTable {
+        let guard = TableGuard {
+            table_path: self.options.base_path.join(id.table_id.to_string()),
+            dropped: AtomicBool::new(false),
+        };
+        DiskTable {
             id,
+            guard: Arc::new(guard),
             options: self.options.clone(),
             column_descs: column_descs.into(),
-            rowsets: RwLock::new(Vec::new()),
+            snapshot: Arc::new(RwLock::new(Snapshot::default())),
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            pins: Arc::new(RowSetPins::default()),
             rowset_id_generator: self.rowset_id_generator.clone(),
-        };
-        let res = tables.insert(id, table.into());
-        if res.is_some() {
-            return Err(anyhow!("table already exists: {:?}", id).into());
+            dv_id_generator: self.dv_id_generator.clone(),
+            epoch_generator: self.epoch_generator.clone(),
+            manifest: self.manifest.clone(),
+            wal: self.wal.clone(),
+            block_cache: self.block_cache.clone(),
         }
+    }
+
+    /// Add a table.
+    pub async fn add_table(
+        &self,
+        id: TableRefId,
+        column_descs: &[ColumnDesc],
+    ) -> StorageResult<()> {
+        for desc in column_descs {
+            column::check_data_type(desc.datatype())?;
+        }
+        let appended = {
+            let mut tables = self.tables.write().unwrap();
+            if tables.contains_key(&id) {
+                return Err(anyhow!("table already exists: {:?}", id).into());
+            }
+            let (epoch, timestamp) = self.epoch_generator.next();
+            let appended = self.manifest.write(&[
+                ManifestOperation::Epoch { epoch, timestamp },
+                ManifestOperation::CreateTable {
+                    table_id: id,
+                    column_descs: column_descs.into(),
+                },
+            ])?;
+            let table = self.new_table(id, column_descs);
+            {
+                let mut snapshot = table.snapshot.write().unwrap();
+                snapshot.epoch = epoch;
+                snapshot.timestamp = timestamp;
+            }
+            tables.insert(id, table.into());
+            appended
+        };
+        self.manifest.sync(appended, None).await
+    }
+
+    /// Drop a table.
+    ///
+    /// The directory of the table is removed once all transactions on the table are finished.
+    /// Transactions that wrote to the table can no longer commit.
+    pub async fn drop_table(&self, id: TableRefId) -> StorageResult<()> {
+        let appended = {
+            let mut tables = self.tables.write().unwrap();
+            let table =
+                (tables.get(&id).cloned()).ok_or_else(|| anyhow!("table not found: {:?}", id))?;
+            let mut snapshot = table.snapshot.write().unwrap();
+            let appended = self
+                .manifest
+                .write(&[ManifestOperation::DropTable { table_id: id }])?;
+            tables.remove(&id);
+            table.guard.dropped.store(true, SeqCst);
+            // cancel the running compaction of the table
+            *snapshot = Snapshot::default();
+            table.history.lock().unwrap().clear();
+            appended
+        };
+        self.manifest.sync(appended, None).await?;
+        info!("dropped table {:?}", id);
+        Ok(())
+    }
+
+    /// Remove all rows of a table.
+    ///
+    /// Transactions started before the truncation still read the old rowsets, whose files are
+    /// removed once these transactions are finished and the old version expires.
+    pub async fn truncate_table(&self, id: TableRefId) -> StorageResult<()> {
+        let table = self.get_table(id)?;
+        let appended = {
+            let mut snapshot = table.snapshot.write().unwrap();
+            let mut operations = (snapshot.rowsets.iter())
+                .map(|rowset| ManifestOperation::DeleteRowSet {
+                    table_id: id,
+                    rowset_id: rowset.rowset_id(),
+                })
+                .collect_vec();
+            if operations.is_empty() {
+                return Ok(());
+            }
+            let (epoch, timestamp) = self.epoch_generator.next();
+            operations.insert(0, ManifestOperation::Epoch { epoch, timestamp });
+            let appended = self.manifest.write(&operations)?;
+            table.commit_version(&mut snapshot, epoch, timestamp);
+            snapshot.rowsets.clear();
+            snapshot.delete_vectors.clear();
+            appended
+        };
+        // the old rowsets are removed once their version expires, after the manifest is synced
+        self.manifest.sync(appended, None).await?;
+        table.expire_versions().await;
+        info!("truncated table {:?}", id);
         Ok(())
     }
 
+    /// Get the statistics of the block cache.
+    pub fn block_cache_stats(&self) -> BlockCacheStats {
+        self.block_cache.stats()
+    }
+
     /// Get a table.
-    pub fn get_table(&self, id: TableRefId) -> StorageResult<StorageTableRef> {
+    pub fn get_table(&self, id: TableRefId) -> StorageResult<Arc<DiskTable>> {
         let tables = self.tables.read().unwrap();
         tables
             .get(&id)
@@ -103,29 +916,138 @@ impl DiskStorage {
 
 impl DiskTable {
     /// Start a transaction which only contains write.
-    pub async fn write(self: &Arc<Self>) -> StorageResult<DiskTransaction> {
-        let rowsets = self.rowsets.read().unwrap();
+    pub async fn write(&self) -> StorageResult<DiskTransaction> {
+        let snapshot = self.snapshot.read().unwrap();
         Ok(DiskTransaction {
             read_only: false,
             table: self.clone(),
-            rowset_snapshot: rowsets.clone(),
-            builder: None,
+            pin: Some(self.pins.pin(&snapshot)),
+            snapshot: snapshot.clone(),
+            memtable: None,
+            flushed_rowsets: vec![],
+            wal_records: vec![],
+            deleted_rows: HashMap::new(),
             finished: false,
         })
     }
 
     /// Start a transaction which only contains read.
-    pub async fn read(self: &Arc<Self>) -> StorageResult<DiskTransaction> {
-        let rowsets = self.rowsets.read().unwrap();
+    pub async fn read(&self) -> StorageResult<DiskTransaction> {
+        let snapshot = self.snapshot.read().unwrap();
+        Ok(DiskTransaction {
+            read_only: true,
+            table: self.clone(),
+            pin: None,
+            snapshot: snapshot.clone(),
+            memtable: None,
+            flushed_rowsets: vec![],
+            wal_records: vec![],
+            deleted_rows: HashMap::new(),
+            finished: false,
+        })
+    }
+
+    /// Start a transaction which only reads the last version of the table committed at or
+    /// before the epoch or the time of `as_of`.
+    ///
+    /// Return an error if the version has expired.
+    pub async fn read_as_of(&self, as_of: AsOf) -> StorageResult<DiskTransaction> {
+        let snapshot = {
+            let snapshot = self.snapshot.read().unwrap();
+            let history = self.history.lock().unwrap();
+            let version = (history.iter().chain([&*snapshot]).rev()).find(|v| match as_of {
+                AsOf::Epoch(epoch) => v.epoch <= epoch,
+                AsOf::Timestamp(timestamp) => v.timestamp <= timestamp,
+            });
+            version
+                .cloned()
+                .ok_or_else(|| anyhow!("no version of table {:?} as of {}", self.id, as_of))?
+        };
         Ok(DiskTransaction {
             read_only: true,
             table: self.clone(),
-            rowset_snapshot: rowsets.clone(),
-            builder: None,
+            pin: None,
+            snapshot,
+            memtable: None,
+            flushed_rowsets: vec![],
+            wal_records: vec![],
+            deleted_rows: HashMap::new(),
             finished: false,
         })
     }
 
+    /// Start a new version of the table committed at `epoch`, retaining the current version in
+    /// the history. The caller then applies the changes of the commit to `snapshot`, which is
+    /// locked for write.
+    fn commit_version(&self, snapshot: &mut Snapshot, epoch: u64, timestamp: u64) {
+        self.history.lock().unwrap().push_back(snapshot.clone());
+        snapshot.epoch = epoch;
+        snapshot.timestamp = timestamp;
+    }
+
+    /// Remove the old versions which have been replaced for longer than the retention, along
+    /// with the rowsets and delete vectors that are no longer in any version.
+    ///
+    /// The files of a removed rowset are deleted once the transactions reading it are finished.
+    async fn expire_versions(&self) {
+        let retention = self.options.version_retention.as_millis() as u64;
+        let now = version::now();
+        let mut expired = vec![];
+        let (live_rowsets, live_dvs) = {
+            let snapshot = self.snapshot.read().unwrap();
+            let mut history = self.history.lock().unwrap();
+            loop {
+                let replaced_at = match history.get(1) {
+                    Some(version) => version.timestamp,
+                    None => snapshot.timestamp,
+                };
+                if history.is_empty() || replaced_at + retention > now {
+                    break;
+                }
+                expired.push(history.pop_front().unwrap());
+            }
+            if expired.is_empty() {
+                return;
+            }
+            let versions = history.iter().chain([&*snapshot]);
+            let live_rowsets = (versions.clone().flat_map(|v| &v.rowsets))
+                .map(|rowset| rowset.rowset_id())
+                .collect::<HashSet<_>>();
+            let live_dvs = (versions.flat_map(|v| v.delete_vectors.values().flatten()))
+                .map(|dv| (dv.rowset_id(), dv.dv_id()))
+                .collect::<HashSet<_>>();
+            (live_rowsets, live_dvs)
+        };
+        for rowset in expired.iter().flat_map(|v| &v.rowsets) {
+            if !live_rowsets.contains(&rowset.rowset_id()) {
+                rowset.mark_obsolete();
+            }
+        }
+        // delete vectors are only read when the storage is opened
+        let expired_dvs = (expired.iter())
+            .flat_map(|v| v.delete_vectors.values().flatten())
+            .map(|dv| (dv.rowset_id(), dv.dv_id()))
+            .filter(|id| !live_dvs.contains(id))
+            .collect::<HashSet<_>>();
+        let paths = (expired_dvs.into_iter())
+            .map(|(rowset_id, dv_id)| self.dv_path_of(rowset_id, dv_id))
+            .collect_vec();
+        let removed = run_blocking(move || {
+            for path in paths {
+                match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("failed to remove {:?}: {}", path, e)
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = removed.await {
+            warn!("failed to remove expired delete vectors: {}", e);
+        }
+    }
+
     pub fn table_path(&self) -> PathBuf {
         self.options.base_path.join(self.id.table_id.to_string())
     }
@@ -133,6 +1055,55 @@ impl DiskTable {
     pub fn rowset_path_of(&self, rowset_id: u32) -> PathBuf {
         self.table_path().join(rowset_id.to_string())
     }
+
+    pub fn dv_path_of(&self, rowset_id: u32, dv_id: u32) -> PathBuf {
+        self.table_path().join(dv_file_name(rowset_id, dv_id))
+    }
+
+    /// The path where a file or directory is written before being moved into the table.
+    fn staging_path_of(&self, name: impl AsRef<std::path::Path>) -> PathBuf {
+        self.options.base_path.join(STAGING_DIR_NAME).join(name)
+    }
+
+    /// Sync the manifest, and then checkpoint the WAL. The manifest is synced first, so that the
+    /// commits whose WAL records are removed are durable.
+    async fn checkpoint(&self) -> StorageResult<()> {
+        self.manifest.sync_all().await?;
+        self.wal.checkpoint().await
+    }
+
+    /// Rebuild a rowset from its rows recorded in the WAL, replacing the files that may be
+    /// partially written.
+    async fn rebuild_rowset(&self, record: &WalRecord) -> StorageResult<()> {
+        let rowset_path = self.rowset_path_of(record.rowset_id);
+        let staging_path = self.staging_path_of(record.rowset_id.to_string());
+        for path in [&rowset_path, &staging_path] {
+            match tokio::fs::remove_dir_all(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(err(e)),
+                _ => {}
+            }
+        }
+        let mut builder = RowSetBuilder::new(self.id, self.column_descs.clone())?;
+        builder.append(record.to_chunk(&self.column_descs)?)?;
+        let block_cache = self.block_cache.clone();
+        (builder.flush(
+            block_cache,
+            record.rowset_id,
+            staging_path,
+            rowset_path,
+            true,
+        ))
+        .await?;
+        info!(
+            "rebuilt rowset {} of table {:?} from WAL",
+            record.rowset_id, self.id
+        );
+        Ok(())
+    }
+}
+
+fn dv_file_name(rowset_id: u32, dv_id: u32) -> String {
+    format!("{}_{}.del", rowset_id, dv_id)
 }
 
 pub struct DiskTransaction {
@@ -140,17 +1111,30 @@ pub struct DiskTransaction {
     read_only: bool,
 
     /// Reference to table object
-    table: Arc<DiskTable>,
+    table: DiskTable,
+
+    /// Current snapshot of RowSets and delete vectors
+    snapshot: Snapshot,
+
+    /// Keeps the RowSets of the snapshot from being compacted, if the txn is not read only
+    pin: Option<RowSetPin>,
+
+    /// Buffer of the appended rows
+    memtable: Option<MemTable>,
+
+    /// RowSets flushed from the memtable, which become visible on commit
+    flushed_rowsets: Vec<DiskRowset>,
 
-    /// Current snapshot of RowSets
-    rowset_snapshot: Vec<DiskRowset>,
+    /// Rows of the flushed rowsets that are not synced, which are recorded in the WAL on commit
+    wal_records: Vec<WalRecord>,
 
-    /// Builder for the RowSet
-    builder: Option<RowSetBuilder>,
+    /// Offsets of the rows deleted by the transaction keyed by rowset id, which become
+    /// invisible on commit
+    deleted_rows: HashMap<u32, Vec<u32>>,
 
     /// Indicates whether the transaction is committed or aborted. If
-    /// the [`SecondaryTransaction`] object is dropped without finishing,
-    /// the transaction will panic.
+    /// the [`DiskTransaction`] object is dropped without finishing,
+    /// the transaction is aborted with a warning.
     finished: bool,
 }
 
@@ -158,6 +1142,41 @@ impl Drop for DiskTransaction {
     fn drop(&mut self) {
         if !self.finished {
             warn!("Transaction dropped without committing or aborting");
+            self.discard();
+        }
+    }
+}
+
+/// The rowsets and delete vectors written by a transaction, which are not yet published.
+#[derive(Default)]
+struct TxnChanges {
+    rowsets: Vec<DiskRowset>,
+    dvs: Vec<DeleteVector>,
+
+    /// Rows of the rowsets that are not synced.
+    wal_records: Vec<WalRecord>,
+
+    /// The WAL files recording the rows of the rowsets that are not synced, keyed by rowset id.
+    wal_file_ids: HashMap<u32, u64>,
+}
+
+impl TxnChanges {
+    /// Remove the files of the changes, which are never published.
+    async fn discard(self, table: &DiskTable) {
+        for rowset in self.rowsets {
+            rowset.mark_obsolete();
+        }
+        for dv in self.dvs {
+            let path = table.dv_path_of(dv.rowset_id(), dv.dv_id());
+            let staging_path = table.staging_path_of(dv_file_name(dv.rowset_id(), dv.dv_id()));
+            for path in [path, staging_path] {
+                match tokio::fs::remove_file(&path).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("failed to remove {:?}: {}", path, e)
+                    }
+                    _ => {}
+                }
+            }
         }
     }
 }
@@ -168,41 +1187,721 @@ impl DiskTransaction {
         if self.read_only {
             return Err(anyhow!("cannot append chunks in read only txn!").into());
         }
-        if self.builder.is_none() {
-            self.builder = Some(RowSetBuilder::new(self.table.column_descs.clone()));
+        let table = &self.table;
+        let memtable = self
+            .memtable
+            .get_or_insert_with(|| MemTable::new(table.column_descs.clone(), &table.options));
+
+        for chunk in memtable.append(chunk)? {
+            self.flush(chunk, true).await?;
         }
-        let builder = self.builder.as_mut().unwrap();
 
+        Ok(())
+    }
+
+    /// Write a sealed chunk into a new rowset.
+    ///
+    /// If `sync` is false, the rowset is not synced, and the chunk is recorded in the WAL on
+    /// commit.
+    async fn flush(&mut self, chunk: DataChunk, sync: bool) -> StorageResult<()> {
+        let rowset_id = self.table.rowset_id_generator.fetch_add(1, SeqCst);
+        if !sync {
+            (self.wal_records).push(WalRecord::new(self.table.id, rowset_id, &chunk));
+        }
+        let mut builder = RowSetBuilder::new(self.table.id, self.table.column_descs.clone())?;
         builder.append(chunk)?;
+        let rowset = builder
+            .flush(
+                self.table.block_cache.clone(),
+                rowset_id,
+                self.table.staging_path_of(rowset_id.to_string()),
+                self.table.rowset_path_of(rowset_id),
+                sync,
+            )
+            .await?;
+        self.flushed_rowsets.push(rowset);
+        Ok(())
+    }
+
+    /// Allow the transaction to write if it is read only, while still reading the same snapshot.
+    ///
+    /// The rowsets of the snapshot are pinned from now on. Those already compacted by then are
+    /// not in the table anymore, so deleting their rows fails on commit.
+    pub fn upgrade(&mut self) {
+        if self.read_only {
+            // lock the snapshot, so that compaction cannot replace the rowsets meanwhile
+            let _current = self.table.snapshot.read().unwrap();
+            self.pin = Some(self.table.pins.pin(&self.snapshot));
+            self.read_only = false;
+        }
+    }
+
+    /// Delete the rows of the given handles, which are read from this transaction.
+    ///
+    /// The rows become invisible to the iterators created afterwards by this transaction, and to
+    /// the transactions started after the commit.
+    pub fn delete(&mut self, handles: &[RowHandle]) -> StorageResult<()> {
+        if self.read_only {
+            return Err(anyhow!("cannot delete rows in read only txn!").into());
+        }
+        for handle in handles {
+            self.deleted_rows
+                .entry(handle.rowset_id)
+                .or_default()
+                .push(handle.row_offset);
+        }
+        Ok(())
+    }
+
+    pub async fn commit(self) -> StorageResult<()> {
+        Self::commit_all(vec![self]).await
+    }
 
+    /// Commit the transactions on different tables atomically.
+    ///
+    /// The rows of the unsynced rowsets are recorded in the WAL, and then the rowsets and delete
+    /// vectors of all transactions are published in one manifest batch, which is synced
+    /// according to the [`WalSyncMode`]. If the commit fails, all transactions are aborted.
+    pub async fn commit_all(mut txns: Vec<DiskTransaction>) -> StorageResult<()> {
+        let mut changes = vec![];
+        let mut result = Ok(());
+        for txn in &mut txns {
+            txn.finished = true;
+            changes.push(TxnChanges::default());
+            result = txn.prepare(changes.last_mut().unwrap()).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = Self::log(&txns, &mut changes).await;
+        }
+        let logged = changes
+            .iter()
+            .any(|changes| !changes.wal_file_ids.is_empty());
+        let mut appended = None;
+        if result.is_ok() {
+            match Self::publish(&txns, &mut changes) {
+                Ok(batch) => appended = batch,
+                Err(e) => result = Err(e),
+            }
+        }
+        if result.is_err() {
+            for txn in &mut txns {
+                txn.discard();
+            }
+            for (txn, changes) in txns.iter().zip(changes) {
+                changes.discard(&txn.table).await;
+            }
+            return result;
+        }
+        let table = match txns.first() {
+            Some(txn) => &txn.table,
+            None => return Ok(()),
+        };
+        if let Some(appended) = appended {
+            match table.options.wal_sync_mode {
+                WalSyncMode::EveryCommit => table.manifest.sync(appended, None).await?,
+                // the commits recording rows in the WAL have waited for the interval
+                WalSyncMode::Group(interval) => {
+                    let delay = if logged { None } else { Some(interval) };
+                    table.manifest.sync(appended, delay).await?;
+                }
+                WalSyncMode::None => table.manifest.flush().await?,
+            }
+            for txn in &txns {
+                txn.table.expire_versions().await;
+            }
+        }
+        if table.wal.file_size() >= table.options.wal_checkpoint_bytes {
+            // the commit is already published, so a failed checkpoint is retried later
+            if let Err(e) = table.checkpoint().await {
+                warn!("failed to checkpoint WAL: {}", e);
+            }
+        }
         Ok(())
     }
 
-    pub async fn commit(mut self) -> StorageResult<()> {
+    /// Abort the transaction. The appended rows and the deleted rows are discarded, and the
+    /// rowsets flushed by the transaction are removed.
+    pub fn abort(mut self) {
         self.finished = true;
+        self.discard();
+    }
+
+    /// Discard the writes of the transaction.
+    fn discard(&mut self) {
+        self.memtable = None;
+        self.wal_records.clear();
+        self.deleted_rows.clear();
+        for rowset in self.flushed_rowsets.drain(..) {
+            rowset.mark_obsolete();
+        }
+    }
+
+    /// Record the rows of the unsynced rowsets of the transactions in the WAL.
+    async fn log(txns: &[DiskTransaction], changes: &mut [TxnChanges]) -> StorageResult<()> {
+        let mut records = vec![];
+        let mut rowset_paths = vec![];
+        let mut txn_indices = vec![];
+        for (i, (txn, changes)) in txns.iter().zip(changes.iter_mut()).enumerate() {
+            for record in std::mem::take(&mut changes.wal_records) {
+                rowset_paths.push(txn.table.rowset_path_of(record.rowset_id));
+                records.push(record);
+                txn_indices.push(i);
+            }
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+        let file_id = txns[0].table.wal.append(&records, rowset_paths).await?;
+        for (record, i) in records.iter().zip(txn_indices) {
+            changes[i].wal_file_ids.insert(record.rowset_id, file_id);
+        }
+        Ok(())
+    }
 
-        if let Some(builder) = self.builder.take() {
-            use std::sync::atomic::Ordering::SeqCst;
-            let rowset_id = self.table.rowset_id_generator.fetch_add(1, SeqCst);
-            let rowset_path = self
-                .table
-                .options
-                .base_path
-                .join(self.table.rowset_path_of(rowset_id));
-            let rowset = builder.flush(rowset_id, rowset_path).await?;
-            let mut rowsets = self.table.rowsets.write().unwrap();
-            rowsets.push(rowset);
+    /// Publish the prepared changes of the transactions to the manifest and the snapshots of the
+    /// tables.
+    ///
+    /// The manifest batch is not synced. Return the number of bytes of the manifest to sync, or
+    /// `None` if there is nothing to publish.
+    fn publish(txns: &[DiskTransaction], changes: &mut [TxnChanges]) -> StorageResult<Option<u64>> {
+        let mut operations = (txns.iter().zip(changes.iter()))
+            .flat_map(|(txn, changes)| {
+                let table_id = txn.table.id;
+                let add_rowsets =
+                    (changes.rowsets.iter()).map(move |rowset| ManifestOperation::AddRowSet {
+                        table_id,
+                        rowset_id: rowset.rowset_id(),
+                        wal_file_id: changes.wal_file_ids.get(&rowset.rowset_id()).copied(),
+                    });
+                let add_dvs =
+                    (changes.dvs.iter()).map(move |dv| ManifestOperation::AddDeleteVector {
+                        table_id,
+                        rowset_id: dv.rowset_id(),
+                        dv_id: dv.dv_id(),
+                    });
+                add_rowsets.chain(add_dvs)
+            })
+            .collect_vec();
+        if operations.is_empty() {
+            return Ok(None);
         }
 
+        // lock the snapshots in the order of table ids to avoid deadlocks
+        let mut order = (0..txns.len()).collect_vec();
+        order.sort_by_key(|&i| (txns[i].table.id.schema_id, txns[i].table.id.table_id));
+        let mut snapshots = (order.iter())
+            .map(|&i| txns[i].table.snapshot.write().unwrap())
+            .collect_vec();
+        for (snapshot, &i) in snapshots.iter().zip(&order) {
+            if txns[i].table.guard.dropped.load(SeqCst) {
+                return Err(anyhow!("table {:?} is dropped", txns[i].table.id).into());
+            }
+            for dv in &changes[i].dvs {
+                let exists = (snapshot.rowsets.iter().chain(&changes[i].rowsets))
+                    .any(|rowset| rowset.rowset_id() == dv.rowset_id());
+                if !exists {
+                    return Err(anyhow!("rowset {} no longer exists", dv.rowset_id()).into());
+                }
+            }
+        }
+        let (epoch, timestamp) = txns[0].table.epoch_generator.next();
+        operations.insert(0, ManifestOperation::Epoch { epoch, timestamp });
+        let appended = txns[0].table.manifest.write(&operations)?;
+        for (snapshot, &i) in snapshots.iter_mut().zip(&order) {
+            let TxnChanges { rowsets, dvs, .. } = std::mem::take(&mut changes[i]);
+            if rowsets.is_empty() && dvs.is_empty() {
+                continue;
+            }
+            txns[i].table.commit_version(snapshot, epoch, timestamp);
+            snapshot.rowsets.extend(rowsets);
+            for dv in dvs {
+                snapshot
+                    .delete_vectors
+                    .entry(dv.rowset_id())
+                    .or_default()
+                    .push(dv);
+            }
+        }
+        drop(snapshots);
+        Ok(Some(appended))
+    }
+
+    /// Flush the memtable and write the delete vectors into `changes`, which are published on
+    /// commit.
+    async fn prepare(&mut self, changes: &mut TxnChanges) -> StorageResult<()> {
+        self.flush_memtable().await?;
+        changes.rowsets = std::mem::take(&mut self.flushed_rowsets);
+        changes.wal_records = std::mem::take(&mut self.wal_records);
+
+        for (rowset_id, rows) in std::mem::take(&mut self.deleted_rows) {
+            let dv_id = self.table.dv_id_generator.fetch_add(1, SeqCst);
+            let dv = DeleteVector::new(dv_id, rowset_id, rows);
+            // the file is removed if the commit fails, even if it is partially written
+            changes.dvs.push(dv.clone());
+            dv.write(
+                self.table.staging_path_of(dv_file_name(rowset_id, dv_id)),
+                self.table.dv_path_of(rowset_id, dv_id),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Flush the buffered rows into a rowset, so that they can be read by the transaction.
+    ///
+    /// A small rowset is not synced, whose rows are recorded in the WAL instead.
+    async fn flush_memtable(&mut self) -> StorageResult<()> {
+        if let Some(chunk) = self.memtable.as_mut().and_then(|memtable| memtable.seal()) {
+            let sync = chunk.cardinality() > WAL_MAX_ROWS;
+            self.flush(chunk, sync).await?;
+        }
         Ok(())
     }
 
-    /// Get all chunks of the table.
-    pub async fn all_chunks(&self) -> StorageResult<Vec<DataChunk>> {
+    /// Create an iterator over the given columns of the table. The chunks yielded by the
+    /// iterator contain exactly these columns in order.
+    ///
+    /// The columns of a table are stored in the order of their ids.
+    pub async fn iter(&mut self, column_ids: &[ColumnId]) -> StorageResult<DiskTxnIterator> {
+        self.iter_with_ranges(column_ids, &[]).await
+    }
+
+    /// Create an iterator over the given columns of the table, which may skip the rows out of
+    /// `ranges`.
+    ///
+    /// The iterator reads the snapshot at the start of the transaction, together with the rows
+    /// appended and deleted by the transaction so far.
+    pub async fn iter_with_ranges(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<DiskTxnIterator> {
+        self.flush_memtable().await?;
+        let iters = (self.snapshot.rowsets.iter().chain(&self.flushed_rowsets))
+            .map(|rowset| rowset.iter(column_ids, self.deleted_rows_of(rowset), ranges))
+            .collect::<StorageResult<Vec<_>>>()?;
+        Ok(DiskTxnIterator::new(iters))
+    }
+
+    /// Split the scan of [`DiskTransaction::iter_with_ranges`] into morsels of about
+    /// [`MORSEL_ROWS`] rows, each of which reads a range of blocks of a rowset.
+    pub async fn iter_morsels(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<Vec<DiskTxnIterator>> {
+        self.flush_memtable().await?;
+        let mut morsels = vec![];
+        for rowset in self.snapshot.rowsets.iter().chain(&self.flushed_rowsets) {
+            let deleted = self.deleted_rows_of(rowset);
+            let column_idx = *column_ids.first().unwrap_or(&0) as usize;
+            for rows in rowset.split_rows(column_idx, MORSEL_ROWS) {
+                let iter = rowset.iter_rows(column_ids, deleted.clone(), ranges, rows)?;
+                morsels.push(DiskTxnIterator::new(vec![iter]));
+            }
+        }
+        Ok(morsels)
+    }
+
+    /// Offsets of the deleted rows of a rowset in ascending order, including the rows deleted
+    /// by the transaction.
+    fn deleted_rows_of(&self, rowset: &DiskRowset) -> Arc<[u32]> {
+        let id = rowset.rowset_id();
+        let mut dvs = (self.snapshot.delete_vectors.get(&id).cloned()).unwrap_or_default();
+        if let Some(rows) = self.deleted_rows.get(&id) {
+            dvs.push(DeleteVector::new(0, id, rows.clone()));
+        }
+        delete_vector::merge(&dvs)
+    }
+}
+
+#[async_trait]
+impl Storage for DiskStorage {
+    async fn add_table(&self, id: TableRefId, column_descs: &[ColumnDesc]) -> StorageResult<()> {
+        DiskStorage::add_table(self, id, column_descs).await
+    }
+
+    fn get_table(&self, id: TableRefId) -> StorageResult<StorageTableRef> {
+        let table = DiskStorage::get_table(self, id)?;
+        Ok(table)
+    }
+
+    fn table_ids(&self) -> Vec<TableRefId> {
+        self.tables.read().unwrap().keys().copied().collect()
+    }
+
+    async fn drop_table(&self, id: TableRefId) -> StorageResult<()> {
+        DiskStorage::drop_table(self, id).await
+    }
+
+    async fn truncate_table(&self, id: TableRefId) -> StorageResult<()> {
+        DiskStorage::truncate_table(self, id).await
+    }
+
+    async fn commit_all(&self, txns: Vec<BoxedTransaction>) -> StorageResult<()> {
+        let mut disk_txns = vec![];
+        for txn in txns {
+            match txn.into_any().downcast::<DiskTransaction>() {
+                Ok(txn) => disk_txns.push(*txn),
+                Err(_) => {
+                    disk_txns.into_iter().for_each(DiskTransaction::abort);
+                    return Err(anyhow!("not a transaction of the disk storage").into());
+                }
+            }
+        }
+        DiskTransaction::commit_all(disk_txns).await
+    }
+
+    async fn backup(&self, dir: &Path) -> StorageResult<()> {
+        DiskStorage::backup(self, dir).await
+    }
+}
+
+#[async_trait]
+impl Table for DiskTable {
+    async fn write(&self) -> StorageResult<BoxedTransaction> {
+        Ok(Box::new(DiskTable::write(self).await?))
+    }
+
+    async fn read(&self) -> StorageResult<BoxedTransaction> {
+        Ok(Box::new(DiskTable::read(self).await?))
+    }
+
+    async fn read_as_of(&self, as_of: AsOf) -> StorageResult<BoxedTransaction> {
+        Ok(Box::new(DiskTable::read_as_of(self, as_of).await?))
+    }
+}
+
+#[async_trait]
+impl Transaction for DiskTransaction {
+    async fn append(&mut self, chunk: DataChunk) -> StorageResult<()> {
+        DiskTransaction::append(self, chunk).await
+    }
+
+    fn delete(&mut self, handles: &[RowHandle]) -> StorageResult<()> {
+        DiskTransaction::delete(self, handles)
+    }
+
+    fn upgrade(&mut self) {
+        DiskTransaction::upgrade(self)
+    }
+
+    async fn iter_with_ranges(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<BoxedTxnIterator> {
+        let iter = DiskTransaction::iter_with_ranges(self, column_ids, ranges).await?;
+        Ok(Box::new(iter))
+    }
+
+    async fn iter_morsels(
+        &mut self,
+        column_ids: &[ColumnId],
+        ranges: &[ColumnRange],
+    ) -> StorageResult<Vec<BoxedTxnIterator>> {
+        let morsels = DiskTransaction::iter_morsels(self, column_ids, ranges).await?;
+        Ok(morsels
+            .into_iter()
+            .map(|iter| Box::new(iter) as _)
+            .collect())
+    }
+
+    async fn commit(self: Box<Self>) -> StorageResult<()> {
+        DiskTransaction::commit(*self).await
+    }
+
+    fn abort(self: Box<Self>) {
+        DiskTransaction::abort(*self)
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any + Send> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::ArrayImpl;
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    /// Read all rows of the given columns, one chunk per rowset.
+    pub async fn scan(
+        txn: &mut DiskTransaction,
+        column_ids: &[ColumnId],
+    ) -> StorageResult<Vec<DataChunk>> {
+        let mut iter = txn.iter(column_ids).await?;
         let mut chunks = vec![];
-        for rowset in &self.rowset_snapshot {
-            chunks.push(rowset.as_chunk().await?);
+        while let Some(chunk) = iter.next_batch(usize::MAX).await? {
+            chunks.push(chunk);
         }
         Ok(chunks)
     }
+
+    /// Open the storage in `dir` with the other options in `options`.
+    pub async fn open_test_storage(dir: &Path, options: StorageOptions) -> DiskStorage {
+        let options = StorageOptions {
+            base_path: dir.into(),
+            ..options
+        };
+        DiskStorage::open(options).await.unwrap()
+    }
+
+    /// Add a table to `storage`, and append each of `chunks` to it in a transaction of its own.
+    pub async fn create_test_table(
+        storage: &DiskStorage,
+        id: TableRefId,
+        column_descs: &[ColumnDesc],
+        chunks: impl IntoIterator<Item = DataChunk>,
+    ) -> Arc<DiskTable> {
+        storage.add_table(id, column_descs).await.unwrap();
+        let table = storage.get_table(id).unwrap();
+        for chunk in chunks {
+            let mut txn = table.write().await.unwrap();
+            txn.append(chunk).await.unwrap();
+            txn.commit().await.unwrap();
+        }
+        table
+    }
+
+    /// A chunk of a single INT column.
+    pub fn int_chunk(values: impl IntoIterator<Item = i32>) -> DataChunk {
+        [ArrayImpl::Int32(values.into_iter().collect())]
+            .into_iter()
+            .collect()
+    }
+
+    /// Delete the first row of a table in a transaction of its own.
+    pub async fn delete_first_row(table: &DiskTable) {
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let (_, handles) = iter.next_batch_with_handles(1).await.unwrap().unwrap();
+        txn.delete(&handles).unwrap();
+        drop(iter);
+        txn.commit().await.unwrap();
+    }
+
+    /// Wait until the directory at `path` is removed in the background, and return false if it
+    /// still exists after a second.
+    pub async fn wait_removed(path: &Path) -> bool {
+        for _ in 0..100 {
+            if !path.exists() {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = TableRefId::new(0, 0);
+        let chunk: DataChunk = [ArrayImpl::Int32(
+            [Some(1), None, Some(3)].into_iter().collect(),
+        )]
+        .into_iter()
+        .collect();
+
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let column_descs = [DataTypeKind::Int(None).nullable().to_column()];
+        create_test_table(&storage, id, &column_descs, [chunk.clone()]).await;
+        drop(storage);
+
+        // The table and its rowset are recovered, and new rowsets get fresh ids.
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let table = storage.get_table(id).unwrap();
+        let mut txn = table.write().await.unwrap();
+        txn.append(chunk).await.unwrap();
+        txn.commit().await.unwrap();
+        drop(table);
+        drop(storage);
+
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let table = storage.get_table(id).unwrap();
+        let mut txn = table.read().await.unwrap();
+        let chunks = scan(&mut txn, &[0]).await.unwrap();
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert_eq!(chunk.arrays()[0].get(1), DataValue::Null);
+            assert_eq!(chunk.arrays()[0].get(2), DataValue::Int32(3));
+        }
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        // every row is flushed into a rowset on append
+        let options = || StorageOptions {
+            memtable_max_rows: 1,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, [int_chunk([1])]).await;
+
+        // a rowset that is flushed by an aborted transaction
+        let mut txn = table.write().await.unwrap();
+        txn.append(int_chunk([2])).await.unwrap();
+        // the process crashes before the transaction is aborted
+        std::mem::forget(txn);
+        let uncommitted_path = table.rowset_path_of(1);
+        assert!(uncommitted_path.exists());
+        drop(table);
+        drop(storage);
+
+        // a rowset that is partially written in the staging directory
+        let staging_path = dir.path().join(STAGING_DIR_NAME).join("2");
+        std::fs::create_dir_all(&staging_path).unwrap();
+        std::fs::write(staging_path.join("0.col"), b"garbage").unwrap();
+
+        let storage = open_test_storage(dir.path(), options()).await;
+        assert!(!uncommitted_path.exists());
+        assert!(!dir.path().join(STAGING_DIR_NAME).exists());
+        let table = storage.get_table(id).unwrap();
+        assert!(table.rowset_path_of(0).exists());
+        let mut txn = table.read().await.unwrap();
+        let chunks = scan(&mut txn, &[0]).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drop_and_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let ids = [TableRefId::new(0, 0), TableRefId::new(0, 1)];
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        for id in ids {
+            let table = create_test_table(&storage, id, &column_descs, [int_chunk(0..4)]).await;
+            delete_first_row(&table).await;
+        }
+        let tables = ids.map(|id| storage.get_table(id).unwrap());
+        let rowset_path = tables[1].rowset_path_of(1);
+        let rows = |chunks: Vec<DataChunk>| chunks.iter().map(|c| c.cardinality()).sum::<usize>();
+
+        // the old transactions still read the table
+        let mut old_txns = [
+            tables[0].read().await.unwrap(),
+            tables[1].read().await.unwrap(),
+        ];
+        let mut write_txn = tables[0].write().await.unwrap();
+        write_txn.append(int_chunk(0..4)).await.unwrap();
+        storage.drop_table(ids[0]).await.unwrap();
+        storage.truncate_table(ids[1]).await.unwrap();
+        assert!(storage.get_table(ids[0]).is_err());
+        assert!(write_txn.commit().await.is_err());
+        for txn in &mut old_txns {
+            assert_eq!(rows(scan(txn, &[0]).await.unwrap()), 3);
+        }
+        let mut txn = tables[1].read().await.unwrap();
+        assert_eq!(rows(scan(&mut txn, &[0]).await.unwrap()), 0);
+        txn.commit().await.unwrap();
+        assert!(tables[0].table_path().exists() && rowset_path.exists());
+
+        // the files are removed once the old transactions are finished
+        for txn in old_txns {
+            txn.commit().await.unwrap();
+        }
+        let table_path = tables[0].table_path();
+        drop(tables);
+        assert!(wait_removed(&table_path).await && wait_removed(&rowset_path).await);
+        drop(storage);
+
+        let storage = open_test_storage(dir.path(), options()).await;
+        assert!(storage.get_table(ids[0]).is_err());
+        let mut txn = storage.get_table(ids[1]).unwrap().read().await.unwrap();
+        assert_eq!(rows(scan(&mut txn, &[0]).await.unwrap()), 0);
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Date.nullable().to_column()];
+        assert!(storage.add_table(id, &column_descs).await.is_err());
+        assert!(storage.get_table(id).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_abort() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(
+            dir.path(),
+            StorageOptions {
+                memtable_max_rows: 2,
+                compaction_interval: None,
+                ..Default::default()
+            },
+        )
+        .await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, []).await;
+        let files = || {
+            std::fs::read_dir(table.table_path())
+                .map(|dir| dir.count())
+                .unwrap_or(0)
+        };
+
+        // the flushed rowsets are removed on abort
+        let mut txn = table.write().await.unwrap();
+        txn.append(int_chunk(0..5)).await.unwrap();
+        assert_eq!(files(), 2);
+        let paths = (std::fs::read_dir(table.table_path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect_vec();
+        txn.abort();
+        for path in &paths {
+            assert!(wait_removed(path).await);
+        }
+
+        // the rowsets of an in-flight write txn are not compacted
+        for i in 0..2 {
+            let mut txn = table.write().await.unwrap();
+            txn.append(int_chunk([i])).await.unwrap();
+            txn.commit().await.unwrap();
+        }
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let (_, handles) = iter.next_batch_with_handles(1).await.unwrap().unwrap();
+        txn.delete(&handles).unwrap();
+        drop(iter);
+        assert!(!table.compact().await.unwrap());
+        txn.commit().await.unwrap();
+        assert!(table.compact().await.unwrap());
+
+        let mut txn = table.read().await.unwrap();
+        let chunks = scan(&mut txn, &[0]).await.unwrap();
+        assert_eq!(DataChunk::concat(&chunks).cardinality(), 1);
+        txn.commit().await.unwrap();
+
+        // the delete vector is removed if the commit fails
+        let dvs = || {
+            std::fs::read_dir(table.table_path())
+                .unwrap()
+                .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("del".as_ref()))
+                .count()
+        };
+        let mut txn = table.write().await.unwrap();
+        let handle = RowHandle {
+            rowset_id: u32::MAX,
+            row_offset: 0,
+        };
+        txn.delete(&[handle]).unwrap();
+        assert!(txn.commit().await.is_err());
+        assert_eq!(dvs(), 0);
+    }
 }
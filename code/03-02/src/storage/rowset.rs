@@ -1,90 +1,600 @@
+use std::io::SeekFrom;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use anyhow::anyhow;
+use bytes::Bytes;
 use itertools::Itertools;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use super::column::{decode_int32_column, encode_int32_column};
-use super::{err, StorageResult};
+use super::block_cache::{BlockCache, BlockCacheKey};
+use super::column::{decode_block, ColumnBuilder, BLOCK_SIZE};
+use super::index::{decode_index, encode_index, BlockIndex};
+use super::iterator::RowSetIterator;
+use super::sort_index::{cmp_key, SortIndex};
+use super::zone_map::{rows_to_skip, ColumnRange};
+use super::{err, remove_dir_in_background, StorageError, StorageResult};
 use crate::array::{ArrayImpl, DataChunk};
-use crate::catalog::ColumnDesc;
+use crate::catalog::{ColumnDesc, ColumnId, TableRefId};
 
 fn column_path(rowset_path: impl AsRef<Path>, column_id: usize) -> PathBuf {
     rowset_path.as_ref().join(format!("{}.col", column_id))
 }
 
+fn index_path(rowset_path: impl AsRef<Path>, column_id: usize) -> PathBuf {
+    rowset_path.as_ref().join(format!("{}.idx", column_id))
+}
+
+fn sort_index_path(rowset_path: impl AsRef<Path>, column_id: usize) -> PathBuf {
+    rowset_path.as_ref().join(format!("{}.sort", column_id))
+}
+
+/// Returns the index of the primary key column, by which the rows of a rowset are sorted.
+pub fn sort_key_idx(column_descs: &[ColumnDesc]) -> Option<usize> {
+    column_descs.iter().position(|desc| desc.is_primary())
+}
+
 #[derive(Clone)]
 pub struct DiskRowset {
+    /// Id of the table that the rowset belongs to.
+    table_id: TableRefId,
+
     /// Columns of the current RowSet.
     column_descs: Arc<[ColumnDesc]>,
 
     /// Id of the current rowset within the table.
-    #[allow(dead_code)]
     rowset_id: u32,
 
     /// Base path of the RowSet
     rowset_path: PathBuf,
+
+    /// Block indexes of all columns.
+    indexes: Arc<[Vec<BlockIndex>]>,
+
+    /// The sort index of the primary key column, if the table has a primary key.
+    sort_index: Option<Arc<SortIndex>>,
+
+    /// The block cache shared by all rowsets.
+    block_cache: Arc<BlockCache>,
+
+    /// Removes the files once the rowset is obsolete and all its clones are dropped.
+    guard: Arc<RowSetGuard>,
+}
+
+/// Removes the directory of a rowset once the rowset is obsolete and no longer referenced.
+struct RowSetGuard {
+    rowset_path: PathBuf,
+    obsolete: AtomicBool,
+}
+
+impl RowSetGuard {
+    fn new(rowset_path: PathBuf) -> Arc<Self> {
+        Arc::new(RowSetGuard {
+            rowset_path,
+            obsolete: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Drop for RowSetGuard {
+    fn drop(&mut self) {
+        if !self.obsolete.load(Ordering::SeqCst) {
+            return;
+        }
+        info!("removing obsolete rowset {:?}", self.rowset_path);
+        remove_dir_in_background(std::mem::take(&mut self.rowset_path));
+    }
 }
 
 impl DiskRowset {
-    pub async fn as_chunk(&self) -> StorageResult<DataChunk> {
-        let mut columns = vec![];
-        for (idx, _) in self.column_descs.iter().enumerate() {
-            let column_path = column_path(&self.rowset_path, idx);
-            let data = tokio::fs::read(column_path).await.map_err(err)?;
-            columns.push(decode_int32_column(&data[..])?);
+    /// Open an existing rowset at `rowset_path`.
+    ///
+    /// The block indexes are verified with their checksums and the sizes of the column files.
+    pub async fn open(
+        table_id: TableRefId,
+        column_descs: Arc<[ColumnDesc]>,
+        block_cache: Arc<BlockCache>,
+        rowset_id: u32,
+        rowset_path: PathBuf,
+    ) -> StorageResult<Self> {
+        let corrupted = |column_idx: usize, reason: String| StorageError::Corrupted {
+            table_id,
+            rowset_id,
+            column_id: column_idx as ColumnId,
+            reason,
+        };
+        let mut indexes = vec![];
+        for idx in 0..column_descs.len() {
+            let data = tokio::fs::read(index_path(&rowset_path, idx))
+                .await
+                .map_err(err)?;
+            let index = decode_index(&data).map_err(|e| corrupted(idx, e.to_string()))?;
+            let column_size = tokio::fs::metadata(column_path(&rowset_path, idx))
+                .await
+                .map_err(err)?
+                .len();
+            let expected_size = index.last().map_or(0, |block| block.offset + block.length);
+            if column_size != expected_size {
+                let reason = format!(
+                    "column file has {} bytes, but {} bytes are indexed",
+                    column_size, expected_size
+                );
+                return Err(corrupted(idx, reason));
+            }
+            indexes.push(index);
         }
-        Ok(columns.into_iter().map(ArrayImpl::Int32).collect())
+        if indexes.iter().map(|index| row_count(index)).dedup().count() > 1 {
+            return Err(corrupted(
+                0,
+                "columns have different numbers of rows".into(),
+            ));
+        }
+        let sort_index = match sort_key_idx(&column_descs) {
+            Some(idx) => {
+                let data = tokio::fs::read(sort_index_path(&rowset_path, idx))
+                    .await
+                    .map_err(err)?;
+                let index = SortIndex::decode(&data).map_err(|e| corrupted(idx, e.to_string()))?;
+                Some(Arc::new(index))
+            }
+            None => None,
+        };
+        Ok(DiskRowset {
+            table_id,
+            column_descs,
+            rowset_id,
+            guard: RowSetGuard::new(rowset_path.clone()),
+            rowset_path,
+            indexes: indexes.into(),
+            sort_index,
+            block_cache,
+        })
+    }
+
+    pub fn rowset_id(&self) -> u32 {
+        self.rowset_id
+    }
+
+    /// Mark the rowset as obsolete, so that its files are removed once all transactions
+    /// reading it are finished.
+    pub fn mark_obsolete(&self) {
+        self.guard.obsolete.store(true, Ordering::SeqCst);
+    }
+
+    /// Read a block of a column, from the block cache if possible.
+    ///
+    /// A block read from disk is verified with its checksum before it is cached.
+    pub async fn read_block(
+        &self,
+        column_idx: usize,
+        block_idx: usize,
+    ) -> StorageResult<ArrayImpl> {
+        let key = BlockCacheKey {
+            rowset_id: self.rowset_id,
+            column_id: column_idx as u32,
+            block_idx: block_idx as u32,
+        };
+        let block = &self.indexes[column_idx][block_idx];
+        let data = match self.block_cache.get(&key) {
+            Some(data) => data,
+            None => {
+                let mut file = tokio::fs::File::open(column_path(&self.rowset_path, column_idx))
+                    .await
+                    .map_err(err)?;
+                file.seek(SeekFrom::Start(block.offset))
+                    .await
+                    .map_err(err)?;
+                let mut data = vec![0; block.length as usize];
+                if let Err(e) = file.read_exact(&mut data).await {
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                        return Err(self.corrupted(column_idx, "column file is truncated"));
+                    }
+                    return Err(err(e));
+                }
+                if crc32fast::hash(&data) != block.checksum {
+                    let reason = format!("checksum mismatch in block {}", block_idx);
+                    return Err(self.corrupted(column_idx, reason));
+                }
+                let data = Bytes::from(data);
+                self.block_cache.insert(key, data.clone());
+                data
+            }
+        };
+        let array = decode_block(data, self.column_descs[column_idx].datatype())
+            .map_err(|e| self.corrupted(column_idx, format!("block {}: {}", block_idx, e)))?;
+        if array.len() != block.row_count as usize {
+            let reason = format!("block {} has unexpected number of rows", block_idx);
+            return Err(self.corrupted(column_idx, reason));
+        }
+        Ok(array)
+    }
+
+    /// Read and decode all blocks of the rowset, which are verified with their checksums.
+    pub async fn verify(&self) -> StorageResult<()> {
+        for column_idx in 0..self.column_descs.len() {
+            let mut rows = 0;
+            for block_idx in 0..self.block_count(column_idx) {
+                rows += self.read_block(column_idx, block_idx).await?.len();
+            }
+            if rows != self.row_count() {
+                let reason = format!(
+                    "{} rows decoded, but {} rows indexed",
+                    rows,
+                    self.row_count()
+                );
+                return Err(self.corrupted(column_idx, reason));
+            }
+        }
+        Ok(())
+    }
+
+    fn corrupted(&self, column_idx: usize, reason: impl ToString) -> StorageError {
+        StorageError::Corrupted {
+            table_id: self.table_id,
+            rowset_id: self.rowset_id,
+            column_id: column_idx as ColumnId,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Number of rows in the rowset.
+    pub fn row_count(&self) -> usize {
+        row_count(&self.indexes[0])
+    }
+
+    /// Number of blocks of a column.
+    pub fn block_count(&self, column_idx: usize) -> usize {
+        self.indexes[column_idx].len()
+    }
+
+    /// Block indexes of a column.
+    pub fn block_indexes(&self, column_idx: usize) -> &[BlockIndex] {
+        &self.indexes[column_idx]
+    }
+
+    pub fn column_descs(&self) -> &[ColumnDesc] {
+        &self.column_descs
+    }
+
+    /// The index of the primary key column and its sort index.
+    pub fn sort_index(&self) -> Option<(usize, &SortIndex)> {
+        let idx = sort_key_idx(&self.column_descs)?;
+        Some((idx, self.sort_index.as_deref()?))
+    }
+
+    /// Create an iterator over the given columns of the rowset, which skips the `deleted` rows
+    /// and the blocks out of `ranges`.
+    pub fn iter(
+        &self,
+        column_ids: &[ColumnId],
+        deleted: Arc<[u32]>,
+        ranges: &[ColumnRange],
+    ) -> StorageResult<RowSetIterator> {
+        self.iter_rows(column_ids, deleted, ranges, 0..self.row_count() as u32)
+    }
+
+    /// Split the rows into consecutive ranges of about `rows_per_range` rows, at the boundaries
+    /// of the blocks of a column.
+    pub fn split_rows(&self, column_idx: usize, rows_per_range: usize) -> Vec<Range<u32>> {
+        let mut splits = vec![];
+        let mut start = 0;
+        let mut end = 0;
+        for block in self.block_indexes(column_idx) {
+            end += block.row_count;
+            if (end - start) as usize >= rows_per_range {
+                splits.push(start..end);
+                start = end;
+            }
+        }
+        if start < end {
+            splits.push(start..end);
+        }
+        splits
+    }
+
+    /// Create an iterator like [`DiskRowset::iter`], which only reads the rows in `rows`.
+    pub fn iter_rows(
+        &self,
+        column_ids: &[ColumnId],
+        deleted: Arc<[u32]>,
+        ranges: &[ColumnRange],
+        rows: Range<u32>,
+    ) -> StorageResult<RowSetIterator> {
+        if column_ids.is_empty() {
+            return Err(anyhow!("at least one column should be scanned").into());
+        }
+        for &column_id in column_ids.iter().chain(ranges.iter().map(|r| &r.column_id)) {
+            if column_id as usize >= self.column_descs.len() {
+                return Err(anyhow!("column not found: {}", column_id).into());
+            }
+        }
+        let skipped = rows_to_skip(self, ranges, rows);
+        Ok(RowSetIterator::new(
+            self.clone(),
+            column_ids,
+            deleted,
+            skipped,
+        ))
     }
 }
 
 pub struct RowSetBuilder {
+    /// Id of the table that the rowset belongs to.
+    table_id: TableRefId,
+
     /// Columns of the current RowSet.
     column_descs: Arc<[ColumnDesc]>,
 
-    /// Buffer of all column data
-    buffer: Vec<Vec<u8>>,
+    /// Builders of all columns
+    columns: Vec<ColumnBuilder>,
+
+    /// The chunks to be sorted by the primary key on flush, if the table has a primary key.
+    chunks: Vec<DataChunk>,
 }
 
 impl RowSetBuilder {
-    pub fn new(column_descs: Arc<[ColumnDesc]>) -> Self {
-        RowSetBuilder {
-            buffer: (0..column_descs.len()).map(|_| vec![]).collect_vec(),
+    pub fn new(table_id: TableRefId, column_descs: Arc<[ColumnDesc]>) -> StorageResult<Self> {
+        Ok(RowSetBuilder {
+            table_id,
+            columns: column_descs
+                .iter()
+                .map(|desc| ColumnBuilder::new(desc.datatype(), BLOCK_SIZE))
+                .try_collect()?,
             column_descs,
-        }
+            chunks: vec![],
+        })
     }
 
     pub fn append(&mut self, chunk: DataChunk) -> StorageResult<()> {
+        if sort_key_idx(&self.column_descs).is_some() {
+            self.chunks.push(chunk);
+            return Ok(());
+        }
         for (idx, column) in chunk.arrays().iter().enumerate() {
-            if let ArrayImpl::Int32(column) = column {
-                encode_int32_column(column, &mut self.buffer[idx])?;
-            } else {
-                return Err(anyhow!("unsupported column type").into());
-            }
+            self.columns[idx].append(column)?;
         }
         Ok(())
     }
 
+    /// Sort the buffered chunks by the primary key and append them to the columns. Return the
+    /// sort index of the key column.
+    fn sort(&mut self, key_idx: usize) -> StorageResult<SortIndex> {
+        if self.chunks.is_empty() {
+            return Ok(SortIndex::default());
+        }
+        let chunk = DataChunk::concat(&std::mem::take(&mut self.chunks));
+        let keys = &chunk.arrays()[key_idx];
+        let values = (0..chunk.cardinality()).map(|i| keys.get(i)).collect_vec();
+        let mut order = (0..chunk.cardinality()).collect_vec();
+        order.sort_by(|&a, &b| cmp_key(&values[a], &values[b]));
+        let chunk = chunk.take(&order);
+        for (idx, column) in chunk.arrays().iter().enumerate() {
+            self.columns[idx].append(column)?;
+        }
+        Ok(SortIndex::build(&chunk.arrays()[key_idx]))
+    }
+
+    /// Write the rowset to `rowset_path`.
+    ///
+    /// The files are first written to `staging_path` and synced to disk. Then the staging
+    /// directory is renamed to `rowset_path`, so that a crash never leaves a partially written
+    /// rowset at `rowset_path`. The rowset is still invisible until it is recorded in the manifest.
+    ///
+    /// If `sync` is false, the files are not synced, and the rows should be recorded in the WAL
+    /// until the rowset is synced by [`sync_rowset`].
+    ///
+    /// If the flush fails, the partially written files are removed.
     pub async fn flush(
         self,
+        block_cache: Arc<BlockCache>,
         rowset_id: u32,
+        staging_path: impl AsRef<Path>,
         rowset_path: impl AsRef<Path>,
+        sync: bool,
     ) -> StorageResult<DiskRowset> {
+        let staging_path = staging_path.as_ref();
         let rowset_path = rowset_path.as_ref();
+        let result = (self.write(block_cache, rowset_id, staging_path, rowset_path, sync)).await;
+        if result.is_err() {
+            for path in [staging_path, rowset_path] {
+                match tokio::fs::remove_dir_all(path).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        warn!("failed to remove {:?}: {}", path, e)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        result
+    }
 
-        tokio::fs::create_dir_all(rowset_path).await.map_err(err)?;
+    async fn write(
+        mut self,
+        block_cache: Arc<BlockCache>,
+        rowset_id: u32,
+        staging_path: &Path,
+        rowset_path: &Path,
+        sync: bool,
+    ) -> StorageResult<DiskRowset> {
+        tokio::fs::create_dir_all(staging_path).await.map_err(err)?;
 
-        for (idx, _) in self.column_descs.iter().enumerate() {
-            let column_path = column_path(rowset_path, idx);
-            tokio::fs::write(column_path, &self.buffer[idx])
-                .await
-                .map_err(err)?;
+        let mut sort_index = None;
+        if let Some(key_idx) = sort_key_idx(&self.column_descs) {
+            let index = self.sort(key_idx)?;
+            let mut buffer = vec![];
+            index.encode(&mut buffer);
+            write_file(sort_index_path(staging_path, key_idx), &buffer, sync).await?;
+            sort_index = Some(Arc::new(index));
+        }
+
+        let mut indexes = vec![];
+        for (idx, column) in self.columns.into_iter().enumerate() {
+            let (data, index) = column.finish();
+            write_file(column_path(staging_path, idx), &data, sync).await?;
+            let mut buffer = vec![];
+            encode_index(&index, &mut buffer);
+            write_file(index_path(staging_path, idx), &buffer, sync).await?;
+            indexes.push(index);
+        }
+        if sync {
+            sync_dir(staging_path).await?;
+        }
+
+        let parent = rowset_path.parent().unwrap();
+        tokio::fs::create_dir_all(parent).await.map_err(err)?;
+        tokio::fs::rename(staging_path, rowset_path)
+            .await
+            .map_err(err)?;
+        if sync {
+            sync_dir(parent).await?;
         }
 
         Ok(DiskRowset {
+            table_id: self.table_id,
             column_descs: self.column_descs,
             rowset_id,
             rowset_path: rowset_path.into(),
+            guard: RowSetGuard::new(rowset_path.into()),
+            indexes: indexes.into(),
+            sort_index,
+            block_cache,
         })
     }
 }
+
+/// Number of rows in a column with the block index.
+fn row_count(index: &[BlockIndex]) -> usize {
+    index.iter().map(|block| block.row_count as usize).sum()
+}
+
+/// Write `data` to a new file at `path`, and sync it to disk if `sync` is true.
+pub async fn write_file(path: impl AsRef<Path>, data: &[u8], sync: bool) -> StorageResult<()> {
+    let mut file = tokio::fs::File::create(path).await.map_err(err)?;
+    file.write_all(data).await.map_err(err)?;
+    if sync {
+        file.sync_all().await.map_err(err)?;
+    }
+    Ok(())
+}
+
+/// Sync the files of a rowset written without syncing, and the directory entry of the rowset.
+///
+/// Nothing is done if the rowset is already removed.
+pub async fn sync_rowset(rowset_path: impl AsRef<Path>) -> StorageResult<()> {
+    match sync_rowset_files(rowset_path.as_ref()).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result.map_err(err),
+    }
+}
+
+async fn sync_rowset_files(rowset_path: &Path) -> std::io::Result<()> {
+    let mut entries = tokio::fs::read_dir(rowset_path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        tokio::fs::File::open(entry.path())
+            .await?
+            .sync_all()
+            .await?;
+    }
+    tokio::fs::File::open(rowset_path).await?.sync_all().await?;
+    let parent = rowset_path.parent().unwrap();
+    tokio::fs::File::open(parent).await?.sync_all().await?;
+    Ok(())
+}
+
+/// Sync a directory to disk, so that the entries created or renamed in it are durable.
+pub async fn sync_dir(path: impl AsRef<Path>) -> StorageResult<()> {
+    let dir = tokio::fs::File::open(path).await.map_err(err)?;
+    dir.sync_all().await.map_err(err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::ArrayImpl;
+    use crate::storage::tests::{create_test_table, open_test_storage, scan};
+    use crate::storage::{DiskStorage, StorageOptions};
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_flush_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging_path = dir.path().join("staging").join("1");
+        let rowset_path = dir.path().join("0").join("1");
+        let column_descs: Arc<[ColumnDesc]> =
+            [DataTypeKind::Int(None).not_null().to_column()].into();
+        let mut builder = RowSetBuilder::new(TableRefId::new(0, 0), column_descs).unwrap();
+        builder
+            .append([ArrayImpl::Int32((0..4).collect())].into_iter().collect())
+            .unwrap();
+
+        // writing the column file fails, as a directory is in the way
+        std::fs::create_dir_all(column_path(&staging_path, 0)).unwrap();
+        let block_cache = Arc::new(BlockCache::new(0));
+        let result = (builder.flush(block_cache, 1, &staging_path, &rowset_path, true)).await;
+        assert!(result.is_err());
+        assert!(!staging_path.exists());
+        assert!(!rowset_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = TableRefId::new(0, 3);
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let column_descs = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            DataTypeKind::Int(None).not_null().to_column(),
+        ];
+        let chunk: DataChunk = [
+            ArrayImpl::Int32([1, 20, 300].into_iter().collect()),
+            ArrayImpl::Int32([4, 50, 600].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        let table = create_test_table(&storage, id, &column_descs, [chunk]).await;
+        // sync the rowset, so that it is not rebuilt from the WAL
+        storage.wal.checkpoint().await.unwrap();
+        let rowset_path = table.rowset_path_of(0);
+        drop(table);
+        drop(storage);
+
+        // flip a bit in the last byte of column 1
+        let path = rowset_path.join("1.col");
+        let mut data = std::fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, data).unwrap();
+
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let table = storage.get_table(id).unwrap();
+        let mut txn = table.read().await.unwrap();
+        scan(&mut txn, &[0]).await.unwrap();
+        match scan(&mut txn, &[1]).await {
+            Err(StorageError::Corrupted {
+                table_id,
+                rowset_id: 0,
+                column_id: 1,
+                ..
+            }) => assert_eq!(table_id, id),
+            _ => panic!("corruption is not detected"),
+        }
+        txn.commit().await.unwrap();
+        drop(table);
+        drop(storage);
+
+        // truncate the index of column 0
+        let path = rowset_path.join("0.idx");
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 1]).unwrap();
+        let options = StorageOptions {
+            base_path: dir.path().into(),
+            ..Default::default()
+        };
+        match DiskStorage::open(options).await {
+            Err(StorageError::Corrupted { column_id: 0, .. }) => {}
+            _ => panic!("corruption is not detected"),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Transactions spanning multiple statements and tables.
+//!
+//! A [`SessionTxn`] starts a transaction on a table when the table is first accessed, and keeps
+//! it until the session transaction ends. Therefore all statements in the transaction read the
+//! same snapshot of a table together with their own writes, while other transactions only see
+//! the committed snapshots. The transaction on a table only reads until the first statement
+//! writing to the table, so that reads do not keep compaction from merging its rowsets. On commit,
+//! the writes to all tables are published atomically by [`Storage::commit_all`](super::Storage::
+//! commit_all).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use super::{BoxedTransaction, StorageRef, StorageResult};
+use crate::catalog::TableRefId;
+
+pub type SessionTxnRef = Arc<SessionTxn>;
+
+/// A transaction over all tables accessed by a session.
+pub struct SessionTxn {
+    storage: StorageRef,
+
+    /// The transactions on the accessed tables.
+    txns: Mutex<HashMap<TableRefId, Arc<Mutex<BoxedTransaction>>>>,
+}
+
+impl SessionTxn {
+    pub fn new(storage: StorageRef) -> Self {
+        SessionTxn {
+            storage,
+            txns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the transaction on a table, starting one if the table is accessed for the first time.
+    ///
+    /// If `write` is true, a read-only transaction is upgraded to write. The transaction is locked
+    /// until the returned guard is dropped.
+    pub async fn table_txn(
+        &self,
+        id: TableRefId,
+        write: bool,
+    ) -> StorageResult<OwnedMutexGuard<BoxedTransaction>> {
+        let txn = {
+            let mut txns = self.txns.lock().await;
+            match txns.get(&id) {
+                Some(txn) => txn.clone(),
+                None => {
+                    let table = self.storage.get_table(id)?;
+                    let txn = if write {
+                        table.write().await?
+                    } else {
+                        table.read().await?
+                    };
+                    let txn = Arc::new(Mutex::new(txn));
+                    txns.insert(id, txn.clone());
+                    txn
+                }
+            }
+        };
+        let mut txn = txn.lock_owned().await;
+        if write {
+            txn.upgrade();
+        }
+        Ok(txn)
+    }
+
+    /// Commit the transactions on all accessed tables atomically.
+    ///
+    /// All statements in the transaction should be finished.
+    pub async fn commit(&self) -> StorageResult<()> {
+        let mut txns = vec![];
+        for (id, txn) in std::mem::take(&mut *self.txns.lock().await) {
+            match Arc::try_unwrap(txn) {
+                Ok(txn) => txns.push(txn.into_inner()),
+                Err(_) => {
+                    txns.into_iter().for_each(|txn| txn.abort());
+                    return Err(anyhow!("transaction on table {:?} is still in use", id).into());
+                }
+            }
+        }
+        self.storage.commit_all(txns).await
+    }
+
+    /// Abort the transactions on all accessed tables.
+    ///
+    /// A transaction still used by a running statement is aborted once the statement is dropped.
+    pub async fn rollback(&self) {
+        for (_, txn) in std::mem::take(&mut *self.txns.lock().await) {
+            if let Ok(txn) = Arc::try_unwrap(txn) {
+                txn.into_inner().abort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage};
+    use crate::storage::{StorageOptions, Transaction};
+    use crate::types::{DataTypeExt, DataTypeKind, DataValue};
+
+    async fn values(txn: &mut dyn Transaction) -> Vec<DataValue> {
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let mut values = vec![];
+        while let Some(chunk) = iter.next_batch(usize::MAX).await.unwrap() {
+            values.extend((0..chunk.cardinality()).map(|i| chunk.arrays()[0].get(i)));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_session_txn() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let ids = [TableRefId::new(0, 0), TableRefId::new(0, 1)];
+        let storage = Arc::new(open_test_storage(dir.path(), options()).await);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        for id in ids {
+            create_test_table(&storage, id, &column_descs, []).await;
+        }
+        let chunk = int_chunk(1..4);
+        let int = |v: &[i32]| v.iter().map(|&v| DataValue::Int32(v)).collect_vec();
+
+        let session = SessionTxn::new(storage.clone());
+        for id in ids {
+            let mut txn = session.table_txn(id, true).await.unwrap();
+            txn.append(chunk.clone()).await.unwrap();
+        }
+        {
+            // the transaction reads its own writes, including the deletions
+            let mut txn = session.table_txn(ids[0], true).await.unwrap();
+            assert_eq!(values(&mut **txn).await, int(&[1, 2, 3]));
+            let mut iter = txn.iter(&[0]).await.unwrap();
+            let (_, handles) = iter.next_batch_with_handles(1).await.unwrap().unwrap();
+            txn.delete(&handles).unwrap();
+            drop(iter);
+            assert_eq!(values(&mut **txn).await, int(&[2, 3]));
+        }
+        // other transactions only see the committed snapshot
+        let mut other = storage.get_table(ids[0]).unwrap().read().await.unwrap();
+        assert_eq!(values(&mut other).await, int(&[]));
+        other.commit().await.unwrap();
+
+        session.commit().await.unwrap();
+        drop(storage);
+
+        let storage = Arc::new(open_test_storage(dir.path(), options()).await);
+        for (id, expected) in ids.into_iter().zip([int(&[2, 3]), int(&[1, 2, 3])]) {
+            let mut txn = storage.get_table(id).unwrap().read().await.unwrap();
+            assert_eq!(values(&mut txn).await, expected);
+            txn.commit().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = Arc::new(open_test_storage(dir.path(), options).await);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let chunk = int_chunk(1..3);
+        let chunks = [chunk.clone(), chunk.clone()];
+        let table = create_test_table(&storage, id, &column_descs, chunks).await;
+        let int = |v: &[i32]| v.iter().map(|&v| DataValue::Int32(v)).collect_vec();
+
+        // a transaction that only reads does not keep the rowsets from being compacted
+        let session = SessionTxn::new(storage.clone());
+        {
+            let mut txn = session.table_txn(id, false).await.unwrap();
+            assert_eq!(values(&mut **txn).await, int(&[1, 2, 1, 2]));
+            assert!(txn.append(chunk.clone()).await.is_err());
+        }
+        assert!(table.compact().await.unwrap());
+
+        // the first write upgrades the transaction, which still reads the same snapshot
+        {
+            let mut txn = session.table_txn(id, true).await.unwrap();
+            txn.append(chunk.clone()).await.unwrap();
+            assert_eq!(values(&mut **txn).await, int(&[1, 2, 1, 2, 1, 2]));
+        }
+        session.commit().await.unwrap();
+
+        let mut txn = table.read().await.unwrap();
+        assert_eq!(values(&mut txn).await, int(&[1, 2, 1, 2, 1, 2]));
+        txn.commit().await.unwrap();
+    }
+}
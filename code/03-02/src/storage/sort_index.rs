@@ -0,0 +1,258 @@
+//! The sort key index of a rowset.
+//!
+//! The rows of a table with a primary key are sorted by the key in each rowset. The sort index
+//! (`.sort`) of the key column records the key of every [`SORT_INDEX_INTERVAL`]-th row, so that
+//! a scan over a range of keys can seek to the rows in the range with a binary search, no
+//! matter how the blocks of other columns are divided. It is laid out as:
+//!
+//! ```plain
+//! | entry count (u32) | row offset (u32) | key | row offset (u32) | key | ... | checksum (u32) |
+//! ```
+//!
+//! where the keys are encoded in the same way as the block index, and the checksum is the CRC32
+//! of all preceding bytes.
+
+use std::cmp::Ordering;
+use std::mem::discriminant;
+use std::ops::Bound;
+
+use anyhow::anyhow;
+use bytes::{Buf, BufMut};
+
+use super::index::{decode_value, encode_value};
+use super::StorageResult;
+use crate::array::ArrayImpl;
+use crate::types::DataValue;
+
+/// The number of rows between two entries of the sort index.
+pub const SORT_INDEX_INTERVAL: usize = 256;
+
+/// Compare two keys in the order the rows are sorted by.
+///
+/// It is a total order, where NULL is less than and NaN is greater than any other value, so
+/// that the rows with NaN keys are at the end, out of any range.
+pub fn cmp_key(a: &DataValue, b: &DataValue) -> Ordering {
+    let is_nan = |v: &DataValue| matches!(v, DataValue::Float64(v) if v.is_nan());
+    match (is_nan(a), is_nan(b)) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        // only NaN is not ordered with other values
+        (false, false) => a.partial_cmp(b).unwrap(),
+    }
+}
+
+/// The sampled keys of a rowset sorted by the key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortIndex {
+    /// The offsets and keys of the sampled rows in ascending order.
+    entries: Vec<(u32, DataValue)>,
+}
+
+impl SortIndex {
+    /// Build the sort index of the sorted `keys`.
+    pub fn build(keys: &ArrayImpl) -> Self {
+        let entries = (0..keys.len())
+            .step_by(SORT_INDEX_INTERVAL)
+            .map(|row| (row as u32, keys.get(row)))
+            .collect();
+        SortIndex { entries }
+    }
+
+    /// Find the rows `[start, end)` out of which no key is in the range.
+    pub fn seek(
+        &self,
+        start: &Bound<DataValue>,
+        end: &Bound<DataValue>,
+        row_count: u32,
+    ) -> (u32, u32) {
+        let comparable =
+            |v: &DataValue| match self.entries.iter().find(|(_, key)| *key != DataValue::Null) {
+                Some((_, key)) => discriminant(key) == discriminant(v),
+                None => true,
+            };
+        for bound in [start, end] {
+            if let Bound::Included(v) | Bound::Excluded(v) = bound {
+                if !comparable(v) {
+                    return (0, row_count);
+                }
+            }
+        }
+        // NULL is less than any non-NULL value, and never in the range
+        let below_range = |key: &DataValue| {
+            *key == DataValue::Null
+                || match start {
+                    Bound::Included(v) => cmp_key(key, v) == Ordering::Less,
+                    Bound::Excluded(v) => cmp_key(key, v) != Ordering::Greater,
+                    Bound::Unbounded => false,
+                }
+        };
+        let above_range = |key: &DataValue| match end {
+            Bound::Included(v) => cmp_key(key, v) == Ordering::Greater,
+            Bound::Excluded(v) => cmp_key(key, v) != Ordering::Less,
+            Bound::Unbounded => false,
+        };
+        // the rows before a sampled row are not greater than its key, and the rows after it are
+        // not less than its key
+        let i = (self.entries).partition_point(|(_, key)| below_range(key));
+        let start_row = if i == 0 { 0 } else { self.entries[i - 1].0 };
+        let j = (self.entries).partition_point(|(_, key)| !above_range(key));
+        let end_row = self.entries.get(j).map_or(row_count, |(row, _)| *row);
+        (start_row, end_row.max(start_row))
+    }
+
+    /// Encode the sort index into `buffer`.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        buffer.put_u32_le(self.entries.len() as u32);
+        for (row, key) in &self.entries {
+            buffer.put_u32_le(*row);
+            encode_value(key, &mut *buffer);
+        }
+        let checksum = crc32fast::hash(&buffer[start..]);
+        buffer.put_u32_le(checksum);
+    }
+
+    /// Decode the sort index from `data`.
+    pub fn decode(data: &[u8]) -> StorageResult<Self> {
+        if data.len() < 8 {
+            return Err(
+                anyhow!("sort index is too short to contain a count and a checksum").into(),
+            );
+        }
+        let (mut data, mut checksum) = data.split_at(data.len() - 4);
+        if crc32fast::hash(data) != checksum.get_u32_le() {
+            return Err(anyhow!("sort index checksum mismatch").into());
+        }
+        let len = data.get_u32_le() as usize;
+        let mut entries = Vec::with_capacity(len);
+        for _ in 0..len {
+            if data.remaining() < 4 {
+                return Err(anyhow!("sort index is too short to contain {} entries", len).into());
+            }
+            entries.push((data.get_u32_le(), decode_value(&mut data)?));
+        }
+        if data.has_remaining() {
+            return Err(anyhow!("unexpected trailing bytes in sort index").into());
+        }
+        Ok(SortIndex { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::{ArrayBuilderImpl, DataChunk};
+    use crate::catalog::{ColumnDesc, TableRefId};
+    use crate::storage::tests::{create_test_table, open_test_storage, scan};
+    use crate::storage::{ColumnRange, StorageOptions};
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[test]
+    fn test_seek() {
+        // keys are 0, 0, 1, 1, ..., sampled at rows 0, 256, 512, ...
+        let keys = ArrayImpl::Int32((0..1024).map(|i| i / 2).collect());
+        let index = SortIndex::build(&keys);
+        let mut buffer = vec![];
+        index.encode(&mut buffer);
+        let index = SortIndex::decode(&buffer).unwrap();
+
+        let int = |v| DataValue::Int32(v);
+        let seek = |start, end| index.seek(&start, &end, 1024);
+        assert_eq!(
+            seek(Bound::Included(int(200)), Bound::Included(int(200))),
+            (256, 512)
+        );
+        // the rows before a sampled key equal to the start may also be in the range
+        assert_eq!(
+            seek(Bound::Included(int(128)), Bound::Excluded(int(256))),
+            (0, 512)
+        );
+        assert_eq!(
+            seek(Bound::Excluded(int(128)), Bound::Unbounded),
+            (256, 1024)
+        );
+        assert_eq!(seek(Bound::Unbounded, Bound::Included(int(128))), (0, 512));
+        assert_eq!(
+            seek(Bound::Included(int(1000)), Bound::Unbounded),
+            (768, 1024)
+        );
+        // values of another type never skip rows
+        let float = Bound::Included(DataValue::Float64(1.0));
+        assert_eq!(seek(float.clone(), float), (0, 1024));
+
+        buffer[4] ^= 1;
+        assert!(SortIndex::decode(&buffer).is_err());
+    }
+
+    #[test]
+    fn test_seek_nan() {
+        // keys are 0.5, 1.5, ..., and NaN from row 512, sampled at rows 0, 256, 512, ...
+        let mut keys = (0..1024)
+            .map(|i| DataValue::Float64(if i % 2 == 0 { f64::NAN } else { i as f64 / 2.0 }))
+            .collect::<Vec<_>>();
+        keys.sort_by(cmp_key);
+        assert_eq!(keys[511], DataValue::Float64(511.5));
+        assert!(matches!(keys[512], DataValue::Float64(v) if v.is_nan()));
+        let mut builder = ArrayBuilderImpl::with_capacity(1024, &DataTypeKind::Double.not_null());
+        keys.into_iter().for_each(|key| builder.push(&key));
+        let index = SortIndex::build(&builder.finish());
+
+        let float = |v| DataValue::Float64(v);
+        let seek = |start, end| index.seek(&start, &end, 1024);
+        assert_eq!(
+            seek(Bound::Included(float(300.0)), Bound::Included(float(300.0))),
+            (256, 512)
+        );
+        assert_eq!(
+            seek(Bound::Included(float(300.0)), Bound::Unbounded),
+            (256, 1024)
+        );
+        assert_eq!(
+            seek(Bound::Unbounded, Bound::Excluded(float(256.0))),
+            (0, 256)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sort_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            ColumnDesc::new(DataTypeKind::Int(None).not_null(), true),
+        ];
+        let chunk: DataChunk = [
+            ArrayImpl::Int32((0..100_000).map(|i| i * 2).rev().collect()),
+            ArrayImpl::Int32((0..100_000).rev().collect()),
+        ]
+        .into_iter()
+        .collect();
+        let table = create_test_table(&storage, id, &column_descs, [chunk]).await;
+
+        // the scan of a range of keys seeks to the rows in the range
+        let mut txn = table.read().await.unwrap();
+        let ranges = [ColumnRange {
+            column_id: 1,
+            start: Bound::Included(DataValue::Int32(50_000)),
+            end: Bound::Included(DataValue::Int32(50_009)),
+        }];
+        let mut iter = txn.iter_with_ranges(&[0, 1], &ranges).await.unwrap();
+        let mut rows = 0;
+        while let Some(chunk) = iter.next_batch(1024).await.unwrap() {
+            rows += chunk.cardinality();
+        }
+        assert!((10..=2 * SORT_INDEX_INTERVAL).contains(&rows));
+        // at most two blocks of each column are read
+        assert!(storage.block_cache_stats().misses <= 4);
+
+        // the rows are sorted by the primary key
+        let chunk = DataChunk::concat(&scan(&mut txn, &[1, 0]).await.unwrap());
+        assert!((0..chunk.cardinality())
+            .all(|i| chunk.arrays()[0].get(i) == DataValue::Int32(i as i32)));
+        assert!((0..chunk.cardinality())
+            .all(|i| chunk.arrays()[1].get(i) == DataValue::Int32(i as i32 * 2)));
+        txn.commit().await.unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Versions of tables for time travel.
+//!
+//! Every commit that changes the rowsets or delete vectors of tables is numbered by a new epoch,
+//! which is recorded in the manifest together with the commit time. A version of a table is
+//! retained for [`StorageOptions::version_retention`] after it is replaced by a later commit, so
+//! that it can still be read [`AsOf`] an epoch or a time in the past. The files only used by the
+//! expired versions are then removed.
+//!
+//! [`StorageOptions::version_retention`]: super::StorageOptions::version_retention
+
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A point in the history of the storage, at which a table is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// Right after the commit of the epoch.
+    Epoch(u64),
+
+    /// At the time in milliseconds since the Unix epoch.
+    Timestamp(u64),
+}
+
+impl std::fmt::Display for AsOf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsOf::Epoch(epoch) => write!(f, "epoch {}", epoch),
+            AsOf::Timestamp(timestamp) => write!(f, "timestamp {}", timestamp),
+        }
+    }
+}
+
+/// Generates the epochs and the times of commits, both of which never decrease.
+pub struct EpochGenerator {
+    /// The epoch and the time of the last commit.
+    last: Mutex<(u64, u64)>,
+}
+
+impl EpochGenerator {
+    pub fn new(epoch: u64, timestamp: u64) -> Self {
+        EpochGenerator {
+            last: Mutex::new((epoch, timestamp)),
+        }
+    }
+
+    /// Return the epoch and the time of a new commit.
+    pub fn next(&self) -> (u64, u64) {
+        let mut last = self.last.lock().unwrap();
+        *last = (last.0 + 1, now().max(last.1));
+        *last
+    }
+}
+
+/// The current time in milliseconds since the Unix epoch.
+pub fn now() -> u64 {
+    let duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    duration.as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{
+        create_test_table, delete_first_row, int_chunk, open_test_storage, scan,
+    };
+    use crate::storage::{DiskStorage, StorageOptions, StorageResult};
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    #[tokio::test]
+    async fn test_time_travel() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = |version_retention| StorageOptions {
+            compaction_interval: None,
+            version_retention,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let mut storage = open_test_storage(dir.path(), options(Duration::from_secs(3600))).await;
+        // epoch 1: create, epoch 2: insert, epoch 3: delete, epoch 4: truncate
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let table = create_test_table(&storage, id, &column_descs, [int_chunk(0..4)]).await;
+        delete_first_row(&table).await;
+        storage.truncate_table(id).await.unwrap();
+        let rowset_path = table.rowset_path_of(0);
+        let dv_path = table.dv_path_of(0, 0);
+        drop(table);
+
+        let rows_as_of = |storage: &DiskStorage, as_of| {
+            let table = storage.get_table(id).unwrap();
+            async move {
+                let mut txn = table.read_as_of(as_of).await?;
+                let chunks = scan(&mut txn, &[0]).await?;
+                txn.commit().await?;
+                StorageResult::Ok(chunks.iter().map(|c| c.cardinality()).sum::<usize>())
+            }
+        };
+        for _ in 0..2 {
+            let epochs = [(1, 0), (2, 4), (3, 3), (4, 0), (10, 0)];
+            for (epoch, rows) in epochs {
+                assert_eq!(
+                    rows_as_of(&storage, AsOf::Epoch(epoch)).await.unwrap(),
+                    rows
+                );
+            }
+            assert!(rows_as_of(&storage, AsOf::Epoch(0)).await.is_err());
+            let latest = AsOf::Timestamp(now());
+            assert_eq!(rows_as_of(&storage, latest).await.unwrap(), 0);
+            assert!(rowset_path.exists() && dv_path.exists());
+
+            // the old versions are recovered from the manifest
+            drop(storage);
+            storage = open_test_storage(dir.path(), options(Duration::from_secs(3600))).await;
+        }
+
+        // the files of the expired versions are removed
+        drop(storage);
+        let storage = open_test_storage(dir.path(), options(Duration::ZERO)).await;
+        assert!(rows_as_of(&storage, AsOf::Epoch(3)).await.is_err());
+        assert_eq!(rows_as_of(&storage, AsOf::Epoch(4)).await.unwrap(), 0);
+        assert!(!rowset_path.exists() && !dv_path.exists());
+    }
+}
@@ -0,0 +1,508 @@
+//! The write-ahead log (WAL) of the storage.
+//!
+//! Syncing a rowset takes one sync for every column and index file, which is expensive for a
+//! small insert. Instead, a small rowset written on commit is not synced, and its rows are
+//! recorded in the WAL, which is synced according to [`WalSyncMode`]. On startup, the rowsets
+//! recorded in the WAL are rebuilt from their rows if they are still in the manifest, and those
+//! whose files and records are both lost in a crash are dropped.
+//!
+//! The WAL is split into files numbered in order. A checkpoint switches to a new file, syncs
+//! the rowsets recorded in the previous files, and then removes these files.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::rowset::sync_rowset;
+use super::{err, run_blocking, StorageResult};
+use crate::array::{ArrayBuilderImpl, DataChunk};
+use crate::catalog::{ColumnDesc, TableRefId};
+use crate::types::DataValue;
+
+/// Chunks with more rows are written into synced rowsets instead of being recorded in the WAL.
+pub const WAL_MAX_ROWS: usize = 4096;
+
+/// When to sync the WAL and the manifest on commit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalSyncMode {
+    /// Sync the WAL and then the manifest before every commit returns.
+    EveryCommit,
+
+    /// Wait for the interval before syncing the WAL and then the manifest, so that the commits
+    /// within the interval share one sync of each.
+    Group(Duration),
+
+    /// Never sync the WAL or the manifest on commit, but only on checkpoints. If the system
+    /// crashes, the commits since the last checkpoint may be lost, and the rowsets whose files
+    /// and WAL records are both lost are dropped on startup.
+    None,
+}
+
+/// The rows of a rowset recorded in the WAL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalRecord {
+    pub table_id: TableRefId,
+    pub rowset_id: u32,
+
+    /// Values of all columns.
+    pub columns: Vec<Vec<DataValue>>,
+}
+
+impl WalRecord {
+    pub fn new(table_id: TableRefId, rowset_id: u32, chunk: &DataChunk) -> Self {
+        let columns = (chunk.arrays().iter())
+            .map(|array| (0..array.len()).map(|i| array.get(i)).collect())
+            .collect();
+        WalRecord {
+            table_id,
+            rowset_id,
+            columns,
+        }
+    }
+
+    /// Convert the record back into the chunk that the rowset is built from.
+    pub fn to_chunk(&self, column_descs: &[ColumnDesc]) -> StorageResult<DataChunk> {
+        if self.columns.len() != column_descs.len() {
+            return Err(
+                anyhow!("WAL record of rowset {} mismatches columns", self.rowset_id).into(),
+            );
+        }
+        let arrays = (self.columns.iter().zip(column_descs))
+            .map(|(values, desc)| {
+                let mut builder = ArrayBuilderImpl::with_capacity(values.len(), desc.datatype());
+                for value in values {
+                    builder.push(value);
+                }
+                builder.finish()
+            })
+            .collect();
+        Ok(arrays)
+    }
+}
+
+/// The write-ahead log.
+pub struct Wal {
+    /// The directory of the WAL files.
+    dir: PathBuf,
+
+    sync_mode: WalSyncMode,
+
+    /// Shared with the blocking threads that write to the files.
+    inner: Arc<Mutex<WalInner>>,
+
+    /// Number of bytes appended to the WAL that are synced.
+    synced: AtomicU64,
+
+    /// Serializes the syncs, so that the commits waiting for a sync share the next one.
+    sync_lock: tokio::sync::Mutex<()>,
+
+    /// Serializes the checkpoints.
+    checkpoint_lock: tokio::sync::Mutex<()>,
+}
+
+struct WalInner {
+    /// The file being appended to.
+    file: File,
+
+    /// Number of the current file.
+    file_id: u64,
+
+    /// Size of the current file in bytes.
+    file_size: u64,
+
+    /// Number of bytes appended to the WAL since it is opened.
+    appended: u64,
+
+    /// Paths of the rowsets recorded in the WAL, which are not synced yet.
+    unsynced_rowsets: Vec<PathBuf>,
+}
+
+impl Wal {
+    /// Open the WAL in `dir`, and return all records in it.
+    ///
+    /// A partially written batch at the end of a file, which is left by a crash, is discarded.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        sync_mode: WalSyncMode,
+    ) -> StorageResult<(Self, Vec<WalRecord>)> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).map_err(err)?;
+        let file_ids = wal_file_ids(dir)?;
+        let mut records = vec![];
+        for &file_id in &file_ids {
+            records.extend(read_file(&wal_file_path(dir, file_id))?);
+        }
+        let file_id = file_ids.last().map_or(0, |id| id + 1);
+        let wal = Wal {
+            dir: dir.into(),
+            sync_mode,
+            inner: Arc::new(Mutex::new(WalInner {
+                file: create_file(dir, file_id)?,
+                file_id,
+                file_size: 0,
+                appended: 0,
+                unsynced_rowsets: vec![],
+            })),
+            synced: AtomicU64::new(0),
+            sync_lock: tokio::sync::Mutex::new(()),
+            checkpoint_lock: tokio::sync::Mutex::new(()),
+        };
+        Ok((wal, records))
+    }
+
+    /// Read all records in the WAL in `dir` without modifying it.
+    pub fn read(dir: impl AsRef<Path>) -> StorageResult<Vec<WalRecord>> {
+        let dir = dir.as_ref();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut records = vec![];
+        for file_id in wal_file_ids(dir)? {
+            records.extend(read_file(&wal_file_path(dir, file_id))?);
+        }
+        Ok(records)
+    }
+
+    /// Number of the oldest file of the WAL in `dir`, or `None` if there is no file. The rowsets
+    /// recorded in the earlier files are synced by checkpoints.
+    pub fn oldest_file_id(dir: impl AsRef<Path>) -> StorageResult<Option<u64>> {
+        let dir = dir.as_ref();
+        if !dir.exists() {
+            return Ok(None);
+        }
+        Ok(wal_file_ids(dir)?.first().copied())
+    }
+
+    /// Append the records of the unsynced rowsets at `rowset_paths` in one batch, and sync the
+    /// WAL according to the sync mode. Return the number of the file containing the batch.
+    pub async fn append(
+        &self,
+        records: &[WalRecord],
+        rowset_paths: Vec<PathBuf>,
+    ) -> StorageResult<u64> {
+        let mut line = serde_json::to_string(records).map_err(err)?;
+        line.push('\n');
+        let inner = self.inner.clone();
+        let (file_id, appended) = run_blocking(move || {
+            let mut inner = inner.lock().unwrap();
+            inner.file.write_all(line.as_bytes()).map_err(err)?;
+            inner.file_size += line.len() as u64;
+            inner.appended += line.len() as u64;
+            inner.unsynced_rowsets.extend(rowset_paths);
+            Ok((inner.file_id, inner.appended))
+        })
+        .await?;
+        match self.sync_mode {
+            WalSyncMode::EveryCommit => self.sync(appended, None).await?,
+            WalSyncMode::Group(interval) => self.sync(appended, Some(interval)).await?,
+            WalSyncMode::None => {}
+        }
+        Ok(file_id)
+    }
+
+    /// Sync the WAL until at least `appended` bytes are synced, after waiting for `delay`.
+    async fn sync(&self, appended: u64, delay: Option<Duration>) -> StorageResult<()> {
+        let _guard = self.sync_lock.lock().await;
+        if self.synced.load(SeqCst) >= appended {
+            return Ok(());
+        }
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let (file, appended) = {
+            let inner = self.inner.lock().unwrap();
+            (inner.file.try_clone().map_err(err)?, inner.appended)
+        };
+        run_blocking(move || file.sync_data().map_err(err)).await?;
+        self.synced.fetch_max(appended, SeqCst);
+        Ok(())
+    }
+
+    /// Size in bytes of the current WAL file.
+    pub fn file_size(&self) -> u64 {
+        self.inner.lock().unwrap().file_size
+    }
+
+    /// Sync all rowsets recorded in the WAL, and remove the WAL files that are no longer needed.
+    pub async fn checkpoint(&self) -> StorageResult<()> {
+        let _guard = self.checkpoint_lock.lock().await;
+
+        // switch to a new file, after which the old files are never appended
+        let (inner, dir) = (self.inner.clone(), self.dir.clone());
+        let (file_id, rowset_paths, appended) = run_blocking(move || {
+            let mut inner = inner.lock().unwrap();
+            inner.file.sync_data().map_err(err)?;
+            inner.file_id += 1;
+            inner.file = create_file(&dir, inner.file_id)?;
+            inner.file_size = 0;
+            let rowset_paths = std::mem::take(&mut inner.unsynced_rowsets);
+            Ok((inner.file_id, rowset_paths, inner.appended))
+        })
+        .await?;
+        self.synced.fetch_max(appended, SeqCst);
+        for (i, path) in rowset_paths.iter().enumerate() {
+            if let Err(e) = sync_rowset(path).await {
+                // the old files are kept until these rowsets are synced by the next checkpoint
+                let mut inner = self.inner.lock().unwrap();
+                inner.unsynced_rowsets.extend_from_slice(&rowset_paths[i..]);
+                return Err(e);
+            }
+        }
+        let dir = self.dir.clone();
+        run_blocking(move || {
+            for old_id in wal_file_ids(&dir)? {
+                if old_id < file_id {
+                    std::fs::remove_file(wal_file_path(&dir, old_id)).map_err(err)?;
+                }
+            }
+            Ok(())
+        })
+        .await?;
+        info!(
+            "WAL checkpoint synced {} rowsets, switched to file {}",
+            rowset_paths.len(),
+            file_id
+        );
+        Ok(())
+    }
+}
+
+fn wal_file_path(dir: &Path, file_id: u64) -> PathBuf {
+    dir.join(format!("{}.json", file_id))
+}
+
+/// Numbers of the WAL files in `dir` in ascending order.
+fn wal_file_ids(dir: &Path) -> StorageResult<Vec<u64>> {
+    let mut file_ids = vec![];
+    for entry in std::fs::read_dir(dir).map_err(err)? {
+        let path = entry.map_err(err)?.path();
+        if path.extension() != Some("json".as_ref()) {
+            continue;
+        }
+        if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+            file_ids.push(id);
+        }
+    }
+    Ok(file_ids.into_iter().sorted().collect())
+}
+
+/// Create a new WAL file, and sync the directory so that the file is never lost.
+fn create_file(dir: &Path, file_id: u64) -> StorageResult<File> {
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(wal_file_path(dir, file_id))
+        .map_err(err)?;
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(err)?;
+    Ok(file)
+}
+
+/// Read all records in a WAL file.
+fn read_file(path: &Path) -> StorageResult<Vec<WalRecord>> {
+    let mut reader = BufReader::new(File::open(path).map_err(err)?);
+    let mut records = vec![];
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(err)? == 0 {
+            break;
+        }
+        let batch = match line.strip_suffix('\n') {
+            Some(line) => serde_json::from_str::<Vec<WalRecord>>(line).ok(),
+            None => None,
+        };
+        match batch {
+            Some(batch) => records.extend(batch),
+            None if reader.fill_buf().map_err(err)?.is_empty() => {
+                warn!("discard a partially written batch at the end of {:?}", path);
+                break;
+            }
+            None => return Err(anyhow!("invalid WAL record in {:?}: {}", path, line).into()),
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::ArrayImpl;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage, scan};
+    use crate::storage::{StorageOptions, WAL_DIR_NAME};
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    fn record(rowset_id: u32) -> WalRecord {
+        let chunk: DataChunk = [ArrayImpl::Int32([Some(1), None].into_iter().collect())]
+            .into_iter()
+            .collect();
+        WalRecord::new(TableRefId::new(0, 1), rowset_id, &chunk)
+    }
+
+    #[test]
+    fn test_record_to_chunk() {
+        let column_descs = [DataTypeKind::Int(None).nullable().to_column()];
+        let record = record(0);
+        let chunk = record.to_chunk(&column_descs).unwrap();
+        assert_eq!(WalRecord::new(record.table_id, 0, &chunk), record);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let rowset_path = dir.path().join("rowset");
+        std::fs::create_dir(&rowset_path).unwrap();
+        std::fs::write(rowset_path.join("0.col"), b"data").unwrap();
+
+        let (wal, records) = Wal::open(dir.path(), WalSyncMode::EveryCommit).unwrap();
+        assert!(records.is_empty());
+        wal.append(&[record(0)], vec![rowset_path]).await.unwrap();
+        drop(wal);
+
+        // Simulate a crash in the middle of writing a batch.
+        let path = wal_file_path(dir.path(), 0);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[{\"table_id\"").unwrap();
+        drop(file);
+
+        let (wal, records) = Wal::open(dir.path(), WalSyncMode::None).unwrap();
+        assert_eq!(records, vec![record(0)]);
+        wal.checkpoint().await.unwrap();
+        wal.append(&[record(1)], vec![]).await.unwrap();
+        drop(wal);
+
+        let (_, records) = Wal::open(dir.path(), WalSyncMode::None).unwrap();
+        assert_eq!(records, vec![record(1)]);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_group_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let mode = WalSyncMode::Group(Duration::from_millis(10));
+        let (wal, _) = Wal::open(dir.path(), mode).unwrap();
+        let records = (0..2).map(|i| [record(i)]).collect_vec();
+        let (r0, r1) = tokio::join!(
+            wal.append(&records[0], vec![]),
+            wal.append(&records[1], vec![])
+        );
+        r0.unwrap();
+        r1.unwrap();
+        assert_eq!(wal.synced.load(SeqCst), wal.inner.lock().unwrap().appended);
+        drop(wal);
+
+        let (_, records) = Wal::open(dir.path(), mode).unwrap();
+        assert_eq!(records, vec![record(0), record(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_wal_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let column_descs = [
+            DataTypeKind::Int(None).not_null().to_column(),
+            ColumnDesc::new(DataTypeKind::Int(None).not_null(), true),
+        ];
+        let chunk: DataChunk = [
+            ArrayImpl::Int32([30, 10, 20].into_iter().collect()),
+            ArrayImpl::Int32([3, 1, 2].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        let storage = open_test_storage(dir.path(), options()).await;
+        let table = create_test_table(&storage, id, &column_descs, [chunk]).await;
+
+        // delete the row with key 1, which is the first row of the sorted rowset
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[1]).await.unwrap();
+        let (_, handles) = iter.next_batch_with_handles(1).await.unwrap().unwrap();
+        assert_eq!(handles[0].row_offset, 0);
+        txn.delete(&handles).unwrap();
+        drop(iter);
+        txn.commit().await.unwrap();
+        let rowset_path = table.rowset_path_of(0);
+        drop(table);
+        drop(storage);
+
+        // Simulate a crash before the files of the rowset are synced.
+        std::fs::remove_dir_all(&rowset_path).unwrap();
+
+        // The rowset is rebuilt from the WAL, with the same row order as before.
+        for _ in 0..2 {
+            let storage = open_test_storage(dir.path(), options()).await;
+            let table = storage.get_table(id).unwrap();
+            let mut txn = table.read().await.unwrap();
+            let chunk = DataChunk::concat(&scan(&mut txn, &[1, 0]).await.unwrap());
+            let expected: DataChunk = [
+                ArrayImpl::Int32([2, 3].into_iter().collect()),
+                ArrayImpl::Int32([20, 30].into_iter().collect()),
+            ]
+            .into_iter()
+            .collect();
+            assert!(chunk == expected);
+            txn.commit().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lost_rowset() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = || StorageOptions {
+            wal_sync_mode: WalSyncMode::None,
+            compaction_interval: None,
+            ..Default::default()
+        };
+        let id = TableRefId::new(0, 0);
+        let storage = open_test_storage(dir.path(), options()).await;
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        create_test_table(&storage, id, &column_descs, [int_chunk([1])]).await;
+        drop(storage);
+
+        // the first rowset is synced by the checkpoint on opening
+        let storage = open_test_storage(dir.path(), options()).await;
+        let table = storage.get_table(id).unwrap();
+        let mut txn = table.write().await.unwrap();
+        txn.append(int_chunk([2, 3])).await.unwrap();
+        txn.commit().await.unwrap();
+        let mut txn = table.write().await.unwrap();
+        let mut iter = txn.iter(&[0]).await.unwrap();
+        let mut handles = vec![];
+        while let Some((_, batch)) = iter.next_batch_with_handles(3).await.unwrap() {
+            handles.extend(batch);
+        }
+        drop(iter);
+        txn.delete(&handles[2..]).unwrap();
+        txn.commit().await.unwrap();
+        let rowset_path = table.rowset_path_of(1);
+        drop(table);
+        drop(storage);
+
+        // Simulate a crash before the WAL and the second rowset are synced.
+        std::fs::remove_dir_all(dir.path().join(WAL_DIR_NAME)).unwrap();
+        std::fs::write(rowset_path.join("0.col"), b"").unwrap();
+
+        // The second rowset is dropped with its delete vector, also after the WAL is checkpointed.
+        for _ in 0..2 {
+            let storage = open_test_storage(dir.path(), options()).await;
+            let table = storage.get_table(id).unwrap();
+            let mut txn = table.read().await.unwrap();
+            let chunk = DataChunk::concat(&scan(&mut txn, &[0]).await.unwrap());
+            assert_eq!(chunk.arrays()[0].get(0), DataValue::Int32(1));
+            assert_eq!(chunk.cardinality(), 1);
+            txn.commit().await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Skip blocks with zone maps.
+//!
+//! The block index records the null count, min and max of each block. A scan with some
+//! [`ColumnRange`]s skips the rows of every block whose zone map shows that no row of the block
+//! is in a range. These rows may be read anyway if they share a block with other rows, so the
+//! ranges only reduce the rows to read, and the scanned rows should still be filtered.
+//!
+//! For a table with a primary key, the ranges of the key are also looked up in the sort index
+//! of each rowset, which narrows the scan down to the rows around the keys in the range.
+
+use std::mem::discriminant;
+use std::ops::{Bound, Range};
+
+use super::index::BlockIndex;
+use super::rowset::DiskRowset;
+use crate::catalog::ColumnId;
+use crate::types::DataValue;
+
+/// A range of non-NULL values of a column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnRange {
+    pub column_id: ColumnId,
+    pub start: Bound<DataValue>,
+    pub end: Bound<DataValue>,
+}
+
+impl std::fmt::Display for ColumnRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} ", self.column_id)?;
+        match &self.start {
+            Bound::Included(v) => write!(f, "[{}", v.to_string())?,
+            Bound::Excluded(v) => write!(f, "({}", v.to_string())?,
+            Bound::Unbounded => write!(f, "(-inf")?,
+        }
+        match &self.end {
+            Bound::Included(v) => write!(f, ", {}]", v.to_string()),
+            Bound::Excluded(v) => write!(f, ", {})", v.to_string()),
+            Bound::Unbounded => write!(f, ", +inf)"),
+        }
+    }
+}
+
+impl ColumnRange {
+    /// Returns false if no row in the block can be in the range.
+    pub fn may_match(&self, block: &BlockIndex) -> bool {
+        if block.null_count == block.row_count {
+            return false;
+        }
+        let comparable = |v: &DataValue| discriminant(v) == discriminant(&block.min);
+        let above_start = match &self.start {
+            Bound::Included(v) if comparable(v) => block.max >= *v,
+            Bound::Excluded(v) if comparable(v) => block.max > *v,
+            _ => true,
+        };
+        let below_end = match &self.end {
+            Bound::Included(v) if comparable(v) => block.min <= *v,
+            Bound::Excluded(v) if comparable(v) => block.min < *v,
+            _ => true,
+        };
+        above_start && below_end
+    }
+}
+
+/// Find the rows of a rowset that are not in all `ranges` according to the zone maps, and the
+/// sort index for the ranges of the primary key, together with the rows out of `rows`.
+///
+/// Return the sorted and disjoint row ranges `[start, end)`.
+pub fn rows_to_skip(
+    rowset: &DiskRowset,
+    ranges: &[ColumnRange],
+    rows: Range<u32>,
+) -> Vec<(u32, u32)> {
+    let row_count = rowset.row_count() as u32;
+    let mut skipped = vec![(0, rows.start), (rows.end, row_count)];
+    for range in ranges {
+        if let Some((key_idx, sort_index)) = rowset.sort_index() {
+            if range.column_id as usize == key_idx {
+                let (start, end) = sort_index.seek(&range.start, &range.end, row_count);
+                skipped.push((0, start));
+                skipped.push((end, row_count));
+            }
+        }
+        let mut start = 0;
+        for block in rowset.block_indexes(range.column_id as usize) {
+            let end = start + block.row_count;
+            if !range.may_match(block) {
+                skipped.push((start, end));
+            }
+            start = end;
+        }
+    }
+    skipped.retain(|(start, end)| start < end);
+    skipped.sort_unstable();
+
+    // merge the overlapping and adjacent ranges
+    let mut merged: Vec<(u32, u32)> = vec![];
+    for (start, end) in skipped {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::TableRefId;
+    use crate::storage::tests::{create_test_table, int_chunk, open_test_storage};
+    use crate::storage::StorageOptions;
+    use crate::types::{DataTypeExt, DataTypeKind};
+
+    fn block(null_count: u32, min: DataValue, max: DataValue) -> BlockIndex {
+        BlockIndex {
+            offset: 0,
+            length: 0,
+            checksum: 0,
+            row_count: 10,
+            null_count,
+            first_key: min.clone(),
+            min,
+            max,
+        }
+    }
+
+    #[test]
+    fn test_may_match() {
+        let range = ColumnRange {
+            column_id: 0,
+            start: Bound::Excluded(DataValue::Int32(5)),
+            end: Bound::Included(DataValue::Int32(10)),
+        };
+        let int = DataValue::Int32;
+        assert!(range.may_match(&block(0, int(0), int(6))));
+        assert!(range.may_match(&block(0, int(10), int(20))));
+        assert!(!range.may_match(&block(0, int(0), int(5))));
+        assert!(!range.may_match(&block(0, int(11), int(20))));
+        assert!(!range.may_match(&block(10, DataValue::Null, DataValue::Null)));
+
+        // values of another type never skip the block
+        let range = ColumnRange {
+            column_id: 0,
+            start: Bound::Included(DataValue::Float64(100.0)),
+            end: Bound::Unbounded,
+        };
+        assert!(range.may_match(&block(0, int(0), int(5))));
+    }
+
+    #[tokio::test]
+    async fn test_zone_map() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_test_storage(dir.path(), StorageOptions::default()).await;
+        let id = TableRefId::new(0, 0);
+        let column_descs = [DataTypeKind::Int(None).not_null().to_column()];
+        let chunks = [int_chunk(0..100_000), int_chunk(200_000..200_010)];
+        let table = create_test_table(&storage, id, &column_descs, chunks).await;
+
+        let mut txn = table.read().await.unwrap();
+        let ranges = [ColumnRange {
+            column_id: 0,
+            start: Bound::Included(DataValue::Int32(50_000)),
+            end: Bound::Excluded(DataValue::Int32(50_010)),
+        }];
+        let mut iter = txn.iter_with_ranges(&[0], &ranges).await.unwrap();
+        let mut values = vec![];
+        while let Some(chunk) = iter.next_batch(1024).await.unwrap() {
+            values.extend((0..chunk.cardinality()).map(|i| chunk.arrays()[0].get(i)));
+        }
+        txn.commit().await.unwrap();
+
+        assert!((50_000..50_010).all(|v| values.contains(&DataValue::Int32(v))));
+        assert!(values.len() < 50_000);
+        // only one block of the first rowset is read, and the second rowset is skipped
+        assert_eq!(storage.block_cache_stats().misses, 1);
+    }
+}
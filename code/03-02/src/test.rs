@@ -1,27 +1,130 @@
 use std::path::Path;
+use std::time::Duration;
 
 use tempfile::tempdir;
 use test_case::test_case;
 
 use crate::array::DataChunk;
+use crate::db::IN_MEMORY_PATH;
 use crate::storage::StorageOptions;
 use crate::types::DataValue;
 use crate::{Database, Error};
 
+#[test_case("01-05.slt")]
 #[test_case("03-01.slt")]
 #[test_case("03-02.slt")]
+#[test_case("03-02-null.slt")]
+#[test_case("03-02-types.slt")]
+#[test_case("03-02-pruning.slt")]
+#[test_case("03-02-delete.slt")]
+#[test_case("03-02-filter.slt")]
+#[test_case("03-02-txn.slt")]
+#[test_case("03-02-primary-key.slt")]
+#[test_case("03-02-drop.slt")]
 fn test(name: &str) {
+    let tempdir = tempdir().unwrap();
+    run_script(name, options(tempdir.path()));
+}
+
+/// Run the scripts that do not depend on the disk storage against an in-memory database.
+#[test_case("01-05.slt")]
+#[test_case("03-01.slt")]
+#[test_case("03-02.slt")]
+#[test_case("03-02-null.slt")]
+#[test_case("03-02-types.slt")]
+#[test_case("03-02-delete.slt")]
+#[test_case("03-02-filter.slt")]
+#[test_case("03-02-txn.slt")]
+#[test_case("03-02-drop.slt")]
+fn test_in_memory(name: &str) {
+    run_script(name, options(Path::new(IN_MEMORY_PATH)));
+}
+
+/// Run the scripts that read old versions of tables, which are retained for an hour.
+#[test_case("03-02-time-travel.slt")]
+fn test_time_travel(name: &str) {
+    let tempdir = tempdir().unwrap();
+    let options = StorageOptions {
+        version_retention: Duration::from_secs(3600),
+        ..options(tempdir.path())
+    };
+    run_script(name, options);
+}
+
+/// Run the scripts that scan multiple morsels in parallel tasks.
+#[test_case("03-02-parallel-scan.slt")]
+fn test_parallel_scan(name: &str) {
+    let tempdir = tempdir().unwrap();
+    let options = StorageOptions {
+        scan_parallelism: 4,
+        ..options(tempdir.path())
+    };
+    run_script(name, options);
+}
+
+/// Run each script against a freshly reopened database on the same directory.
+#[test_case(&["03-02-restart-1.slt", "03-02-restart-2.slt"])]
+#[test_case(&["03-02-drop-restart-1.slt", "03-02-drop-restart-2.slt"])]
+fn test_restart(names: &[&str]) {
+    let tempdir = tempdir().unwrap();
+    for name in names {
+        run_script(name, options(tempdir.path()));
+    }
+}
+
+/// Run the script `name` against a new database opened with `options`.
+fn run_script(name: &str, options: StorageOptions) {
     init_logger();
     let script = std::fs::read_to_string(Path::new("../sql").join(name)).unwrap();
-    let tempdir = tempdir().unwrap();
-    let mut tester = sqllogictest::Runner::new(Database::new(StorageOptions {
-        base_path: tempdir.path().into(),
-    }));
+    let mut tester = sqllogictest::Runner::new(Database::new(options).unwrap());
     if let Err(err) = tester.run_script(&script) {
-        panic!("{}", err);
+        panic!("{}: {}", name, err);
+    }
+}
+
+/// The default options of a database at `base_path`.
+fn options(base_path: &Path) -> StorageOptions {
+    StorageOptions {
+        base_path: base_path.into(),
+        ..Default::default()
     }
 }
 
+/// Back up a database while it is being written, and restore the backup as new databases.
+#[test]
+fn test_backup() {
+    init_logger();
+    let tempdir = tempdir().unwrap();
+    let path = |name: &str| tempdir.path().join(name);
+    let rows = |db: &Database, sql: &str| {
+        let chunks = db.run(sql).unwrap();
+        chunks.iter().map(datachunk_to_string).collect::<String>()
+    };
+    let backup_sql = format!("BACKUP TO '{}'", path("backup").display());
+
+    let db = Database::new(options(&path("db"))).unwrap();
+    db.run("CREATE TABLE t (a INT NOT NULL); INSERT INTO t VALUES (1), (2), (3)")
+        .unwrap();
+    db.run("DELETE FROM t WHERE a = 2").unwrap();
+    // the transaction running during the backup is not included
+    db.run("BEGIN; INSERT INTO t VALUES (4)").unwrap();
+    db.run(&backup_sql).unwrap();
+    db.run("COMMIT").unwrap();
+    assert!(db.run(&backup_sql).is_err());
+    drop(db);
+
+    let db = Database::restore(path("backup"), options(&path("restored-1"))).unwrap();
+    assert_eq!(rows(&db, "SELECT a FROM t"), "1\n3\n");
+    db.run("INSERT INTO t VALUES (5)").unwrap();
+    assert_eq!(rows(&db, "SELECT a FROM t"), "1\n3\n5\n");
+    drop(db);
+
+    // the backup is not changed by the restored database
+    assert!(Database::restore(path("backup"), options(&path("restored-1"))).is_err());
+    let db = Database::restore(path("backup"), options(&path("restored-2"))).unwrap();
+    assert_eq!(rows(&db, "SELECT a FROM t"), "1\n3\n");
+}
+
 impl sqllogictest::DB for Database {
     type Error = Error;
     fn run(&self, sql: &str) -> Result<String, Self::Error> {
@@ -1,9 +1,10 @@
 //! Defination of data types.
 
+use serde::{Deserialize, Serialize};
 pub use sqlparser::ast::DataType as DataTypeKind;
 
 /// Data type with nullable.
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DataType {
     kind: DataTypeKind,
     nullable: bool,
@@ -52,7 +53,7 @@ impl DataTypeExt for DataTypeKind {
 }
 
 /// Primitive SQL value.
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum DataValue {
     // NOTE: Null comes first.
     // => NULL is less than any non-NULL values